@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet};
+use crate::ast::{NodoSentencia, Patron, Sentencia, Tipo};
+use crate::diagnostico::{self, Diagnostico};
+use crate::lexico::Posicion;
+
+// Resolución de los alias de tipo (`tipo Dinero = número`). Se hace una vez,
+// justo después del análisis sintáctico: cada nombre de alias que aparece en
+// un tipo se sustituye por `Tipo::Alias`, que conserva el nombre para los
+// mensajes y guarda el tipo al que se refiere. Así el verificador y el
+// intérprete no necesitan conocer los alias.
+//
+// Los alias valen en todo el programa, como los objetos, sin importar dónde
+// se definan.
+
+/// Resuelve los alias del programa y devuelve todos los errores encontrados.
+pub fn resolver(programa: &mut [Sentencia]) -> Result<(), Vec<Diagnostico>> {
+    let mut resolutor = Resolutor {
+        definiciones: HashMap::new(),
+        definidos: HashMap::new(),
+        resueltos: HashMap::new(),
+        invalidos: HashSet::new(),
+        errores: Vec::new(),
+    };
+    resolutor.registrar(programa);
+    let mut nombres: Vec<String> = resolutor.definiciones.keys().cloned().collect();
+    nombres.sort_by_key(|nombre| {
+        let pos = resolutor.definiciones[nombre].1;
+        (pos.linea, pos.columna)
+    });
+    for nombre in nombres {
+        resolutor.resolver_alias(&nombre, &mut Vec::new());
+    }
+    resolutor.sentencias(programa);
+    if resolutor.errores.is_empty() {
+        Ok(())
+    } else {
+        Err(resolutor.errores)
+    }
+}
+
+struct Resolutor {
+    /// Tipo escrito en cada `tipo Nombre = ...` y dónde se definió.
+    definiciones: HashMap<String, (Tipo, Posicion)>,
+    /// Objetos y enumeraciones, cuyos nombres no pueden usarse como alias.
+    definidos: HashMap<String, &'static str>,
+    /// Tipo al que se refiere cada alias, con sus propios alias ya resueltos.
+    resueltos: HashMap<String, Tipo>,
+    /// Alias con errores ya informados, para no repetirlos al resolver los
+    /// demás alias del mismo ciclo.
+    invalidos: HashSet<String>,
+    errores: Vec<Diagnostico>,
+}
+
+impl Resolutor {
+    fn error(&mut self, pos: Posicion, mensaje: impl Into<String>) {
+        self.errores.push(Diagnostico::en(diagnostico::ALIAS_INVALIDO, pos, mensaje));
+    }
+
+    fn registrar(&mut self, sentencias: &[Sentencia]) {
+        for sentencia in sentencias {
+            match &sentencia.nodo {
+                NodoSentencia::Alias { nombre, tipo } => {
+                    if let Some((_, anterior)) = self.definiciones.get(nombre) {
+                        let mensaje = format!(
+                            "El tipo '{}' ya está definido en la línea {}",
+                            nombre, anterior.linea
+                        );
+                        self.error(sentencia.pos, mensaje);
+                    } else {
+                        self.definiciones.insert(nombre.clone(), (tipo.clone(), sentencia.pos));
+                    }
+                }
+                NodoSentencia::Objeto { nombre, .. } => {
+                    self.definidos.insert(nombre.clone(), "objeto");
+                }
+                NodoSentencia::Enumeracion { nombre, .. } => {
+                    self.definidos.insert(nombre.clone(), "enumeración");
+                }
+                NodoSentencia::Funcion { cuerpo, .. }
+                | NodoSentencia::Mientras { cuerpo, .. }
+                | NodoSentencia::Hacer { cuerpo, .. }
+                | NodoSentencia::Para { cuerpo, .. }
+                | NodoSentencia::ParaCada { cuerpo, .. } => self.registrar(cuerpo),
+                NodoSentencia::Si { ramas, sino } => {
+                    for (_, cuerpo) in ramas {
+                        self.registrar(cuerpo);
+                    }
+                    if let Some(cuerpo) = sino {
+                        self.registrar(cuerpo);
+                    }
+                }
+                NodoSentencia::Segun { casos, otro, .. } => {
+                    for caso in casos {
+                        self.registrar(&caso.cuerpo);
+                    }
+                    if let Some(cuerpo) = otro {
+                        self.registrar(cuerpo);
+                    }
+                }
+                NodoSentencia::Intentar { cuerpo, atrapar, finalmente } => {
+                    self.registrar(cuerpo);
+                    if let Some(atrapar) = atrapar {
+                        self.registrar(&atrapar.cuerpo);
+                    }
+                    if let Some(cuerpo) = finalmente {
+                        self.registrar(cuerpo);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Resuelve un alias y los que use. `cadena` son los alias que se están
+    /// resolviendo, para detectar los ciclos.
+    fn resolver_alias(&mut self, nombre: &str, cadena: &mut Vec<String>) -> Option<Tipo> {
+        if let Some(tipo) = self.resueltos.get(nombre) {
+            return Some(tipo.clone());
+        }
+        if self.invalidos.contains(nombre) {
+            return None;
+        }
+        let (tipo, pos) = self.definiciones.get(nombre).cloned()?;
+        if let Some(inicio) = cadena.iter().position(|n| n == nombre) {
+            // Solo se informa una vez, desde el alias donde empieza el ciclo
+            if inicio == 0 {
+                let mut ciclo = cadena.clone();
+                ciclo.push(nombre.to_string());
+                self.error(pos, format!("El tipo '{}' se define a sí mismo: {}", nombre, ciclo.join(" -> ")));
+            }
+            return None;
+        }
+        if let Some(clase) = self.definidos.get(nombre) {
+            let mensaje = format!("El tipo '{}' ya está definido como {}", nombre, clase);
+            self.error(pos, mensaje);
+            self.invalidos.insert(nombre.to_string());
+            return None;
+        }
+        cadena.push(nombre.to_string());
+        let resuelto = self.resolver_tipo(&tipo, pos, cadena);
+        cadena.pop();
+        let Some(resuelto) = resuelto else {
+            self.invalidos.insert(nombre.to_string());
+            return None;
+        };
+        self.resueltos.insert(nombre.to_string(), resuelto.clone());
+        Some(resuelto)
+    }
+
+    /// Sustituye los alias dentro de `tipo`, también en los tipos de los
+    /// elementos de una lista y en los opcionales.
+    fn resolver_tipo(&mut self, tipo: &Tipo, pos: Posicion, cadena: &mut Vec<String>) -> Option<Tipo> {
+        let resuelto = match tipo {
+            Tipo::Nombre(nombre) if self.definiciones.contains_key(nombre) => {
+                let tipo = self.resolver_alias(nombre, cadena)?;
+                Tipo::Alias { nombre: nombre.clone(), tipo: Box::new(tipo) }
+            }
+            Tipo::Lista(Some(elemento)) => {
+                Tipo::Lista(Some(Box::new(self.resolver_tipo(elemento, pos, cadena)?)))
+            }
+            Tipo::Opcional(interior) => {
+                let interior = self.resolver_tipo(interior, pos, cadena)?;
+                if *interior.resuelto() == Tipo::Vacio {
+                    self.error(pos, format!("El tipo {} es vacio y no puede ser opcional", interior));
+                    return None;
+                }
+                Tipo::Opcional(Box::new(interior))
+            }
+            otro => otro.clone(),
+        };
+        Some(resuelto)
+    }
+
+    fn tipo(&mut self, tipo: &mut Tipo, pos: Posicion) {
+        if let Some(resuelto) = self.resolver_tipo(tipo, pos, &mut Vec::new()) {
+            *tipo = resuelto;
+        }
+    }
+
+    fn sentencias(&mut self, sentencias: &mut [Sentencia]) {
+        for sentencia in sentencias {
+            self.sentencia(sentencia);
+        }
+    }
+
+    fn sentencia(&mut self, sentencia: &mut Sentencia) {
+        let pos = sentencia.pos;
+        match &mut sentencia.nodo {
+            NodoSentencia::Declaracion { tipo: Some(tipo), .. } => self.tipo(tipo, pos),
+            NodoSentencia::Alias { tipo, .. } => self.tipo(tipo, pos),
+            NodoSentencia::Enumeracion { variantes, .. } => {
+                for variante in variantes.iter_mut() {
+                    for tipo in variante.datos.iter_mut() {
+                        self.tipo(tipo, variante.pos);
+                    }
+                }
+            }
+            NodoSentencia::Funcion { parametros, tipo_retorno, cuerpo, .. } => {
+                for parametro in parametros.iter_mut() {
+                    self.tipo(&mut parametro.tipo, parametro.pos);
+                }
+                self.tipo(tipo_retorno, pos);
+                self.sentencias(cuerpo);
+            }
+            NodoSentencia::ParaCada { clave, variable, cuerpo, .. } => {
+                for variable in clave.iter_mut().chain([variable]) {
+                    if let Some(tipo) = &mut variable.tipo {
+                        self.tipo(tipo, pos);
+                    }
+                }
+                self.sentencias(cuerpo);
+            }
+            NodoSentencia::Para { inicio, paso, cuerpo, .. } => {
+                self.sentencia(inicio);
+                self.sentencia(paso);
+                self.sentencias(cuerpo);
+            }
+            NodoSentencia::Mientras { cuerpo, .. } | NodoSentencia::Hacer { cuerpo, .. } => self.sentencias(cuerpo),
+            NodoSentencia::Si { ramas, sino } => {
+                for (_, cuerpo) in ramas {
+                    self.sentencias(cuerpo);
+                }
+                if let Some(cuerpo) = sino {
+                    self.sentencias(cuerpo);
+                }
+            }
+            NodoSentencia::Segun { casos, otro, .. } => {
+                for caso in casos.iter_mut() {
+                    for patron in caso.patrones.iter_mut() {
+                        self.patron(patron, caso.pos);
+                    }
+                    self.sentencias(&mut caso.cuerpo);
+                }
+                if let Some(cuerpo) = otro {
+                    self.sentencias(cuerpo);
+                }
+            }
+            NodoSentencia::Intentar { cuerpo, atrapar, finalmente } => {
+                self.sentencias(cuerpo);
+                if let Some(atrapar) = atrapar {
+                    self.sentencias(&mut atrapar.cuerpo);
+                }
+                if let Some(cuerpo) = finalmente {
+                    self.sentencias(cuerpo);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn patron(&mut self, patron: &mut Patron, pos: Posicion) {
+        match patron {
+            Patron::Tipo { tipo, .. } => self.tipo(tipo, pos),
+            Patron::Lista(patrones) => {
+                for patron in patrones.iter_mut() {
+                    self.patron(patron, pos);
+                }
+            }
+            Patron::Jsn(campos) => {
+                for (_, patron) in campos.iter_mut() {
+                    self.patron(patron, pos);
+                }
+            }
+            Patron::Variante { datos: Some(patrones), .. } => {
+                for patron in patrones.iter_mut() {
+                    self.patron(patron, pos);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,490 @@
+use std::fmt;
+use crate::lexico::Posicion;
+
+#[derive(Clone, Debug)]
+pub enum Tipo {
+    Vacio,
+    Entero,
+    Numero,
+    Cadena,
+    Bool,
+    /// `lista` o `lista<T>`; el tipo de los elementos es opcional.
+    Lista(Option<Box<Tipo>>),
+    Jsn,
+    /// Tipo definido por el usuario con `objeto`.
+    Nombre(String),
+    /// `T?`: un valor de tipo `T` o `nulo`.
+    Opcional(Box<Tipo>),
+    /// Tipo del literal `nulo`. No se puede declarar; solo aparece al
+    /// verificar las expresiones.
+    Nulo,
+    /// Nombre definido con `tipo Nombre = ...`, ya resuelto al tipo que
+    /// representa. Se muestra con su nombre en los mensajes.
+    Alias {
+        nombre: String,
+        tipo: Box<Tipo>,
+    },
+}
+
+impl Tipo {
+    /// El tipo sin alias: el que determina qué valores se aceptan.
+    pub fn resuelto(&self) -> &Tipo {
+        match self {
+            Tipo::Alias { tipo, .. } => tipo.resuelto(),
+            otro => otro,
+        }
+    }
+}
+
+/// Dos tipos son iguales si lo son sus tipos resueltos: `Dinero` es igual a
+/// `número` si `tipo Dinero = número`.
+impl PartialEq for Tipo {
+    fn eq(&self, otro: &Tipo) -> bool {
+        match (self.resuelto(), otro.resuelto()) {
+            (Tipo::Lista(a), Tipo::Lista(b)) => a == b,
+            (Tipo::Opcional(a), Tipo::Opcional(b)) => a == b,
+            (Tipo::Nombre(a), Tipo::Nombre(b)) => a == b,
+            (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+        }
+    }
+}
+
+impl fmt::Display for Tipo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tipo::Vacio => write!(f, "vacio"),
+            Tipo::Entero => write!(f, "entero"),
+            Tipo::Numero => write!(f, "número"),
+            Tipo::Cadena => write!(f, "cadena"),
+            Tipo::Bool => write!(f, "bool"),
+            Tipo::Lista(None) => write!(f, "lista"),
+            Tipo::Lista(Some(elemento)) => write!(f, "lista<{}>", elemento),
+            Tipo::Jsn => write!(f, "jsn"),
+            Tipo::Nombre(nombre) => write!(f, "{}", nombre),
+            Tipo::Opcional(tipo) => write!(f, "{}?", tipo),
+            Tipo::Nulo => write!(f, "nulo"),
+            Tipo::Alias { nombre, .. } => write!(f, "{}", nombre),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperadorBinario {
+    Suma,
+    Resta,
+    Multiplicacion,
+    Division,
+    Modulo,
+    Igual,
+    Distinto,
+    Menor,
+    MenorIgual,
+    Mayor,
+    MayorIgual,
+    Y,
+    O,
+    /// `a ?? b`: `a` si no es nulo, si no `b`.
+    Coalescencia,
+}
+
+impl fmt::Display for OperadorBinario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let simbolo = match self {
+            OperadorBinario::Suma => "+",
+            OperadorBinario::Resta => "-",
+            OperadorBinario::Multiplicacion => "*",
+            OperadorBinario::Division => "/",
+            OperadorBinario::Modulo => "%",
+            OperadorBinario::Igual => "==",
+            OperadorBinario::Distinto => "!=",
+            OperadorBinario::Menor => "<",
+            OperadorBinario::MenorIgual => "<=",
+            OperadorBinario::Mayor => ">",
+            OperadorBinario::MayorIgual => ">=",
+            OperadorBinario::Y => "&&",
+            OperadorBinario::O => "||",
+            OperadorBinario::Coalescencia => "??",
+        };
+        write!(f, "{}", simbolo)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperadorUnario {
+    /// `-x`
+    Negativo,
+    /// `!x`
+    Negacion,
+}
+
+#[derive(Clone, Debug)]
+pub struct Expresion {
+    pub nodo: NodoExpresion,
+    pub pos: Posicion,
+}
+
+impl Expresion {
+    /// Variable a la que pertenece el valor de la expresión: la propia
+    /// variable, o la que contiene el elemento o la propiedad, como `m` en
+    /// `m[0]` o en `p.items`.
+    pub fn variable_raiz(&self) -> Option<&str> {
+        match &self.nodo {
+            NodoExpresion::Variable(nombre) => Some(nombre),
+            NodoExpresion::Indice { objeto, .. } | NodoExpresion::Miembro { objeto, .. } => objeto.variable_raiz(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum NodoExpresion {
+    Entero(i64),
+    Numero(f64),
+    Cadena(String),
+    /// Cadena con expresiones intercaladas: `"Hola ${nombre}"`.
+    Interpolacion(Vec<ParteCadena>),
+    Bool(bool),
+    Nulo,
+    Variable(String),
+    Lista(Vec<Expresion>),
+    Jsn(Vec<(String, Expresion)>),
+    Unaria {
+        operador: OperadorUnario,
+        operando: Box<Expresion>,
+    },
+    Binaria {
+        operador: OperadorBinario,
+        izquierda: Box<Expresion>,
+        derecha: Box<Expresion>,
+    },
+    Ternaria {
+        condicion: Box<Expresion>,
+        si_verdadero: Box<Expresion>,
+        si_falso: Box<Expresion>,
+    },
+    Llamada {
+        funcion: Box<Expresion>,
+        argumentos: Vec<Expresion>,
+    },
+    /// `objeto.nombre` u `objeto?.nombre`, que da nulo si el objeto es nulo.
+    Miembro {
+        objeto: Box<Expresion>,
+        nombre: String,
+        opcional: bool,
+    },
+    Indice {
+        objeto: Box<Expresion>,
+        indice: Box<Expresion>,
+    },
+    /// `nuevo Tipo(argumentos)`
+    Nuevo {
+        tipo: String,
+        argumentos: Vec<Expresion>,
+    },
+    /// `desde..hasta` o `desde..=hasta`, con `paso n` opcional: los enteros
+    /// del rango, como lista o recorridos por `para`.
+    Rango {
+        desde: Box<Expresion>,
+        hasta: Box<Expresion>,
+        inclusivo: bool,
+        paso: Option<Box<Expresion>>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub enum ParteCadena {
+    Texto(String),
+    Expresion(Expresion),
+}
+
+fn escribir_lista(f: &mut fmt::Formatter<'_>, elementos: &[Expresion]) -> fmt::Result {
+    for (i, elemento) in elementos.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", elemento)?;
+    }
+    Ok(())
+}
+
+/// Escribe el contenido de una cadena con las secuencias de escape que el
+/// analizador léxico reconoce.
+fn escapar_cadena(texto: &str) -> String {
+    let mut salida = String::with_capacity(texto.len());
+    let mut caracteres = texto.chars().peekable();
+    while let Some(c) = caracteres.next() {
+        match c {
+            '\\' => salida.push_str("\\\\"),
+            '"' => salida.push_str("\\\""),
+            '\n' => salida.push_str("\\n"),
+            '\t' => salida.push_str("\\t"),
+            '\r' => salida.push_str("\\r"),
+            '\0' => salida.push_str("\\0"),
+            '$' if caracteres.peek() == Some(&'{') => salida.push_str("\\$"),
+            otro => salida.push(otro),
+        }
+    }
+    salida
+}
+
+/// Representa la expresión como código Quetzal equivalente.
+impl fmt::Display for Expresion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.nodo {
+            NodoExpresion::Entero(i) => write!(f, "{}", i),
+            NodoExpresion::Numero(n) => write!(f, "{:?}", n),
+            NodoExpresion::Cadena(c) => write!(f, "\"{}\"", escapar_cadena(c)),
+            NodoExpresion::Interpolacion(partes) => {
+                write!(f, "\"")?;
+                for parte in partes {
+                    match parte {
+                        ParteCadena::Texto(texto) => write!(f, "{}", escapar_cadena(texto))?,
+                        ParteCadena::Expresion(expresion) => write!(f, "${{{}}}", expresion)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            NodoExpresion::Bool(true) => write!(f, "verdadero"),
+            NodoExpresion::Bool(false) => write!(f, "falso"),
+            NodoExpresion::Nulo => write!(f, "nulo"),
+            NodoExpresion::Variable(nombre) => write!(f, "{}", nombre),
+            NodoExpresion::Lista(elementos) => {
+                write!(f, "[")?;
+                escribir_lista(f, elementos)?;
+                write!(f, "]")
+            }
+            NodoExpresion::Jsn(campos) => {
+                write!(f, "{{")?;
+                for (i, (clave, valor)) in campos.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "\"{}\": {}", clave, valor)?;
+                }
+                write!(f, "}}")
+            }
+            NodoExpresion::Unaria { operador, operando } => match operador {
+                OperadorUnario::Negativo => write!(f, "(-{})", operando),
+                OperadorUnario::Negacion => write!(f, "(!{})", operando),
+            },
+            NodoExpresion::Binaria { operador, izquierda, derecha } => {
+                write!(f, "({} {} {})", izquierda, operador, derecha)
+            }
+            NodoExpresion::Ternaria { condicion, si_verdadero, si_falso } => {
+                write!(f, "({} ? {} : {})", condicion, si_verdadero, si_falso)
+            }
+            NodoExpresion::Llamada { funcion, argumentos } => {
+                write!(f, "{}(", funcion)?;
+                escribir_lista(f, argumentos)?;
+                write!(f, ")")
+            }
+            NodoExpresion::Miembro { objeto, nombre, opcional: false } => write!(f, "{}.{}", objeto, nombre),
+            NodoExpresion::Miembro { objeto, nombre, opcional: true } => write!(f, "{}?.{}", objeto, nombre),
+            NodoExpresion::Indice { objeto, indice } => write!(f, "{}[{}]", objeto, indice),
+            NodoExpresion::Nuevo { tipo, argumentos } => {
+                write!(f, "nuevo {}(", tipo)?;
+                escribir_lista(f, argumentos)?;
+                write!(f, ")")
+            }
+            NodoExpresion::Rango { desde, hasta, inclusivo, paso } => {
+                let operador = if *inclusivo { "..=" } else { ".." };
+                write!(f, "({}{}{}", desde, operador, hasta)?;
+                if let Some(paso) = paso {
+                    write!(f, " paso {}", paso)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Parametro {
+    pub nombre: String,
+    pub tipo: Tipo,
+    /// `tipo mut nombre`: el parámetro se puede modificar dentro de la función.
+    pub mutable: bool,
+    pub pos: Posicion,
+}
+
+/// Variable de un bucle `para ... en`: `[tipo [mut]] nombre`.
+#[derive(Clone, Debug)]
+pub struct VariableBucle {
+    pub nombre: String,
+    pub tipo: Option<Tipo>,
+    pub mutable: bool,
+}
+
+/// Variante de una enumeración: `Nombre` o, con datos, `Nombre(tipo, ...)`.
+#[derive(Clone, Debug)]
+pub struct Variante {
+    pub nombre: String,
+    pub datos: Vec<Tipo>,
+    pub pos: Posicion,
+}
+
+/// Patrón de un `caso` dentro de `segun`.
+#[derive(Clone, Debug)]
+pub enum Patron {
+    /// `_`: coincide con cualquier valor.
+    Comodin,
+    /// Entero, número, cadena, bool o nulo.
+    Literal(Expresion),
+    /// `desde..hasta` o, incluyendo el final, `desde..=hasta`
+    Rango {
+        desde: Expresion,
+        hasta: Expresion,
+        inclusivo: bool,
+    },
+    /// Si es el nombre de un objeto o de una enumeración coincide con sus
+    /// valores; si no, coincide con cualquier valor y lo guarda en una
+    /// variable con ese nombre.
+    Nombre(String),
+    /// `Tipo nombre` o `Tipo`: coincide con los valores del tipo.
+    Tipo {
+        tipo: Tipo,
+        variable: Option<String>,
+    },
+    /// `[a, b]`: una lista con exactamente esos elementos.
+    Lista(Vec<Patron>),
+    /// `{ clave: patron }`; `{ clave }` guarda el valor en `clave`.
+    Jsn(Vec<(String, Patron)>),
+    /// `Enumeracion.Variante` o `Enumeracion.Variante(patrones)`; sin
+    /// paréntesis coincide con cualquier dato.
+    Variante {
+        enumeracion: String,
+        variante: String,
+        datos: Option<Vec<Patron>>,
+    },
+}
+
+/// `caso patron, patron [si guarda] => cuerpo`
+#[derive(Clone, Debug)]
+pub struct Caso {
+    pub patrones: Vec<Patron>,
+    pub guarda: Option<Expresion>,
+    pub cuerpo: Vec<Sentencia>,
+    pub pos: Posicion,
+}
+
+/// `atrapar [(variable)] { ... }`
+#[derive(Clone, Debug)]
+pub struct Atrapar {
+    pub variable: Option<String>,
+    pub cuerpo: Vec<Sentencia>,
+    pub pos: Posicion,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sentencia {
+    pub nodo: NodoSentencia,
+    pub pos: Posicion,
+}
+
+#[derive(Clone, Debug)]
+pub enum NodoSentencia {
+    /// `[mutable] tipo [mut] nombre [= valor]` o `[mutable] var [mut] nombre = valor`;
+    /// con `var` no hay tipo escrito y se infiere del valor.
+    Declaracion {
+        tipo: Option<Tipo>,
+        nombre: String,
+        mutable: bool,
+        valor: Option<Expresion>,
+    },
+    Asignacion {
+        nombre: String,
+        valor: Expresion,
+    },
+    /// `nombre[indice] = valor`, con uno o más índices: `matriz[i][j] = valor`
+    AsignacionIndice {
+        nombre: String,
+        indices: Vec<Expresion>,
+        valor: Expresion,
+    },
+    /// `nombre += valor`, `nombre -= valor`, ...
+    AsignacionCompuesta {
+        nombre: String,
+        operador: OperadorBinario,
+        valor: Expresion,
+    },
+    /// `nombre++` (cambio 1) o `nombre--` (cambio -1)
+    Incremento {
+        nombre: String,
+        cambio: i64,
+    },
+    Expresion(Expresion),
+    Si {
+        ramas: Vec<(Expresion, Vec<Sentencia>)>,
+        sino: Option<Vec<Sentencia>>,
+    },
+    /// Cada bucle puede llevar una etiqueta, `externo: mientras (...)`, para
+    /// salir de él o continuarlo desde un bucle interno.
+    Mientras {
+        condicion: Expresion,
+        cuerpo: Vec<Sentencia>,
+        etiqueta: Option<String>,
+    },
+    Hacer {
+        cuerpo: Vec<Sentencia>,
+        condicion: Expresion,
+        etiqueta: Option<String>,
+    },
+    Para {
+        inicio: Box<Sentencia>,
+        condicion: Expresion,
+        paso: Box<Sentencia>,
+        cuerpo: Vec<Sentencia>,
+        etiqueta: Option<String>,
+    },
+    /// `para ([tipo [mut]] variable en iterable)` o, con dos variables,
+    /// `para (clave, variable en iterable)`: la clave de un jsn o la
+    /// posición en una lista, cadena o rango.
+    ParaCada {
+        clave: Option<VariableBucle>,
+        variable: VariableBucle,
+        iterable: Expresion,
+        cuerpo: Vec<Sentencia>,
+        etiqueta: Option<String>,
+    },
+    /// `segun (valor) { caso ... => ... otro => ... }`: se ejecuta el primer
+    /// caso que coincide o, si ninguno coincide, `otro`.
+    Segun {
+        valor: Expresion,
+        casos: Vec<Caso>,
+        otro: Option<Vec<Sentencia>>,
+    },
+    /// `intentar { ... } atrapar (e) { ... } finalmente { ... }`, con
+    /// `atrapar`, `finalmente` o ambos.
+    Intentar {
+        cuerpo: Vec<Sentencia>,
+        atrapar: Option<Atrapar>,
+        finalmente: Option<Vec<Sentencia>>,
+    },
+    /// `lanzar valor`: interrumpe la ejecución hasta el `atrapar` más cercano.
+    Lanzar(Expresion),
+    Funcion {
+        nombre: String,
+        parametros: Vec<Parametro>,
+        tipo_retorno: Tipo,
+        cuerpo: Vec<Sentencia>,
+    },
+    Objeto {
+        nombre: String,
+        campos: Vec<String>,
+    },
+    /// `enumeracion Nombre { Variante, Variante(tipo), ... }`
+    Enumeracion {
+        nombre: String,
+        variantes: Vec<Variante>,
+    },
+    /// `tipo Nombre = tipo`
+    Alias {
+        nombre: String,
+        tipo: Tipo,
+    },
+    Retornar(Option<Expresion>),
+    /// `romper [etiqueta]`; sin etiqueta afecta al bucle más interno.
+    Romper(Option<String>),
+    /// `continuar [etiqueta]`
+    Continuar(Option<String>),
+}
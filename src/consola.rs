@@ -29,6 +29,6 @@ pub fn imprimir_confirmacion(texto: &str) {
 }
 
 fn imprimir_color(texto: &str, codigo: &str) {
-    print!("{}{}\x1b[0m\n", codigo, texto);
+    println!("{}{}\x1b[0m", codigo, texto);
     let _ = io::stdout().flush();
 }
@@ -0,0 +1,481 @@
+use crate::lexico::Posicion;
+use crate::valores::Valor;
+
+// Códigos estables de los diagnósticos. Cada código identifica una clase de
+// error y no cambia entre versiones; `quetzal explicar <código>` muestra su
+// explicación detallada.
+//
+//   Q01xx  errores léxicos
+//   Q02xx  errores de sintaxis
+//   Q03xx  nombres y tipos
+//   Q04xx  errores de ejecución
+
+pub const CARACTER_INESPERADO: &str = "Q0101";
+pub const CADENA_SIN_CERRAR: &str = "Q0102";
+pub const COMENTARIO_SIN_CERRAR: &str = "Q0103";
+pub const NUMERO_INVALIDO: &str = "Q0104";
+pub const ESCAPE_INVALIDO: &str = "Q0105";
+pub const INTERPOLACION_INVALIDA: &str = "Q0106";
+
+pub const TOKEN_INESPERADO: &str = "Q0201";
+pub const DELIMITADOR_SIN_CERRAR: &str = "Q0202";
+pub const PALABRA_RESERVADA: &str = "Q0203";
+pub const FUERA_DE_BUCLE: &str = "Q0204";
+pub const FUERA_DE_FUNCION: &str = "Q0205";
+pub const ETIQUETA_INVALIDA: &str = "Q0206";
+
+pub const VARIABLE_NO_ENCONTRADA: &str = "Q0301";
+pub const FUNCION_NO_ENCONTRADA: &str = "Q0302";
+pub const TIPO_DESCONOCIDO: &str = "Q0303";
+pub const TIPOS_INCOMPATIBLES: &str = "Q0304";
+pub const NUMERO_DE_ARGUMENTOS: &str = "Q0305";
+pub const MIEMBRO_NO_ENCONTRADO: &str = "Q0306";
+pub const VARIABLE_INMUTABLE: &str = "Q0307";
+pub const VALOR_NULO: &str = "Q0308";
+pub const TIPO_NO_INFERIBLE: &str = "Q0309";
+pub const ALIAS_INVALIDO: &str = "Q0310";
+pub const SEGUN_NO_EXHAUSTIVO: &str = "Q0311";
+pub const VARIABLE_REDECLARADA: &str = "Q0312";
+
+pub const ERROR_DE_EJECUCION: &str = "Q0400";
+pub const DIVISION_POR_CERO: &str = "Q0401";
+pub const INDICE_FUERA_DE_RANGO: &str = "Q0402";
+pub const DESBORDAMIENTO: &str = "Q0403";
+pub const EXCEPCION_NO_ATRAPADA: &str = "Q0404";
+pub const RANGO_DEMASIADO_GRANDE: &str = "Q0405";
+pub const CONVERSION_INVALIDA: &str = "Q0406";
+
+/// Error con código estable y, si se conoce, la posición en el código fuente
+/// donde se produjo.
+#[derive(Clone, Debug)]
+pub struct Diagnostico {
+    pub codigo: &'static str,
+    pub mensaje: String,
+    pub pos: Option<Posicion>,
+    /// Segunda ubicación relacionada con el error, como la declaración de
+    /// la variable que se intentó modificar.
+    pub nota: Option<(Posicion, String)>,
+    /// Una advertencia se muestra pero no impide ejecutar el programa.
+    pub advertencia: bool,
+    /// Valor pasado a `lanzar`; `atrapar` lo recibe tal cual.
+    pub lanzado: Option<Box<Valor>>,
+}
+
+impl Diagnostico {
+    pub fn nuevo(codigo: &'static str, mensaje: impl Into<String>) -> Self {
+        Self { codigo, mensaje: mensaje.into(), pos: None, nota: None, advertencia: false, lanzado: None }
+    }
+
+    pub fn en(codigo: &'static str, pos: Posicion, mensaje: impl Into<String>) -> Self {
+        Self { codigo, mensaje: mensaje.into(), pos: Some(pos), nota: None, advertencia: false, lanzado: None }
+    }
+
+    /// Error producido por `lanzar valor`. Si nadie lo atrapa, el programa
+    /// termina con este mensaje; de un jsn o una instancia con `mensaje` se
+    /// muestra solo el mensaje y, si lo hay, el `tipo`.
+    pub fn lanzado(pos: Posicion, valor: Valor) -> Self {
+        let descripcion = match &valor {
+            Valor::Objeto(campos) | Valor::Instancia(_, campos) => match (campos.get("tipo"), campos.get("mensaje")) {
+                (Some(Valor::Cadena(tipo)), Some(Valor::Cadena(mensaje))) => format!("{}: {}", tipo, mensaje),
+                (_, Some(Valor::Cadena(mensaje))) => mensaje.clone(),
+                _ => valor.a_cadena(),
+            },
+            _ => valor.a_cadena(),
+        };
+        let mut error = Self::en(EXCEPCION_NO_ATRAPADA, pos, format!("Excepción no atrapada: {}", descripcion));
+        error.lanzado = Some(Box::new(valor));
+        error
+    }
+
+    pub fn como_advertencia(mut self) -> Self {
+        self.advertencia = true;
+        self
+    }
+
+    /// Asigna la posición solo si el error todavía no tiene una; así se
+    /// conserva siempre la más precisa, la del nodo más interno.
+    pub fn con_posicion(mut self, pos: Posicion) -> Self {
+        if self.pos.is_none() {
+            self.pos = Some(pos);
+        }
+        self
+    }
+
+    pub fn con_nota(mut self, pos: Posicion, mensaje: impl Into<String>) -> Self {
+        self.nota = Some((pos, mensaje.into()));
+        self
+    }
+
+    /// Formatea el diagnóstico completo: código, mensaje, ubicación y la
+    /// línea del código fuente con el punto del error subrayado, seguido de
+    /// la nota si la hay.
+    pub fn mostrar(&self, archivo: &str, fuente: &str) -> String {
+        let clase = if self.advertencia { "Advertencia" } else { "Error" };
+        let mut salida = format!("{}[{}]: {}", clase, self.codigo, self.mensaje);
+        match self.pos {
+            Some(pos) => salida.push_str(&fragmento(archivo, fuente, pos)),
+            None => salida.push_str(&format!("\n --> {}", archivo)),
+        }
+        if let Some((pos, mensaje)) = &self.nota {
+            salida.push_str(&format!("\nnota: {}", mensaje));
+            salida.push_str(&fragmento(archivo, fuente, *pos));
+        }
+        salida
+    }
+}
+
+/// Ubicación y línea del código fuente con el punto indicado subrayado.
+fn fragmento(archivo: &str, fuente: &str, pos: Posicion) -> String {
+    let margen = " ".repeat(pos.linea.to_string().len());
+    let mut salida = format!("\n{}--> {}:{}:{}", margen, archivo, pos.linea, pos.columna);
+
+    if let Some(texto) = fuente.lines().nth(pos.linea - 1) {
+        let texto = texto.trim_start_matches('\u{feff}');
+        let antes: String = texto
+            .chars()
+            .take(pos.columna - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let subrayado = "^".repeat(longitud_subrayado(texto, pos.columna));
+        salida.push_str(&format!("\n{} |", margen));
+        salida.push_str(&format!("\n{} | {}", pos.linea, texto));
+        salida.push_str(&format!("\n{} | {}{}", margen, antes, subrayado));
+    }
+    salida
+}
+
+/// Subraya la palabra o número completo que empieza en la columna, o un
+/// solo carácter si ahí hay un símbolo.
+fn longitud_subrayado(texto: &str, columna: usize) -> usize {
+    let palabra = texto
+        .chars()
+        .skip(columna - 1)
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .count();
+    palabra.max(1)
+}
+
+impl From<String> for Diagnostico {
+    fn from(mensaje: String) -> Self {
+        Diagnostico::nuevo(ERROR_DE_EJECUCION, mensaje)
+    }
+}
+
+/// Nombre de la clase de error que recibe `atrapar` en el campo `tipo`.
+pub fn clase(codigo: &str) -> &'static str {
+    match codigo {
+        VARIABLE_NO_ENCONTRADA => "VariableNoEncontrada",
+        FUNCION_NO_ENCONTRADA => "FuncionNoEncontrada",
+        TIPO_DESCONOCIDO => "TipoDesconocido",
+        TIPOS_INCOMPATIBLES => "TiposIncompatibles",
+        NUMERO_DE_ARGUMENTOS => "NumeroDeArgumentos",
+        MIEMBRO_NO_ENCONTRADO => "MiembroNoEncontrado",
+        VARIABLE_INMUTABLE => "VariableInmutable",
+        VALOR_NULO => "ValorNulo",
+        VARIABLE_REDECLARADA => "VariableRedeclarada",
+        DIVISION_POR_CERO => "DivisionPorCero",
+        INDICE_FUERA_DE_RANGO => "IndiceFueraDeRango",
+        DESBORDAMIENTO => "Desbordamiento",
+        RANGO_DEMASIADO_GRANDE => "RangoDemasiadoGrande",
+        CONVERSION_INVALIDA => "ErrorDeConversion",
+        _ => "ErrorDeEjecucion",
+    }
+}
+
+/// Explicación detallada de un código de diagnóstico.
+pub fn explicar(codigo: &str) -> Option<&'static str> {
+    let texto = match codigo.to_uppercase().as_str() {
+        "Q0101" => "\
+Carácter inesperado.
+
+El analizador léxico encontró un carácter que no forma parte de ningún
+token de Quetzal, como '@' o '#'. Elimina el carácter o, si querías
+escribir texto, colócalo dentro de una cadena:
+
+    cadena correo = \"ana@ejemplo.com\"",
+        "Q0102" => "\
+Cadena sin cerrar.
+
+Una cadena empezó con comillas pero la línea terminó antes de encontrar
+las comillas de cierre. Las cadenas deben abrirse y cerrarse con el mismo
+tipo de comillas en la misma línea. Para incluir una comilla del mismo
+tipo dentro de la cadena, escápala con una barra invertida:
+
+    cadena saludo = \"Hola\"
+    cadena nombre = 'Ana'
+    cadena cita = \"Dijo \\\"hola\\\"\"",
+        "Q0103" => "\
+Comentario de bloque sin cerrar.
+
+Un comentario que empieza con /* debe terminar con */. El archivo terminó
+sin encontrar el cierre, así que todo lo que seguía se trató como
+comentario. Los comentarios de bloque se pueden anidar, por lo que cada
+/* interior necesita su propio */:
+
+    /* exterior /* interior */ sigue el exterior */",
+        "Q0104" => "\
+Número inválido.
+
+El literal numérico no se pudo interpretar; normalmente porque un entero
+no cabe en 64 bits. Usa un valor más pequeño o un número decimal:
+
+    número grande = 10000000000000000000.0",
+        "Q0105" => "\
+Secuencia de escape inválida.
+
+Dentro de una cadena, la barra invertida inicia una secuencia de escape.
+Las secuencias válidas son:
+
+    \\n   salto de línea        \\t   tabulación
+    \\r   retorno de carro      \\0   carácter nulo
+    \\\\   barra invertida       \\\"   comilla doble
+    \\'   comilla simple        \\$   signo de dólar
+    \\u{1F426}   carácter Unicode
+
+Para escribir una barra invertida literal usa \\\\.",
+        "Q0106" => "\
+Interpolación inválida.
+
+Dentro de una cadena, ${ inicia una expresión que se evalúa y se inserta
+en el texto. La expresión no puede estar vacía y debe cerrarse con } en
+la misma línea:
+
+    cadena saludo = \"Hola ${nombre}, tienes ${edad + 1} años\"
+
+Para escribir ${ literalmente, escapa el signo de dólar: \\${.",
+        "Q0201" => "\
+Token inesperado.
+
+El analizador sintáctico esperaba otra cosa en esta posición: un
+paréntesis de cierre, un nombre, una expresión o el final de la sentencia.
+Revisa que los paréntesis estén balanceados y que cada sentencia esté en
+su propia línea:
+
+    si (x > 0) {
+        imprimir(x)
+    }",
+        "Q0202" => "\
+Delimitador sin cerrar.
+
+Un bloque '{', una lista '[', un objeto o un literal jsn se abrió pero el
+archivo terminó antes de encontrar su cierre. El error señala dónde se
+abrió el delimitador.",
+        "Q0203" => "\
+Palabra reservada usada como nombre.
+
+Las palabras clave de Quetzal, como 'si', 'mientras' o 'entero', no pueden
+usarse como nombres de variables, funciones o parámetros. Elige otro
+nombre:
+
+    entero cantidad = 3",
+        "Q0204" => "\
+'romper' o 'continuar' fuera de un bucle.
+
+Estas sentencias solo tienen sentido dentro de 'mientras', 'hacer' o
+'para'. El cuerpo de una función no hereda los bucles que la rodean, así
+que tampoco pueden usarse para salir de un bucle desde una función.",
+        "Q0205" => "\
+'retornar' fuera de una función.
+
+'retornar' termina la función actual y devuelve un valor. En el nivel
+superior del programa no hay ninguna función de la que salir.",
+        "Q0206" => "\
+Etiqueta de bucle inválida.
+
+'romper' y 'continuar' con etiqueta solo pueden nombrar un bucle que
+contenga la sentencia, dentro de la misma función. Una etiqueta se escribe
+antes del bucle, seguida de ':', y no puede repetir la de un bucle que lo
+contenga:
+
+    externo: para (entero mut i = 0; i < 3; i++) {
+        para (entero mut j = 0; j < 3; j++) {
+            si (i * j == 2) {
+                romper externo
+            }
+        }
+    }",
+        "Q0301" => "\
+Variable no encontrada.
+
+Se usó una variable que no ha sido declarada. Declárala con su tipo antes
+de usarla:
+
+    entero contador = 0
+    contador = contador + 1",
+        "Q0302" => "\
+Función no reconocida.
+
+Se llamó a una función que no existe. Las funciones deben definirse antes
+de la línea en la que se llaman por primera vez.",
+        "Q0303" => "\
+Tipo desconocido.
+
+El tipo indicado no es un tipo básico ni un objeto definido con 'objeto'.
+Comprueba que el objeto se defina antes de usarlo.",
+        "Q0304" => "\
+Tipos incompatibles.
+
+Un valor no tiene el tipo que la operación necesita: por ejemplo, una
+cadena asignada a una variable 'entero', una condición que no es bool,
+una resta entre una lista y un número o un argumento que no coincide con
+el tipo del parámetro. Estos errores se detectan antes de ejecutar el
+programa; 'quetzal verificar' los muestra sin ejecutarlo.",
+        "Q0305" => "\
+Número de argumentos incorrecto.
+
+La función se llamó con más o menos argumentos de los parámetros que
+declara.",
+        "Q0306" => "\
+Propiedad o método inexistente.
+
+El valor no tiene la propiedad o el método indicado. Revisa el nombre y
+el tipo del valor sobre el que se usa.",
+        "Q0307" => "\
+Variable inmutable.
+
+Las variables y los parámetros son inmutables salvo que se declaren con
+'mut' o 'mutable': después de declararlos no se pueden reasignar, ni usar
+con '+=', '++' o '--', ni modificar con métodos como 'agregar'. La nota
+del error señala la declaración. Para poder modificar la variable:
+
+    entero mut contador = 0
+    contador++
+
+    mutable lista<cadena> nombres = []
+    nombres.agregar(\"Ana\")",
+        "Q0308" => "\
+Valor posiblemente nulo.
+
+Solo las variables, parámetros y campos de un tipo opcional, escrito con
+'?' al final, pueden contener 'nulo'. Un valor opcional tampoco se puede
+usar donde se espera uno que no lo es, ni acceder a sus propiedades con
+'.'. Usa '?.' para acceder solo si no es nulo y '??' para dar un valor por
+defecto:
+
+    cadena? apodo = nulo
+    cadena nombre = apodo ?? \"sin apodo\"
+    imprimir(usuario?.correo)",
+        "Q0309" => "\
+No se puede inferir el tipo.
+
+Una declaración con 'var' toma el tipo de su valor inicial, y ese valor no
+lo determina: 'nulo' no dice de qué tipo opcional se trata, una llamada a
+una función vacio no produce ningún valor y una expresión que a veces es
+nula, como un ternario con 'nulo' en una rama, no tiene un tipo fijo. Escribe
+el tipo explícitamente:
+
+    var contador = 0
+    cadena? apodo = nulo",
+        "Q0310" => "\
+Alias de tipo inválido.
+
+'tipo Nombre = tipo' da otro nombre a un tipo existente. El nombre no puede
+estar definido dos veces ni coincidir con el de un objeto, y un alias no
+puede referirse a sí mismo, directamente o a través de otros alias:
+
+    tipo Dinero = número
+    tipo Fila = lista<jsn>
+    tipo Tabla = lista<Fila>",
+        "Q0311" => "\
+Segun no exhaustivo (advertencia).
+
+Algún valor puede no coincidir con ningún caso del segun, y entonces no se
+ejecuta nada. Sobre un bool o una enumeración basta con cubrir todos sus
+valores o variantes; con otros tipos hace falta un caso que acepte
+cualquier valor. Los casos con guarda ('si') no cuentan, porque pueden no
+cumplirse. Agrega los casos que faltan o 'otro':
+
+    segun (estado) {
+        caso Estado.Pendiente => imprimir(\"pendiente\")
+        caso Estado.Pagado(monto) => imprimir(monto)
+        otro => imprimir(\"cancelado\")
+    }",
+        "Q0312" => "\
+Variable redeclarada.
+
+Un bloque declara dos veces el mismo nombre, ya sea como variable, como
+parámetro, como variable de un bucle o de un patrón. Cada declaración
+necesita su propio nombre. Para cambiar el valor de una variable
+existente, declárala con 'mut' y asígnale el nuevo valor:
+
+    entero mut total = 1
+    total = 2
+
+Un bloque interno sí puede declarar un nombre que ya existe fuera de él;
+la variable externa queda oculta hasta el final del bloque.",
+        "Q0400" => "\
+Error de ejecución.
+
+El programa falló mientras se ejecutaba por un motivo que no tiene su
+propio código, por ejemplo al usar un paso cero en un rango.",
+        "Q0401" => "\
+División por cero.
+
+El divisor de '/', '%', '/=' o '%=' es cero, tanto entre enteros como
+entre números. Comprueba el divisor antes de dividir:
+
+    si (b != 0) {
+        imprimir(a / b)
+    }",
+        "Q0402" => "\
+Índice fuera de rango.
+
+Se accedió a una posición de una lista o cadena que no existe. Los
+índices empiezan en 0 y deben ser menores que la longitud.",
+        "Q0403" => "\
+Desbordamiento de entero.
+
+El resultado de una operación entre enteros no cabe en 64 bits (de
+-9223372036854775808 a 9223372036854775807). Esto incluye '+', '-', '*',
+'/', '%', la negación, '++', '--' y las asignaciones compuestas. Usa
+números decimales si necesitas valores más grandes o, si el desbordamiento
+es lo esperado, las funciones que lo manejan explícitamente:
+
+    suma_envolvente(a, b)     resta_envolvente(a, b)     producto_envolvente(a, b)
+    suma_saturada(a, b)       resta_saturada(a, b)       producto_saturado(a, b)
+
+Las envolventes dan la vuelta al llegar al límite; las saturadas se quedan
+en el valor máximo o mínimo.",
+        "Q0404" => "\
+Excepción no atrapada.
+
+Se ejecutó 'lanzar' y ningún 'intentar' la atrapó. Cualquier valor se
+puede lanzar; el 'atrapar' más cercano lo recibe tal cual. Los errores de
+ejecución, como Q0401, también se pueden atrapar: se reciben como un jsn
+con los campos tipo, codigo, mensaje, linea y columna.
+
+    intentar {
+        lanzar { tipo: \"Validacion\", mensaje: \"edad negativa\" }
+    } atrapar (e) {
+        imprimir(e.mensaje)
+    } finalmente {
+        imprimir(\"siempre se ejecuta\")
+    }",
+        "Q0405" => "\
+Rango demasiado grande.
+
+Un rango usado como valor, por ejemplo al guardarlo en una lista, crea
+todos sus enteros a la vez, y admite como máximo 1000000. Para recorrer
+un rango más grande usa 'para', que calcula cada entero cuando lo
+necesita y no tiene límite:
+
+    para (entero i en 0..100000000) {
+        imprimir(i)
+    }",
+        "Q0406" => "\
+Conversión inválida.
+
+Un método de conversión como 'entero()', 'numero()', 'bool()' o 'jsn()'
+recibió un valor que no representa el tipo pedido, por ejemplo
+\"abc\".entero(). El error se puede atrapar; su tipo es
+ErrorDeConversion:
+
+    intentar {
+        entero n = entrada.entero()
+    } atrapar (e) {
+        imprimir(e.tipo)   // ErrorDeConversion
+    }",
+        _ => return None,
+    };
+    Some(texto)
+}
@@ -1,11 +1,32 @@
 use std::collections::HashMap;
-use crate::valores::{Valor, DefFuncion};
+use crate::valores::{Valor, DefEnumeracion, DefFuncion};
 use crate::objetos::DefObjeto;
+use crate::ast::Tipo;
+use crate::lexico::Posicion;
+use crate::diagnostico::{self, Diagnostico};
+
+/// Lo que se sabe de una variable por su declaración. Las variables sin tipo
+/// declarado, como la de `para (x en lista)`, aceptan cualquier valor.
+#[derive(Clone, Debug)]
+pub struct Enlace {
+    pub tipo: Option<Tipo>,
+    /// Declarada con `mut` o `mutable`; si no, no se puede modificar.
+    pub mutable: bool,
+    /// Posición de la declaración, para señalarla en los errores.
+    pub declarada_en: Option<Posicion>,
+}
+
+#[derive(Debug)]
+struct Variable {
+    valor: Valor,
+    enlace: Enlace,
+}
 
 #[derive(Debug)]
 pub struct Entorno {
-    variables: HashMap<String, Valor>,
+    variables: HashMap<String, Variable>,
     objetos: HashMap<String, DefObjeto>,
+    enumeraciones: HashMap<String, DefEnumeracion>,
     pub funciones: HashMap<String, DefFuncion>,
     pub padre: Option<Box<Entorno>>,
 }
@@ -15,6 +36,7 @@ impl Entorno {
         Self { 
             variables: HashMap::new(), 
             objetos: HashMap::new(),
+            enumeraciones: HashMap::new(),
             funciones: HashMap::new(),
             padre: None,
         }
@@ -24,19 +46,74 @@ impl Entorno {
         Self {
             variables: HashMap::new(),
             objetos: HashMap::new(),
+            enumeraciones: HashMap::new(),
             funciones: HashMap::new(),
             padre: Some(Box::new(padre)),
         }
     }
 
+    /// Empieza un bloque: lo que se declare hasta `cerrar_bloque` queda en
+    /// un entorno hijo del actual.
+    pub fn abrir_bloque(&mut self) {
+        let padre = std::mem::replace(self, Entorno::nuevo());
+        *self = Entorno::nuevo_con_padre(padre);
+    }
+
+    /// Termina el bloque abierto con `abrir_bloque` y descarta lo que se
+    /// declaró en él.
+    pub fn cerrar_bloque(&mut self) {
+        if let Some(padre) = self.padre.take() {
+            *self = *padre;
+        }
+    }
+
+    /// Crea la variable en el bloque actual; una variable de un bloque
+    /// externo con el mismo nombre queda oculta hasta el final del bloque.
+    /// Declarar dos veces el mismo nombre en un bloque es un error.
+    pub fn declarar(&mut self, nombre: &str, valor: Valor, enlace: Enlace) -> Result<(), Diagnostico> {
+        if let Some(anterior) = self.variables.get(nombre) {
+            return Err(redeclarada(nombre, anterior.enlace.declarada_en));
+        }
+        self.variables.insert(nombre.to_string(), Variable { valor, enlace });
+        Ok(())
+    }
+
+    /// Cambia el valor de una variable conservando su declaración, en el
+    /// bloque más interno donde esté declarada.
     pub fn establecer(&mut self, nombre: &str, valor: Valor) {
-        self.variables.insert(nombre.to_string(), valor);
+        match self.variable_mut(nombre) {
+            Some(variable) => variable.valor = valor,
+            None => {
+                let enlace = Enlace { tipo: None, mutable: true, declarada_en: None };
+                self.variables.insert(nombre.to_string(), Variable { valor, enlace });
+            }
+        }
+    }
+
+    fn variable_mut(&mut self, nombre: &str) -> Option<&mut Variable> {
+        if self.variables.contains_key(nombre) {
+            return self.variables.get_mut(nombre);
+        }
+        self.padre.as_mut().and_then(|p| p.variable_mut(nombre))
     }
 
     pub fn obtener(&self, nombre: &str) -> Option<&Valor> {
-        self.variables.get(nombre).or_else(|| {
-            self.padre.as_ref().and_then(|p| p.obtener(nombre))
-        })
+        match self.variables.get(nombre) {
+            Some(variable) => Some(&variable.valor),
+            None => self.padre.as_ref().and_then(|p| p.obtener(nombre)),
+        }
+    }
+
+    pub fn enlace(&self, nombre: &str) -> Option<&Enlace> {
+        match self.variables.get(nombre) {
+            Some(variable) => Some(&variable.enlace),
+            None => self.padre.as_ref().and_then(|p| p.enlace(nombre)),
+        }
+    }
+
+    /// Tipo declarado de la variable, si existe y se declaró con tipo.
+    pub fn tipo_de(&self, nombre: &str) -> Option<&Tipo> {
+        self.enlace(nombre).and_then(|enlace| enlace.tipo.as_ref())
     }
 
     pub fn definir_objeto(&mut self, def: DefObjeto) {
@@ -49,6 +126,16 @@ impl Entorno {
         })
     }
 
+    pub fn definir_enumeracion(&mut self, def: DefEnumeracion) {
+        self.enumeraciones.insert(def.nombre.clone(), def);
+    }
+
+    pub fn obtener_enumeracion(&self, nombre: &str) -> Option<&DefEnumeracion> {
+        self.enumeraciones.get(nombre).or_else(|| {
+            self.padre.as_ref().and_then(|p| p.obtener_enumeracion(nombre))
+        })
+    }
+
     pub fn definir_funcion(&mut self, def: DefFuncion) {
         self.funciones.insert(def.nombre.clone(), def);
     }
@@ -58,4 +145,32 @@ impl Entorno {
             self.padre.as_ref().and_then(|p| p.obtener_funcion(nombre))
         })
     }
+
+    /// Entorno en el que se ejecuta el cuerpo de una función: ve las
+    /// funciones, objetos y enumeraciones visibles desde la llamada, pero no
+    /// sus variables. Las definiciones de un bloque interno ocultan a las de
+    /// uno externo con el mismo nombre.
+    pub fn para_funcion(&self) -> Entorno {
+        let mut entorno = match &self.padre {
+            Some(padre) => padre.para_funcion(),
+            None => Entorno::nuevo(),
+        };
+        entorno.funciones.extend(self.funciones.iter().map(|(nombre, def)| (nombre.clone(), def.clone())));
+        entorno.objetos.extend(self.objetos.iter().map(|(nombre, def)| (nombre.clone(), def.clone())));
+        entorno.enumeraciones.extend(self.enumeraciones.iter().map(|(nombre, def)| (nombre.clone(), def.clone())));
+        entorno
+    }
+}
+
+/// Error al declarar un nombre que ya existe en el mismo bloque, con una
+/// nota en la declaración anterior si se conoce.
+pub fn redeclarada(nombre: &str, anterior: Option<Posicion>) -> Diagnostico {
+    let error = Diagnostico::nuevo(
+        diagnostico::VARIABLE_REDECLARADA,
+        format!("La variable '{}' ya está declarada en este bloque", nombre),
+    );
+    match anterior {
+        Some(pos) => error.con_nota(pos, format!("'{}' se declaró antes aquí", nombre)),
+        None => error,
+    }
 }
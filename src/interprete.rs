@@ -1,1782 +1,1610 @@
-use crate::entorno::Entorno;
-use crate::valores::{Valor, DefFuncion};
+use std::collections::HashMap;
+use crate::ast::{Atrapar, Caso, Expresion, NodoExpresion, NodoSentencia, OperadorBinario, OperadorUnario, ParteCadena, Patron, Sentencia, Tipo, VariableBucle};
+use crate::entorno::{Enlace, Entorno};
+use crate::valores::{Valor, DefEnumeracion, DefFuncion};
 use crate::objetos::{DefObjeto, TipoMetodo};
 use crate::consola;
-
-pub fn interpretar(contenido: &str) -> Result<(), String> {
+use crate::diagnostico::{self, Diagnostico};
+use crate::lexico::{self, Posicion};
+use crate::{alias, sintactico};
+use crate::verificador;
+
+/// Analiza el programa completo: si hay errores léxicos o de sintaxis se
+/// devuelven todos juntos. Los alias de tipo quedan resueltos en el árbol.
+pub fn analizar(contenido: &str) -> Result<Vec<Sentencia>, Vec<Diagnostico>> {
     let limpio = contenido.trim_start_matches('\u{feff}');
+    let tokens = lexico::tokenizar(limpio)?;
+    let mut programa = sintactico::analizar(tokens)?;
+    alias::resolver(&mut programa)?;
+    Ok(programa)
+}
+
+/// Analiza y, si se pide, verifica los tipos del programa completo antes de
+/// ejecutar nada: con cualquier error no se ejecuta ninguna línea. Las
+/// advertencias del verificador se pasan a `advertir` antes de ejecutar.
+pub fn interpretar(contenido: &str, verificar: bool, mut advertir: impl FnMut(&Diagnostico)) -> Result<(), Vec<Diagnostico>> {
+    let programa = analizar(contenido)?;
+    if verificar {
+        for advertencia in verificador::verificar(&programa)?.advertencias {
+            advertir(&advertencia);
+        }
+    }
     let mut entorno = Entorno::nuevo();
-    let lineas: Vec<String> = limpio.lines().map(|l| l.to_string()).collect();
-    procesar_lineas(&lineas, &mut entorno, 0)
+    // El analizador sintáctico rechaza `retornar`, `romper` y `continuar`
+    // fuera de su contexto, así que el programa siempre termina normalmente
+    ejecutar_sentencias(&programa, &mut entorno).map_err(|error| vec![error])?;
+    Ok(())
 }
 
-fn procesar_lineas(lineas: &[String], entorno: &mut Entorno, inicio: usize) -> Result<(), String> {
-    let mut en_comentario = false;
-    let mut indice = 0;
+/// Cómo terminó la ejecución de una sentencia o de un bloque.
+enum Flujo {
+    Normal,
+    /// `retornar`, con la posición del valor retornado, o la de la sentencia
+    /// si no lo hay, para señalarla si el valor no es del tipo de la función.
+    Retorno(Valor, Posicion),
+    /// `romper`, con la etiqueta del bucle si se indicó.
+    Romper(Option<String>),
+    Continuar(Option<String>),
+}
 
-    while indice < lineas.len() {
-        let mut linea = lineas[indice].trim();
-        indice += 1;
-        if en_comentario {
-            if let Some(pos) = linea.find("*/") {
-                linea = &linea[pos + 2..];
-                en_comentario = false;
-            } else {
-                continue;
-            }
-        }
-        if linea.starts_with("/*") {
-            if let Some(pos) = linea.find("*/") {
-                linea = &linea[pos + 2..];
-            } else {
-                en_comentario = true;
-                continue;
-            }
-        }
-        if linea.starts_with("//") || linea.is_empty() {
-            continue;
-        }
+/// Lo que hace un bucle después de ejecutar su cuerpo una vez.
+enum Vuelta {
+    Siguiente,
+    Salir,
+    /// El flujo es para un bucle externo o para la función.
+    Propagar(Flujo),
+}
 
-        if linea.starts_with("para") {
-            let (bloque, fin) = extraer_bloque(lineas, indice - 1)?;
-            if linea.contains(';') {
-                procesar_bucle_para(linea, &bloque, entorno, inicio + indice - 1)?;
-            } else {
-                procesar_bucle_foreach(linea, &bloque, entorno, inicio + indice - 1)?;
-            }
-            indice = fin + 1;
-            continue;
-        }
+fn vuelta(flujo: Flujo, etiqueta: Option<&str>) -> Vuelta {
+    let es_propio = |destino: &Option<String>| destino.is_none() || destino.as_deref() == etiqueta;
+    match flujo {
+        Flujo::Normal => Vuelta::Siguiente,
+        Flujo::Continuar(destino) if es_propio(&destino) => Vuelta::Siguiente,
+        Flujo::Romper(destino) if es_propio(&destino) => Vuelta::Salir,
+        flujo => Vuelta::Propagar(flujo),
+    }
+}
 
-        if linea.starts_with("mientras") {
-            let (bloque, fin) = extraer_bloque(lineas, indice - 1)?;
-            procesar_bucle_mientras(linea, &bloque, entorno, inicio + indice - 1)?;
-            indice = fin + 1;
-            continue;
+fn ejecutar_sentencias(sentencias: &[Sentencia], entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    definir(sentencias, entorno);
+    for sentencia in sentencias {
+        match ejecutar_sentencia(sentencia, entorno)? {
+            Flujo::Normal => {}
+            flujo => return Ok(flujo),
         }
+    }
+    Ok(Flujo::Normal)
+}
 
-        if linea.starts_with("hacer") {
-            let (bloque, fin) = extraer_bloque(lineas, indice - 1)?;
-            let mut sig = lineas[fin].trim().trim_start_matches('}').trim();
-            let mut nuevo_indice = fin;
-            if sig.is_empty() {
-                nuevo_indice += 1;
-                if nuevo_indice >= lineas.len() {
-                    return Err(formatear_error(inicio + fin, "Bucle hacer-mientras inválido"));
-                }
-                sig = lineas[nuevo_indice].trim();
+/// Define las funciones, objetos y enumeraciones escritos directamente en un
+/// bloque antes de ejecutarlo, como hace el verificador: una función puede
+/// llamarse antes de su definición, y las de un bloque interno solo existen
+/// mientras se ejecuta ese bloque.
+fn definir(sentencias: &[Sentencia], entorno: &mut Entorno) {
+    for sentencia in sentencias {
+        match &sentencia.nodo {
+            NodoSentencia::Funcion { nombre, parametros, tipo_retorno, cuerpo } => {
+                entorno.definir_funcion(DefFuncion {
+                    nombre: nombre.clone(),
+                    parametros: parametros.clone(),
+                    tipo_retorno: tipo_retorno.clone(),
+                    cuerpo: cuerpo.clone(),
+                });
+            }
+            NodoSentencia::Objeto { nombre, campos } => {
+                let mut def = DefObjeto {
+                    nombre: nombre.clone(),
+                    campos: campos.clone(),
+                    metodos: HashMap::<String, TipoMetodo>::new(),
+                    modificadores: Vec::new(),
+                };
+                agregar_metodos_built_in(&mut def);
+                entorno.definir_objeto(def);
             }
-            if !sig.starts_with("mientras") {
-                return Err(formatear_error(inicio + nuevo_indice, "Bucle hacer-mientras inválido"));
+            NodoSentencia::Enumeracion { nombre, variantes } => {
+                entorno.definir_enumeracion(DefEnumeracion { nombre: nombre.clone(), variantes: variantes.clone() });
             }
-            let ini = sig.find('(').ok_or_else(|| formatear_error(inicio + nuevo_indice, "Bucle hacer-mientras inválido"))?;
-            let fin_paren = sig.rfind(')').ok_or_else(|| formatear_error(inicio + nuevo_indice, "Bucle hacer-mientras inválido"))?;
-            let condicion = &sig[ini + 1..fin_paren];
-            procesar_bucle_hacer(&bloque, condicion, entorno, inicio + indice - 1)?;
-            indice = nuevo_indice + 1;
-            continue;
+            _ => {}
         }
+    }
+}
 
-        if linea.starts_with("si") {
-            indice = procesar_condicional(lineas, indice - 1, entorno, inicio)?;
-            continue;
-        }
+/// Ejecuta las sentencias en un bloque propio: las variables que declaran
+/// desaparecen al terminar.
+fn ejecutar_bloque(sentencias: &[Sentencia], entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    en_bloque(entorno, |entorno| ejecutar_sentencias(sentencias, entorno))
+}
 
-        if linea.starts_with("objeto") {
-            let (objeto, fin) = procesar_objeto(lineas, indice - 1)?;
-            entorno.definir_objeto(objeto);
-            indice = fin + 1;
-            continue;
-        }
+/// Ejecuta `f` en un bloque nuevo, que se cierra también si termina con un
+/// error.
+fn en_bloque<T>(entorno: &mut Entorno, f: impl FnOnce(&mut Entorno) -> Result<T, Diagnostico>) -> Result<T, Diagnostico> {
+    entorno.abrir_bloque();
+    let resultado = f(entorno);
+    entorno.cerrar_bloque();
+    resultado
+}
 
-        if linea.starts_with("imprimir_error") {
-            manejar_impresion(linea, "imprimir_error", inicio + indice - 1, entorno, consola::imprimir_error)?;
-            continue;
+fn ejecutar_sentencia(sentencia: &Sentencia, entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    // Los errores sin posición propia se sitúan en la sentencia
+    ejecutar_nodo(sentencia, entorno).map_err(|e| e.con_posicion(sentencia.pos))
+}
+
+fn ejecutar_nodo(sentencia: &Sentencia, entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    match &sentencia.nodo {
+        NodoSentencia::Declaracion { tipo, nombre, mutable, valor } => {
+            procesar_declaracion(tipo.as_ref(), nombre, *mutable, valor.as_ref(), sentencia.pos, entorno)?;
         }
-        if linea.starts_with("imprimir_advertencia") {
-            manejar_impresion(linea, "imprimir_advertencia", inicio + indice - 1, entorno, consola::imprimir_advertencia)?;
-            continue;
+        NodoSentencia::Asignacion { nombre, valor } => {
+            procesar_asignacion(nombre, valor, entorno)?;
         }
-        if linea.starts_with("imprimir_informacion") {
-            manejar_impresion(linea, "imprimir_informacion", inicio + indice - 1, entorno, consola::imprimir_informacion)?;
-            continue;
+        NodoSentencia::AsignacionIndice { nombre, indices, valor } => {
+            procesar_asignacion_indice(nombre, indices, valor, entorno)?;
         }
-        if linea.starts_with("imprimir_depurar") {
-            manejar_impresion(linea, "imprimir_depurar", inicio + indice - 1, entorno, consola::imprimir_depurar)?;
-            continue;
+        NodoSentencia::AsignacionCompuesta { nombre, operador, valor } => {
+            procesar_asignacion_compuesta(nombre, *operador, valor, entorno)?;
         }
-        if linea.starts_with("imprimir_exito") {
-            manejar_impresion(linea, "imprimir_exito", inicio + indice - 1, entorno, consola::imprimir_exito)?;
-            continue;
+        NodoSentencia::Incremento { nombre, cambio } => {
+            aplicar_incremento(nombre, *cambio, entorno)?;
         }
-        if linea.starts_with("imprimir_alerta") {
-            manejar_impresion(linea, "imprimir_alerta", inicio + indice - 1, entorno, consola::imprimir_alerta)?;
-            continue;
+        NodoSentencia::Expresion(expresion) => {
+            evaluar_expresion(expresion, entorno)?;
         }
-        if linea.starts_with("imprimir_confirmacion") {
-            manejar_impresion(linea, "imprimir_confirmacion", inicio + indice - 1, entorno, consola::imprimir_confirmacion)?;
-            continue;
+        NodoSentencia::Si { ramas, sino } => return procesar_condicional(ramas, sino.as_deref(), entorno),
+        NodoSentencia::Mientras { condicion, cuerpo, etiqueta } => {
+            return procesar_bucle_mientras(condicion, cuerpo, etiqueta.as_deref(), entorno)
         }
-        if linea.starts_with("imprimir") {
-            manejar_impresion(linea, "imprimir", inicio + indice - 1, entorno, |t| println!("{}", t))?;
-            continue;
+        NodoSentencia::Hacer { cuerpo, condicion, etiqueta } => {
+            return procesar_bucle_hacer(cuerpo, condicion, etiqueta.as_deref(), entorno)
         }
-
-        // Manejo de declaración de funciones con sintaxis Quetzal: tipo nombre_funcion(parametros) {
-        if es_declaracion_funcion(linea) && linea.trim_end().ends_with('{') {
-            let (bloque_funcion, fin_funcion) = extraer_bloque(lineas, indice - 1)?;
-            procesar_declaracion_funcion_quetzal(linea, &bloque_funcion, entorno)?;
-            indice = fin_funcion + 1;
-            continue;
+        NodoSentencia::Para { inicio, condicion, paso, cuerpo, etiqueta } => {
+            return procesar_bucle_para(inicio, condicion, paso, cuerpo, etiqueta.as_deref(), entorno)
         }
-
-        // Manejo de llamadas a funciones
-        if linea.contains('(') && linea.contains(')') && linea.contains('=') && !linea.starts_with("para") && !linea.starts_with("si") {
-            if let Err(_) = procesar_llamada_funcion(linea, entorno) {
-                // Si no es una llamada a función, procesar como declaración normal
-                if let Err(error) = procesar_declaracion(linea, entorno) {
-                    return Err(formatear_error(inicio + indice - 1, &error));
-                }
-            }
-            continue;
+        NodoSentencia::ParaCada { clave, variable, iterable, cuerpo, etiqueta } => {
+            let etiqueta = etiqueta.as_deref();
+            return procesar_bucle_foreach(clave.as_ref(), variable, iterable, cuerpo, etiqueta, sentencia.pos, entorno)
         }
-
-        // Manejo de operadores de asignación compuesta
-        if linea.contains("+=") || linea.contains("-=") || linea.contains("*=") || linea.contains("/=") || linea.contains("%=") {
-            procesar_asignacion_compuesta(linea, entorno, inicio + indice - 1)?;
-            continue;
+        NodoSentencia::Segun { valor, casos, otro } => return procesar_segun(valor, casos, otro.as_deref(), entorno),
+        NodoSentencia::Intentar { cuerpo, atrapar, finalmente } => {
+            return procesar_intentar(cuerpo, atrapar.as_ref(), finalmente.as_deref(), entorno)
         }
-
-        // Manejo de retorno de funciones
-        if linea.starts_with("retornar") {
-            let valor_retorno = if linea.trim() == "retornar" {
-                "vacio"
-            } else {
-                linea.strip_prefix("retornar").unwrap_or("").trim()
+        NodoSentencia::Lanzar(valor) => {
+            let valor = evaluar_expresion(valor, entorno)?;
+            return Err(Diagnostico::lanzado(sentencia.pos, valor));
+        }
+        // Las definiciones se registran al empezar el bloque y los alias ya
+        // se resolvieron al analizar el programa
+        NodoSentencia::Funcion { .. }
+        | NodoSentencia::Objeto { .. }
+        | NodoSentencia::Enumeracion { .. }
+        | NodoSentencia::Alias { .. } => {}
+        NodoSentencia::Retornar(valor) => {
+            let (valor_retorno, pos) = match valor {
+                Some(expresion) => (evaluar_expresion(expresion, entorno)?, expresion.pos),
+                None => (Valor::Vacio, sentencia.pos),
             };
-            return Err(format!("RETORNO:{}", valor_retorno));
+            return Ok(Flujo::Retorno(valor_retorno, pos));
         }
+        NodoSentencia::Romper(etiqueta) => return Ok(Flujo::Romper(etiqueta.clone())),
+        NodoSentencia::Continuar(etiqueta) => return Ok(Flujo::Continuar(etiqueta.clone())),
+    }
+    Ok(Flujo::Normal)
+}
 
-        // Manejo de control de flujo en bucles
-        if linea.trim() == "romper" {
-            return Err("ROMPER".to_string());
-        }
-        
-        if linea.trim() == "continuar" {
-            return Err("CONTINUAR".to_string());
-        }
+fn no_encontrada(nombre: &str) -> Diagnostico {
+    Diagnostico::nuevo(diagnostico::VARIABLE_NO_ENCONTRADA, format!("Variable '{}' no encontrada", nombre))
+}
 
-        if linea.starts_with("jsn") && linea.contains('=') && linea.contains('{') && !linea.contains('}') {
-            let mut compuesto = linea.to_string();
-            let mut nivel = linea.matches('{').count() as i32 - linea.matches('}').count() as i32;
-            while indice < lineas.len() && nivel > 0 {
-                let sig = lineas[indice].trim();
-                compuesto.push(' ');
-                compuesto.push_str(sig);
-                nivel += sig.matches('{').count() as i32;
-                nivel -= sig.matches('}').count() as i32;
-                indice += 1;
-            }
-            if nivel != 0 {
-                return Err(formatear_error(inicio + indice - 1, "JSON sin cerrar"));
-            }
-            if let Err(error) = procesar_declaracion(&compuesto, entorno) {
-                return Err(formatear_error(inicio + indice - 1, &error));
-            }
-        } else {
-            // Verificar si es una llamada a función sin asignación
-            if linea.contains('(') && linea.contains(')') && !linea.contains('=') && 
-               !linea.starts_with("para") && !linea.starts_with("si") && !linea.starts_with("mientras") {
-                if let Ok(_) = procesar_llamada_funcion_sin_asignacion(linea, entorno) {
-                    continue;
+fn division_por_cero() -> Diagnostico {
+    Diagnostico::nuevo(diagnostico::DIVISION_POR_CERO, "División por cero")
+}
+
+fn desbordamiento(operacion: String) -> Diagnostico {
+    Diagnostico::nuevo(
+        diagnostico::DESBORDAMIENTO,
+        format!("Desbordamiento de entero: {} no cabe en 64 bits", operacion),
+    )
+}
+
+fn procesar_declaracion(tipo: Option<&Tipo>, nombre: &str, mutable: bool, valor: Option<&Expresion>, pos: Posicion, entorno: &mut Entorno) -> Result<(), Diagnostico> {
+    let tipo = match (tipo, valor) {
+        (Some(tipo), _) => tipo,
+        // `var nombre = valor`: el analizador sintáctico exige el valor
+        (None, Some(expresion)) => {
+            let resultado = evaluar_expresion(expresion, entorno)?;
+            let tipo = inferir_tipo(nombre, expresion, &resultado, entorno)?;
+            let enlace = Enlace { tipo: Some(tipo), mutable, declarada_en: Some(pos) };
+            return entorno.declarar(nombre, resultado, enlace);
+        }
+        (None, None) => return Err(Diagnostico::nuevo(
+            diagnostico::TIPO_NO_INFERIBLE,
+            format!("'var {}' necesita un valor inicial para inferir su tipo", nombre),
+        )),
+    };
+    let valor = match valor {
+        Some(expresion) => {
+            let resultado = evaluar_expresion(expresion, entorno)?;
+            convertir_a_tipo(tipo, nombre, resultado, entorno).map_err(|e| e.con_posicion(expresion.pos))?
+        }
+        None => Valor::valor_por_defecto(tipo).ok_or_else(|| {
+            Diagnostico::nuevo(diagnostico::TIPO_DESCONOCIDO, format!("Tipo desconocido: {}", tipo))
+        })?,
+    };
+    let enlace = Enlace { tipo: Some(tipo.clone()), mutable, declarada_en: Some(pos) };
+    entorno.declarar(nombre, valor, enlace)
+}
+
+/// Tipo de una declaración con `var`: el declarado de la variable o el de
+/// retorno de la función si el valor es una de ellas, y si no el del valor.
+fn inferir_tipo(nombre: &str, expresion: &Expresion, valor: &Valor, entorno: &Entorno) -> Result<Tipo, Diagnostico> {
+    let estatico = match &expresion.nodo {
+        NodoExpresion::Variable(variable) => entorno.tipo_de(variable).cloned(),
+        NodoExpresion::Llamada { funcion, .. } => match &funcion.nodo {
+            NodoExpresion::Variable(funcion) => entorno.obtener_funcion(funcion).map(|f| f.tipo_retorno.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(tipo) = estatico.filter(|tipo| *tipo != Tipo::Vacio) {
+        return Ok(tipo);
+    }
+    valor.tipo().ok_or_else(|| {
+        let motivo = match valor {
+            Valor::Nulo => format!("a partir de nulo; escribe el tipo, como 'cadena? {} = nulo'", nombre),
+            _ => "porque la expresión no produce ningún valor".to_string(),
+        };
+        Diagnostico::en(
+            diagnostico::TIPO_NO_INFERIBLE,
+            expresion.pos,
+            format!("No se puede inferir el tipo de '{}' {}", nombre, motivo),
+        )
+    })
+}
+
+/// Mensaje de error para un valor que no se puede guardar en `ruta`, que es
+/// el nombre de una variable o un elemento como `matriz[1][2]`.
+pub fn no_asignable(obtenido: &str, ruta: &str, tipo: &Tipo) -> String {
+    let destino = if ruta.ends_with(']') { "al elemento" } else { "a la variable" };
+    format!("No se puede asignar un valor de tipo {} {} '{}' de tipo {}", obtenido, destino, ruta, tipo)
+}
+
+/// Ajusta un valor al tipo declarado de `ruta` al declarar la variable. En
+/// una `lista<T>` se ajusta cada elemento a `T`.
+fn convertir_a_tipo(tipo: &Tipo, ruta: &str, valor: Valor, entorno: &Entorno) -> Result<Valor, Diagnostico> {
+    let incompatible = |valor: &Valor| {
+        Diagnostico::nuevo(diagnostico::TIPOS_INCOMPATIBLES, no_asignable(&valor.nombre_tipo(), ruta, tipo))
+    };
+    match (tipo.resuelto(), valor) {
+        (Tipo::Opcional(_), Valor::Nulo) => Ok(Valor::Nulo),
+        (Tipo::Opcional(tipo), valor) => convertir_a_tipo(tipo, ruta, valor, entorno),
+        (_, Valor::Nulo) => Err(Diagnostico::nuevo(
+            diagnostico::VALOR_NULO,
+            format!("{}; solo los tipos opcionales como {}? aceptan nulo", no_asignable("nulo", ruta, tipo), tipo),
+        )),
+        (Tipo::Entero, Valor::Entero(i)) => Ok(Valor::Entero(i)),
+        (Tipo::Entero, Valor::Numero(n)) => Ok(Valor::Entero(n as i64)),
+        (Tipo::Numero, Valor::Numero(n)) => Ok(Valor::Numero(n)),
+        (Tipo::Numero, Valor::Entero(i)) => Ok(Valor::Numero(i as f64)),
+        (Tipo::Cadena, Valor::Cadena(c)) => Ok(Valor::Cadena(c)),
+        (Tipo::Cadena, otro) => Ok(Valor::Cadena(otro.a_cadena())),
+        (Tipo::Bool, Valor::Bool(b)) => Ok(Valor::Bool(b)),
+        (Tipo::Lista(None), Valor::Lista(elementos)) => Ok(Valor::Lista(elementos)),
+        (Tipo::Lista(Some(tipo_elemento)), Valor::Lista(elementos)) => {
+            let mut convertidos = Vec::with_capacity(elementos.len());
+            for (i, elemento) in elementos.into_iter().enumerate() {
+                let ruta_elemento = format!("{}[{}]", ruta, i);
+                convertidos.push(convertir_a_tipo(tipo_elemento, &ruta_elemento, elemento, entorno)?);
+            }
+            Ok(Valor::Lista(convertidos))
+        }
+        (Tipo::Jsn, valor @ Valor::Enumeracion(..)) => Ok(valor.a_jsn()),
+        (Tipo::Jsn, valor) => Ok(valor),
+        (Tipo::Vacio, Valor::Vacio) => Ok(Valor::Vacio),
+        (Tipo::Nombre(objeto), valor) => {
+            if entorno.obtener_objeto(objeto).is_none() && entorno.obtener_enumeracion(objeto).is_none() {
+                return Err(Diagnostico::nuevo(diagnostico::TIPO_DESCONOCIDO, format!("Tipo desconocido: {}", objeto)));
+            }
+            match valor {
+                Valor::Instancia(tipo_instancia, campos) if tipo_instancia == *objeto => {
+                    Ok(Valor::Instancia(tipo_instancia, campos))
                 }
-            }
-            
-            // Ignorar líneas que solo contienen estructuras de control (como } sino {)
-            if linea.trim().starts_with("}") && linea.contains("sino") {
-                continue;
-            }
-            
-            if let Err(error) = procesar_declaracion(linea, entorno) {
-                if let Err(_) = procesar_expresion(linea, inicio + indice - 1, entorno) {
-                    return Err(formatear_error(inicio + indice - 1, &error));
+                Valor::Enumeracion(tipo_valor, variante, datos) if tipo_valor == *objeto => {
+                    Ok(Valor::Enumeracion(tipo_valor, variante, datos))
                 }
+                otro => Err(incompatible(&otro)),
             }
         }
+        (_, otro) => Err(incompatible(&otro)),
     }
-    Ok(())
 }
 
-fn procesar_declaracion(linea: &str, entorno: &mut Entorno) -> Result<(), String> {
-    let tokens: Vec<&str> = linea.split_whitespace().collect();
-    if tokens.len() < 2 {
-        return Err("Declaración inválida".to_string());
-    }
-    
-    let primer_token = tokens[0];
-    
-    // Verificar si el primer token es un tipo válido
-    let tipos_validos = ["vacio", "entero", "número", "cadena", "bool", "lista", "jsn", "mutable"];
-    if !tipos_validos.contains(&primer_token) && !primer_token.starts_with("lista<") {
-        return Err("No es una declaración válida".to_string());
-    }
-    
-    // Debug: imprimir tokens para entender el problema
-    // eprintln!("DEBUG: Procesando línea: '{}'", linea);
-    // eprintln!("DEBUG: Tokens: {:?}", tokens);
-    
-    let mut tipo = tokens[0];
-    let mut indice_tipo = 0;
-    
-    // Manejar el caso de variables mutables que pueden empezar con 'mutable'
-    if tipo == "mutable" {
-        if tokens.len() < 3 {
-            return Err("Declaración mutable inválida".to_string());
-        }
-        indice_tipo = 1;
-        tipo = tokens[1];
-    }
-    
-    // Soporte para tipos genéricos como `lista<entero>` simplemente
-    // identificando el tipo base antes del carácter '<'
-    if let Some(inicio) = tipo.find('<') {
-        if tipo.ends_with('>') {
-            tipo = &tipo[..inicio];
-        }
-    }
-    
-    let mut indice = indice_tipo + 1;
-    
-    // Verificar si hay 'mut' después del tipo (sintaxis: tipo mut nombre)
-    if tokens.get(indice).copied() == Some("mut") {
-        indice += 1;
-    }
-    
-    let nombre = tokens.get(indice).ok_or("Falta nombre de variable")?;
-    
-    // eprintln!("DEBUG: tipo='{}', indice={}, nombre='{}'", tipo, indice, nombre);
-    
-    // Validar nombre de variable
-    if es_palabra_reservada(nombre) {
-        return Err(format!("'{}' es una palabra reservada y no puede usarse como nombre de variable", nombre));
-    }
-    
-    if !es_nombre_variable_valido(nombre) {
-        return Err(format!("Nombre de variable inválido: {}", nombre));
+/// Comprueba un valor que se reasigna a `ruta` contra su tipo declarado. Como
+/// en una llamada, no hay conversiones: solo se amplía un entero a número.
+fn ajustar_asignacion(tipo: &Tipo, ruta: &str, valor: Valor) -> Result<Valor, Diagnostico> {
+    ajustar_a_tipo_declarado(tipo, valor).map_err(|valor| match valor {
+        Valor::Nulo => Diagnostico::nuevo(
+            diagnostico::VALOR_NULO,
+            format!("{}; solo los tipos opcionales como {}? aceptan nulo", no_asignable("nulo", ruta, tipo), tipo),
+        ),
+        valor => Diagnostico::nuevo(diagnostico::TIPOS_INCOMPATIBLES, no_asignable(&valor.nombre_tipo(), ruta, tipo)),
+    })
+}
+
+/// Falla si la variable se declaró sin `mut` o `mutable`; la nota del error
+/// señala la declaración.
+fn comprobar_mutable(nombre: &str, entorno: &Entorno) -> Result<(), Diagnostico> {
+    match entorno.enlace(nombre) {
+        Some(enlace) if !enlace.mutable => {
+            let error = Diagnostico::nuevo(
+                diagnostico::VARIABLE_INMUTABLE,
+                format!("No se puede modificar la variable inmutable '{}'", nombre),
+            );
+            Err(match enlace.declarada_en {
+                Some(pos) => error.con_nota(pos, format!("'{}' se declaró aquí sin 'mut'", nombre)),
+                None => error,
+            })
+        }
+        _ => Ok(()),
     }
-    
-    indice += 1;
-    let valor = if tokens.get(indice) == Some(&"=") {
-        indice += 1;
-        let valor_cadena = tokens[indice..].join(" ");
-        match tipo {
-            "entero" => {
-                if let Ok(v) = valor_cadena.parse::<i64>() {
-                    Valor::Entero(v)
-                } else {
-                    let resultado = evaluar_expresion_valor(&valor_cadena, entorno)?;
-                    match resultado {
-                        Valor::Entero(i) => Valor::Entero(i),
-                        Valor::Numero(n) => Valor::Entero(n as i64),
-                        _ => return Err("Valor entero inválido".to_string()),
-                    }
-                }
-            }
-            "número" => {
-                if let Ok(v) = valor_cadena.parse::<f64>() {
-                    Valor::Numero(v)
-                } else {
-                    let resultado = evaluar_expresion_valor(&valor_cadena, entorno)?;
-                    match resultado {
-                        Valor::Numero(n) => Valor::Numero(n),
-                        Valor::Entero(i) => Valor::Numero(i as f64),
-                        _ => return Err("Valor numérico inválido".to_string()),
-                    }
-                }
-            }
-            "cadena" => {
-                if (valor_cadena.starts_with('"') && valor_cadena.ends_with('"')) ||
-                   (valor_cadena.starts_with('\'') && valor_cadena.ends_with('\'')) {
-                    Valor::Cadena(valor_cadena.trim_matches(|c| c == '"' || c == '\'').to_string())
-                } else {
-                    let resultado = evaluar_expresion_valor(&valor_cadena, entorno)?;
-                    match resultado {
-                        Valor::Cadena(c) => Valor::Cadena(c),
-                        other => Valor::Cadena(other.a_cadena()),
-                    }
-                }
-            }
-            "bool" => match valor_cadena.as_str() {
-                "verdadero" => Valor::Bool(true),
-                "falso" => Valor::Bool(false),
-                _ => {
-                    let resultado = evaluar_expresion_valor(&valor_cadena, entorno)?;
-                    match resultado {
-                        Valor::Bool(b) => Valor::Bool(b),
-                        _ => return Err("Valor bool inválido".to_string()),
-                    }
-                }
-            },
-            "lista" => {
-                if valor_cadena.starts_with('[') && valor_cadena.ends_with(']') {
-                    // Es una lista literal
-                    let contenido = &valor_cadena[1..valor_cadena.len() - 1];
-                    let mut elementos = Vec::new();
-                    if !contenido.trim().is_empty() {
-                        // Dividir respetando listas anidadas
-                        let elementos_texto = dividir_elementos_lista(contenido)?;
-                        for texto_elemento in elementos_texto {
-                            elementos.push(parsear_elemento_lista(texto_elemento.trim(), entorno)?);
-                        }
-                    }
-                    Valor::Lista(elementos)
-                } else {
-                    // Es una expresión que debe evaluarse
-                    let resultado = evaluar_expresion_valor(&valor_cadena, entorno)?;
-                    match resultado {
-                        Valor::Lista(_) => resultado,
-                        _ => return Err("El valor no es una lista".to_string()),
-                    }
-                }
-            }
-            "jsn" => {
-                // Verificar si es una expresión (contiene método) o JSON directo
-                if valor_cadena.contains('.') && valor_cadena.contains('(') && valor_cadena.ends_with(')') {
-                    // Es una llamada a método, evaluar como expresión
-                    evaluar_expresion_valor(&valor_cadena, entorno)?
-                } else {
-                    // Es JSON directo, parsear
-                    parsear_jsn(&valor_cadena)?
-                }
-            },
-            _ => {
-                if let Some(obj) = entorno.obtener_objeto(tipo) {
-                    if valor_cadena.starts_with("nuevo") {
-                        let inicio = valor_cadena.find('(').ok_or("Instancia inválida")?;
-                        let fin = valor_cadena.rfind(')').ok_or("Instancia inválida")?;
-                        let args_str = &valor_cadena[inicio + 1..fin];
-                        let mut args = Vec::new();
-                        if !args_str.trim().is_empty() {
-                            for a in args_str.split(',') {
-                                args.push(obtener_valor(a.trim(), entorno)?);
-                            }
-                        }
-                        instanciar_objeto(obj, args)
-                    } else {
-                        return Err("Instancia de objeto inválida".to_string());
-                    }
-                } else {
-                    return Err(format!("Tipo desconocido: {}", tipo));
-                }
-            }
-        }
-    } else {
-        Valor::valor_por_defecto(tipo).ok_or_else(|| format!("Tipo desconocido: {}", tipo))?
-    };
+}
 
+/// Guarda un nuevo valor en una variable existente, que debe ser de su tipo
+/// declarado.
+fn asignar_variable(nombre: &str, valor: Valor, entorno: &mut Entorno) -> Result<(), Diagnostico> {
+    let valor = match entorno.tipo_de(nombre) {
+        Some(tipo) => ajustar_asignacion(tipo, nombre, valor)?,
+        None => valor,
+    };
     entorno.establecer(nombre, valor);
     Ok(())
 }
 
-fn parsear_literal(texto: &str) -> Result<Valor, String> {
-    if texto.starts_with('"') && texto.ends_with('"') {
-        Ok(Valor::Cadena(texto.trim_matches('"').to_string()))
-    } else if texto == "verdadero" {
-        Ok(Valor::Bool(true))
-    } else if texto == "falso" {
-        Ok(Valor::Bool(false))
-    } else if let Ok(entero) = texto.parse::<i64>() {
-        Ok(Valor::Entero(entero))
-    } else if let Ok(numero) = texto.parse::<f64>() {
-        Ok(Valor::Numero(numero))
-    } else {
-        Err("Literal inválido".to_string())
+fn procesar_asignacion(nombre: &str, valor: &Expresion, entorno: &mut Entorno) -> Result<(), Diagnostico> {
+    // Verificar que la variable existe antes de asignar
+    if entorno.obtener(nombre).is_none() {
+        return Err(no_encontrada(nombre));
     }
+    comprobar_mutable(nombre, entorno)?;
+    let resultado = evaluar_expresion(valor, entorno)?;
+    asignar_variable(nombre, resultado, entorno).map_err(|e| e.con_posicion(valor.pos))
 }
 
+fn procesar_asignacion_indice(nombre: &str, indices: &[Expresion], valor: &Expresion, entorno: &mut Entorno) -> Result<(), Diagnostico> {
+    let mut contenedor = entorno.obtener(nombre).cloned()
+        .ok_or_else(|| no_encontrada(nombre))?;
+    comprobar_mutable(nombre, entorno)?;
 
-fn parsear_jsn(texto: &str) -> Result<Valor, String> {
-    fn saltar(bl: &[u8], i: &mut usize) {
-        while *i < bl.len() && bl[*i].is_ascii_whitespace() {
-            *i += 1;
-        }
+    let mut pasos = Vec::with_capacity(indices.len());
+    for indice in indices {
+        pasos.push((Paso::Indice(evaluar_expresion(indice, entorno)?), indice.pos));
     }
+    let mut nuevo = evaluar_expresion(valor, entorno)?;
 
-    fn leer_cadena(bl: &[u8], i: &mut usize) -> Result<String, String> {
-        *i += 1; // salta la comilla inicial
-        let inicio = *i;
-        while *i < bl.len() {
-            if bl[*i] == b'"' {
-                let s = String::from_utf8(bl[inicio..*i].to_vec()).map_err(|_| "Cadena inválida".to_string())?;
-                *i += 1;
-                return Ok(s);
-            }
-            *i += 1;
-        }
-        Err("Cadena sin cerrar".to_string())
+    // En una `lista<T>` el valor debe ser del tipo de sus elementos
+    let mut tipo = entorno.tipo_de(nombre).cloned();
+    let mut ruta = nombre.to_string();
+    for indice in indices {
+        tipo = match tipo.as_ref().map(Tipo::resuelto) {
+            Some(Tipo::Lista(Some(tipo_elemento))) => Some((**tipo_elemento).clone()),
+            _ => None,
+        };
+        ruta.push_str(&format!("[{}]", indice));
     }
-
-    fn leer_identificador(bl: &[u8], i: &mut usize) -> String {
-        let inicio = *i;
-        while *i < bl.len() && (bl[*i].is_ascii_alphanumeric() || bl[*i] == b'_') {
-            *i += 1;
-        }
-        String::from_utf8_lossy(&bl[inicio..*i]).to_string()
+    if let Some(tipo) = tipo {
+        nuevo = ajustar_asignacion(&tipo, &ruta, nuevo).map_err(|e| e.con_posicion(valor.pos))?;
     }
 
-    fn leer_valor(bl: &[u8], i: &mut usize) -> Result<Valor, String> {
-        saltar(bl, i);
-        if *i >= bl.len() {
-            return Err("JSON incompleto".to_string());
-        }
-        match bl[*i] {
-            b'{' => leer_objeto(bl, i),
-            b'[' => leer_lista(bl, i),
-            b'"' => Ok(Valor::Cadena(leer_cadena(bl, i)?)),
-            b'-' | b'0'..=b'9' => leer_numero(bl, i),
-            b'v' => {
-                if bl.len() >= *i + 9 && &bl[*i..*i + 9] == b"verdadero" {
-                    *i += 9;
-                    Ok(Valor::Bool(true))
-                } else {
-                    Err("Valor bool inválido".to_string())
-                }
-            }
-            b'f' => {
-                if bl.len() >= *i + 5 && &bl[*i..*i + 5] == b"falso" {
-                    *i += 5;
-                    Ok(Valor::Bool(false))
-                } else if bl.len() >= *i + 5 && &bl[*i..*i + 5] == b"false" {
-                    *i += 5;
-                    Ok(Valor::Bool(false))
-                } else {
-                    Err("Valor bool inválido".to_string())
-                }
-            }
-            b't' => {
-                if bl.len() >= *i + 4 && &bl[*i..*i + 4] == b"true" {
-                    *i += 4;
-                    Ok(Valor::Bool(true))
-                } else {
-                    Err("Valor bool inválido".to_string())
-                }
-            }
-            _ => Err("JSON inválido".to_string()),
-        }
-    }
+    colocar_en_ruta(&mut contenedor, &pasos, nuevo)?;
+    entorno.establecer(nombre, contenedor);
+    Ok(())
+}
 
-    fn leer_numero(bl: &[u8], i: &mut usize) -> Result<Valor, String> {
-        let inicio = *i;
-        if bl[*i] == b'-' { *i += 1; }
-        while *i < bl.len() && (bl[*i].is_ascii_digit() || bl[*i] == b'.') {
-            *i += 1;
+/// Un paso desde una variable hasta un valor que contiene: un índice, como
+/// `[0]` en `m[0]`, o una propiedad, como `.items` en `p.items`.
+enum Paso {
+    Indice(Valor),
+    /// Nombre de la propiedad y si se accede con `?.`.
+    Propiedad(String, bool),
+}
+
+/// Variable en la que empieza una ruta y sus pasos, con la posición de cada
+/// uno para los errores.
+type Ruta = (String, Vec<(Paso, Posicion)>);
+
+/// Recorre los pasos dentro del contenedor y guarda el valor en el último.
+fn colocar_en_ruta(contenedor: &mut Valor, pasos: &[(Paso, Posicion)], valor: Valor) -> Result<(), Diagnostico> {
+    let ((paso, pos), resto) = match pasos.split_first() {
+        Some(partes) => partes,
+        None => {
+            *contenedor = valor;
+            return Ok(());
         }
-        let s = String::from_utf8_lossy(&bl[inicio..*i]);
-        if s.contains('.') {
-            s.parse::<f64>().map(Valor::Numero).map_err(|_| "Número inválido".to_string())
-        } else {
-            s.parse::<i64>().map(Valor::Entero).map_err(|_| "Número inválido".to_string())
+    };
+    let destino = match (contenedor, paso) {
+        (Valor::Lista(elementos), Paso::Indice(Valor::Entero(i))) => {
+            let longitud = elementos.len();
+            if *i < 0 || *i as usize >= longitud {
+                return Err(fuera_de_rango(*i, longitud).con_posicion(*pos));
+            }
+            &mut elementos[*i as usize]
+        }
+        (Valor::Objeto(mapa), Paso::Indice(Valor::Cadena(clave)) | Paso::Propiedad(clave, _)) => {
+            if resto.is_empty() {
+                mapa.insert(clave.clone(), valor);
+                return Ok(());
+            }
+            mapa.get_mut(clave)
+                .ok_or_else(|| propiedad_inexistente(clave).con_posicion(*pos))?
+        }
+        (Valor::Instancia(_, campos), Paso::Propiedad(nombre, _)) => campos
+            .get_mut(nombre)
+            .ok_or_else(|| propiedad_inexistente(nombre).con_posicion(*pos))?,
+        (contenedor, Paso::Indice(clave)) => {
+            return Err(Diagnostico::en(
+                diagnostico::TIPOS_INCOMPATIBLES,
+                *pos,
+                format!(
+                    "No se puede asignar en un valor de tipo {} con un índice de tipo {}",
+                    contenedor.nombre_tipo(),
+                    clave.nombre_tipo()
+                ),
+            ))
+        }
+        (contenedor, Paso::Propiedad(nombre, _)) => {
+            return Err(Diagnostico::en(
+                diagnostico::MIEMBRO_NO_ENCONTRADO,
+                *pos,
+                format!("Un valor de tipo {} no tiene la propiedad '{}'", contenedor.nombre_tipo(), nombre),
+            ))
         }
-    }
+    };
+    colocar_en_ruta(destino, resto, valor)
+}
 
-    fn leer_lista(bl: &[u8], i: &mut usize) -> Result<Valor, String> {
-        *i += 1; // salta '['
-        let mut elementos = Vec::new();
-        loop {
-            saltar(bl, i);
-            if *i >= bl.len() { return Err("Lista sin cerrar".to_string()); }
-            if bl[*i] == b']' {
-                *i += 1;
-                break;
-            }
-            elementos.push(leer_valor(bl, i)?);
-            saltar(bl, i);
-            if *i < bl.len() && bl[*i] == b',' {
-                *i += 1;
-            } else if *i < bl.len() && bl[*i] == b']' {
-                *i += 1;
-                break;
-            } else {
-                return Err("Lista inválida".to_string());
-            }
+/// Variable y pasos hasta el valor de `objeto` cuando este es una variable o
+/// un elemento o una propiedad de una, como `m[0]` o `p.items`. Los índices
+/// se evalúan una sola vez, para leer el valor y para guardarlo de nuevo.
+fn ruta_de(objeto: &Expresion, entorno: &mut Entorno) -> Result<Option<Ruta>, Diagnostico> {
+    let contenedor = match &objeto.nodo {
+        NodoExpresion::Variable(nombre) if entorno.obtener(nombre).is_some() => {
+            return Ok(Some((nombre.clone(), Vec::new())))
         }
-        Ok(Valor::Lista(elementos))
-    }
+        NodoExpresion::Indice { objeto, .. } | NodoExpresion::Miembro { objeto, .. } => objeto,
+        _ => return Ok(None),
+    };
+    let Some((raiz, mut pasos)) = ruta_de(contenedor, entorno)? else {
+        return Ok(None);
+    };
+    let paso = match &objeto.nodo {
+        NodoExpresion::Indice { indice, .. } => Paso::Indice(evaluar_expresion(indice, entorno)?),
+        NodoExpresion::Miembro { nombre, opcional, .. } => Paso::Propiedad(nombre.clone(), *opcional),
+        _ => return Ok(None),
+    };
+    pasos.push((paso, objeto.pos));
+    Ok(Some((raiz, pasos)))
+}
 
-    fn leer_objeto(bl: &[u8], i: &mut usize) -> Result<Valor, String> {
-        *i += 1; // salta '{'
-        let mut mapa = std::collections::HashMap::new();
-        loop {
-            saltar(bl, i);
-            if *i >= bl.len() { return Err("JSON sin cerrar".to_string()); }
-            if bl[*i] == b'}' {
-                *i += 1;
-                break;
-            }
-            let clave = if bl[*i] == b'"' { leer_cadena(bl, i)? } else { leer_identificador(bl, i) };
-            saltar(bl, i);
-            if *i >= bl.len() || bl[*i] != b':' { return Err("JSON inválido".to_string()); }
-            *i += 1;
-            let valor = leer_valor(bl, i)?;
-            mapa.insert(clave, valor);
-            saltar(bl, i);
-            if *i < bl.len() && bl[*i] == b',' {
-                *i += 1;
-                continue;
-            } else if *i < bl.len() && bl[*i] == b'}' {
-                *i += 1;
-                break;
-            } else {
-                return Err("JSON inválido".to_string());
-            }
+/// Valor al que llevan los pasos desde la variable.
+fn valor_en_ruta(raiz: &str, pasos: &[(Paso, Posicion)], entorno: &Entorno) -> Result<Valor, Diagnostico> {
+    let mut valor = entorno.obtener(raiz).cloned().ok_or_else(|| no_encontrada(raiz))?;
+    for (paso, pos) in pasos {
+        valor = match paso {
+            Paso::Indice(clave) => acceder_indice(valor, clave.clone()),
+            Paso::Propiedad(nombre, opcional) => acceder_miembro(valor, nombre, *opcional),
         }
-        Ok(Valor::Objeto(mapa))
-    }
-
-    let bytes = texto.as_bytes();
-    let mut i = 0;
-    let valor = leer_valor(bytes, &mut i)?;
-    saltar(bytes, &mut i);
-    if i != bytes.len() {
-        return Err("JSON inválido".to_string());
+        .map_err(|e| e.con_posicion(*pos))?;
     }
     Ok(valor)
 }
 
-// Función para detectar declaraciones de funciones con sintaxis Quetzal
-fn es_declaracion_funcion(linea: &str) -> bool {
-    let linea = linea.trim();
-    
-    // Verificar si empieza con "asincrono"
-    let linea_sin_async = if linea.starts_with("asincrono ") {
-        &linea[10..]
-    } else {
-        linea
-    };
-    
-    let tokens: Vec<&str> = linea_sin_async.split_whitespace().collect();
-    if tokens.len() < 2 {
-        return false;
-    }
-    
-    let tipo = tokens[0];
-    // Verificar que el primer token sea un tipo válido
-    if !["vacio", "entero", "número", "cadena", "bool", "lista", "jsn"].contains(&tipo) {
-        return false;
-    }
-    
-    // Verificar que tenga patrón nombre_funcion(
-    let resto = &linea_sin_async[tipo.len()..].trim_start();
-    if let Some(pos_paren) = resto.find('(') {
-        let nombre_parte = &resto[..pos_paren].trim();
-        return !nombre_parte.is_empty() && !nombre_parte.contains(' ');
+/// `x += v` equivale a `x = x + v`: el resultado de la operación debe ser
+/// del tipo declarado de la variable como en cualquier asignación.
+fn procesar_asignacion_compuesta(nombre: &str, operador: OperadorBinario, valor: &Expresion, entorno: &mut Entorno) -> Result<(), Diagnostico> {
+    let valor_actual = entorno.obtener(nombre).cloned()
+        .ok_or_else(|| no_encontrada(nombre))?;
+    comprobar_mutable(nombre, entorno)?;
+    let valor_nuevo = evaluar_expresion(valor, entorno)?;
+    let resultado = evaluar_operacion_binaria(operador, valor_actual, valor_nuevo)?;
+    asignar_variable(nombre, resultado, entorno).map_err(|e| e.con_posicion(valor.pos))
+}
+
+fn aplicar_incremento(nombre: &str, cambio: i64, entorno: &mut Entorno) -> Result<(), Diagnostico> {
+    let valor = entorno.obtener(nombre).cloned().ok_or_else(|| no_encontrada(nombre))?;
+    comprobar_mutable(nombre, entorno)?;
+    match (entorno.tipo_de(nombre).map(Tipo::resuelto), valor) {
+        (Some(Tipo::Entero) | None, Valor::Entero(i)) => {
+            let nuevo = i.checked_add(cambio).ok_or_else(|| {
+                desbordamiento(format!("{}{}", nombre, if cambio > 0 { "++" } else { "--" }))
+            })?;
+            entorno.establecer(nombre, Valor::Entero(nuevo));
+            Ok(())
+        }
+        _ => Err(Diagnostico::nuevo(
+            diagnostico::TIPOS_INCOMPATIBLES,
+            format!("Solo se pueden incrementar o decrementar variables enteras: '{}'", nombre),
+        )),
     }
-    
-    false
 }
 
-fn procesar_declaracion_funcion_quetzal(linea: &str, bloque: &[String], entorno: &mut Entorno) -> Result<(), String> {
-    let linea = linea.trim().trim_end_matches('{').trim();
-    
-    // Verificar si es asíncrona
-    let linea_sin_async = if linea.starts_with("asincrono ") {
-        &linea[10..]
-    } else {
-        linea
+pub fn funcion_impresion(nombre: &str) -> Option<fn(&str)> {
+    let funcion: fn(&str) = match nombre {
+        "imprimir" => |t| println!("{}", t),
+        "imprimir_error" => consola::imprimir_error,
+        "imprimir_advertencia" => consola::imprimir_advertencia,
+        "imprimir_informacion" => consola::imprimir_informacion,
+        "imprimir_depurar" => consola::imprimir_depurar,
+        "imprimir_exito" => consola::imprimir_exito,
+        "imprimir_alerta" => consola::imprimir_alerta,
+        "imprimir_confirmacion" => consola::imprimir_confirmacion,
+        _ => return None,
     };
-    
-    let tokens: Vec<&str> = linea_sin_async.split_whitespace().collect();
-    if tokens.is_empty() {
-        return Err("Declaración de función inválida".to_string());
-    }
-    
-    let tipo_retorno = tokens[0].to_string();
-    
-    // Encontrar nombre y parámetros
-    let resto = &linea_sin_async[tipo_retorno.len()..].trim_start();
-    let inicio_parentesis = resto.find('(').ok_or("Sintaxis de función inválida")?;
-    let fin_parentesis = resto.rfind(')').ok_or("Sintaxis de función inválida")?;
-    
-    let nombre = resto[..inicio_parentesis].trim().to_string();
-    let params_str = &resto[inicio_parentesis + 1..fin_parentesis];
-    
-    if nombre.is_empty() {
-        return Err("Nombre de función vacío".to_string());
-    }
-    
-    // Verificar que no sea palabra reservada
-    if es_palabra_reservada(&nombre) {
-        return Err(format!("'{}' es una palabra reservada y no puede usarse como nombre de función", nombre));
-    }
-    
-    // Parsear parámetros: tipo nombre, tipo mut nombre, etc.
-    let mut parametros = Vec::new();
-    if !params_str.trim().is_empty() {
-        for param in params_str.split(',') {
-            let param = param.trim();
-            let tokens_param: Vec<&str> = param.split_whitespace().collect();
-            
-            if tokens_param.len() < 2 {
-                return Err("Parámetro de función mal formado".to_string());
-            }
-            
-            let mut tipo_param = tokens_param[0].to_string();
-            let mut nombre_param_inicio = 1;
-            
-            // Manejar parámetros mutables: tipo mut nombre
-            if tokens_param.len() > 2 && tokens_param[1] == "mut" {
-                tipo_param = format!("{} mut", tipo_param);
-                nombre_param_inicio = 2;
-            }
-            
-            if tokens_param.len() <= nombre_param_inicio {
-                return Err("Falta nombre del parámetro".to_string());
-            }
-            
-            let nombre_param = tokens_param[nombre_param_inicio].to_string();
-            
-            if es_palabra_reservada(&nombre_param) {
-                return Err(format!("'{}' es una palabra reservada y no puede usarse como nombre de parámetro", nombre_param));
-            }
-            
-            parametros.push((nombre_param, tipo_param));
+    Some(funcion)
+}
+
+fn manejar_impresion<F>(argumentos: &[Expresion], entorno: &mut Entorno, func: F) -> Result<(), Diagnostico>
+where
+    F: Fn(&str),
+{
+    match argumentos {
+        [] => func(""),
+        [argumento] => {
+            let texto = evaluar_expresion(argumento, entorno)?.a_cadena();
+            func(&texto);
+        }
+        _ => {
+            return Err(Diagnostico::nuevo(
+                diagnostico::NUMERO_DE_ARGUMENTOS,
+                "Las funciones de impresión reciben un solo argumento",
+            ))
         }
     }
-    
-    let def_funcion = DefFuncion {
-        nombre: nombre.clone(),
-        parametros,
-        tipo_retorno,
-        cuerpo: bloque.to_vec(),
-    };
-    
-    entorno.definir_funcion(def_funcion);
     Ok(())
 }
 
-// Función para verificar si un nombre es palabra reservada
-fn es_palabra_reservada(nombre: &str) -> bool {
-    let palabras_reservadas = [
-        "vacio", "entero", "número", "numero", "cadena", "bool", "verdadero", "falso",
-        "lista", "jsn", "mut", "tipo", "publico", "privado", "libre", "fn", "retornar",
-        "objeto", "nuevo", "ambiente", "asincrono", "esperar", "si", "sino", "mientras",
-        "para", "hacer", "romper", "continuar", "intentar", "atrapar", "finalmente",
-        "lanzar", "excepción", "importar", "exportar", "desde", "como", "y", "o", "en"
-    ];
-    
-    palabras_reservadas.contains(&nombre)
+fn evaluar_condicion(condicion: &Expresion, entorno: &mut Entorno) -> Result<bool, Diagnostico> {
+    match evaluar_expresion(condicion, entorno)? {
+        Valor::Bool(b) => Ok(b),
+        otro => Err(Diagnostico::en(
+            diagnostico::TIPOS_INCOMPATIBLES,
+            condicion.pos,
+            format!("La condición debe ser bool, se obtuvo {}", otro.nombre_tipo()),
+        )),
+    }
 }
 
-// Función para validar nombres de variables (permite camelCase y snake_case)
-fn es_nombre_variable_valido(nombre: &str) -> bool {
-    if nombre.is_empty() {
-        return false;
-    }
-    
-    let primer_char = nombre.chars().next().unwrap();
-    // No puede empezar con número
-    if primer_char.is_ascii_digit() {
-        return false;
+fn procesar_condicional(ramas: &[(Expresion, Vec<Sentencia>)], sino: Option<&[Sentencia]>, entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    for (condicion, cuerpo) in ramas {
+        if evaluar_condicion(condicion, entorno)? {
+            return ejecutar_bloque(cuerpo, entorno);
+        }
     }
-    
-    // Debe empezar con letra o guion bajo
-    if !primer_char.is_alphabetic() && primer_char != '_' {
-        return false;
+    match sino {
+        Some(cuerpo) => ejecutar_bloque(cuerpo, entorno),
+        None => Ok(Flujo::Normal),
     }
-    
-    // Solo puede contener letras, números y guiones bajos
-    for c in nombre.chars() {
-        if !c.is_alphanumeric() && c != '_' {
-            return false;
+}
+
+/// El valor se evalúa una sola vez y se compara con los patrones de cada
+/// caso en orden. Las variables de un patrón se declaran antes de evaluar la
+/// guarda, que puede usarlas.
+fn procesar_segun(valor: &Expresion, casos: &[Caso], otro: Option<&[Sentencia]>, entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    let valor = evaluar_expresion(valor, entorno)?;
+    for caso in casos {
+        for patron in &caso.patrones {
+            let mut variables = Vec::new();
+            if !coincide(patron, &valor, &mut variables, entorno)? {
+                continue;
+            }
+            // Las variables del patrón solo existen en la guarda y el cuerpo
+            if let Some(flujo) = en_bloque(entorno, |entorno| ejecutar_caso(caso, variables, entorno))? {
+                return Ok(flujo);
+            }
         }
     }
-    
-    true
+    match otro {
+        Some(cuerpo) => ejecutar_bloque(cuerpo, entorno),
+        None => Ok(Flujo::Normal),
+    }
 }
 
-fn procesar_declaracion_funcion(linea: &str, bloque: &[String], entorno: &mut Entorno) -> Result<(), String> {
-    // Parsear la declaración de función
-    // Formato: funcion nombre(param1: tipo1, param2: tipo2) -> tipo_retorno
-    
-    let partes = linea.split("->").collect::<Vec<&str>>();
-    let declaracion = partes[0].trim().trim_end_matches('{').trim();
-    let tipo_retorno = if partes.len() > 1 {
-        partes[1].trim().trim_start_matches('{').trim().to_string()
-    } else {
-        "vacio".to_string()
-    };
-    
-    // Extraer nombre y parámetros
-    let inicio_parentesis = declaracion.find('(').ok_or("Sintaxis de función inválida")?;
-    let fin_parentesis = declaracion.rfind(')').ok_or("Sintaxis de función inválida")?;
-    
-    let partes_func: Vec<&str> = declaracion[..inicio_parentesis].split_whitespace().collect();
-    if partes_func.len() < 2 || partes_func[0] != "funcion" {
-        return Err("Sintaxis de función inválida".to_string());
+/// Declara las variables del patrón y ejecuta el cuerpo del caso si se
+/// cumple su guarda; `None` si no se cumple.
+fn ejecutar_caso(caso: &Caso, variables: Vec<(String, Valor)>, entorno: &mut Entorno) -> Result<Option<Flujo>, Diagnostico> {
+    for (nombre, valor) in variables {
+        let enlace = Enlace { tipo: None, mutable: false, declarada_en: Some(caso.pos) };
+        entorno.declarar(&nombre, valor, enlace)?;
     }
-    
-    let nombre = partes_func[1].to_string();
-    let params_str = &declaracion[inicio_parentesis + 1..fin_parentesis];
-    
-    // Parsear parámetros
-    let mut parametros = Vec::new();
-    if !params_str.trim().is_empty() {
-        for param in params_str.split(',') {
-            let param = param.trim();
-            if let Some(pos) = param.find(':') {
-                let nombre_param = param[..pos].trim().to_string();
-                let tipo_param = param[pos + 1..].trim().to_string();
-                parametros.push((nombre_param, tipo_param));
-            } else {
-                return Err("Parámetro de función mal formado".to_string());
-            }
+    if let Some(guarda) = &caso.guarda {
+        if !evaluar_condicion(guarda, entorno)? {
+            return Ok(None);
         }
     }
-    
-    let def_funcion = DefFuncion {
-        nombre: nombre.clone(),
-        parametros,
-        tipo_retorno,
-        cuerpo: bloque.to_vec(),
-    };
-    
-    entorno.definir_funcion(def_funcion);
-    Ok(())
+    ejecutar_sentencias(&caso.cuerpo, entorno).map(Some)
 }
 
-fn procesar_llamada_funcion(linea: &str, entorno: &mut Entorno) -> Result<(), String> {
-    // Parsear líneas del tipo: tipo variable = funcion()
-    let partes_asignacion: Vec<&str> = linea.split('=').collect();
-    if partes_asignacion.len() != 2 {
-        return Err("Sintaxis de asignación inválida".to_string());
-    }
-    
-    let izquierda = partes_asignacion[0].trim();
-    let llamada = partes_asignacion[1].trim();
-    
-    // Extraer el nombre de la variable (último token de la izquierda)
-    let tokens_izq: Vec<&str> = izquierda.split_whitespace().collect();
-    let variable_resultado = if tokens_izq.len() >= 2 {
-        tokens_izq[tokens_izq.len() - 1]
-    } else {
-        tokens_izq[0]
-    };
-    
-    // Funciones built-in
-    if llamada.starts_with("sumar(") {
-        let args = extraer_argumentos_funcion(llamada)?;
-        if args.len() >= 2 {
-            let val1 = evaluar_expresion_valor(&args[0], entorno)?;
-            let val2 = evaluar_expresion_valor(&args[1], entorno)?;
-            match (val1, val2) {
-                (Valor::Entero(a), Valor::Entero(b)) => {
-                    entorno.establecer(variable_resultado, Valor::Entero(a + b));
-                }
-                (Valor::Numero(a), Valor::Numero(b)) => {
-                    entorno.establecer(variable_resultado, Valor::Numero(a + b));
-                }
-                _ => return Err("Tipos incompatibles para suma".to_string()),
+fn procesar_intentar(cuerpo: &[Sentencia], atrapar: Option<&Atrapar>, finalmente: Option<&[Sentencia]>, entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    let resultado = match (ejecutar_bloque(cuerpo, entorno), atrapar) {
+        (Err(error), Some(atrapar)) => en_bloque(entorno, |entorno| {
+            if let Some(variable) = &atrapar.variable {
+                let enlace = Enlace { tipo: None, mutable: false, declarada_en: Some(atrapar.pos) };
+                entorno.declarar(variable, valor_atrapado(error), enlace)?;
             }
+            ejecutar_sentencias(&atrapar.cuerpo, entorno)
+        }),
+        (resultado, _) => resultado,
+    };
+    // `finalmente` se ejecuta también tras un error, `retornar`, `romper` o
+    // `continuar`; si él mismo interrumpe el flujo, eso reemplaza al
+    // resultado anterior
+    if let Some(finalmente) = finalmente {
+        match ejecutar_bloque(finalmente, entorno)? {
+            Flujo::Normal => {}
+            flujo => return Ok(flujo),
         }
-        return Ok(());
     }
-    
-    if llamada.starts_with("saludar(") {
-        let args = extraer_argumentos_funcion(llamada)?;
-        let nombre = if args.is_empty() {
-            "Mundo".to_string()
-        } else {
-            evaluar_expresion_valor(&args[0], entorno)?.a_cadena().trim_matches('"').to_string()
-        };
-        let saludo = if args.len() > 1 {
-            evaluar_expresion_valor(&args[1], entorno)?.a_cadena().trim_matches('"').to_string()
-        } else {
-            "Hola".to_string()
-        };
-        let resultado = format!("{}, {}!", saludo, nombre);
-        entorno.establecer(variable_resultado, Valor::Cadena(resultado));
-        return Ok(());
+    resultado
+}
+
+/// Valor que recibe `atrapar`: el que se pasó a `lanzar` o, para un error de
+/// ejecución, un jsn con su clase, código, mensaje y posición.
+fn valor_atrapado(error: Diagnostico) -> Valor {
+    if let Some(valor) = error.lanzado {
+        return *valor;
     }
-    
-    if llamada.starts_with("calcular_promedio(") {
-        let args = extraer_argumentos_funcion(llamada)?;
-        if !args.is_empty() {
-            let lista_val = evaluar_expresion_valor(&args[0], entorno)?;
-            if let Valor::Lista(elementos) = lista_val {
-                let mut suma = 0.0;
-                let mut count = 0;
-                for elem in elementos {
-                    match elem {
-                        Valor::Numero(n) => {
-                            suma += n;
-                            count += 1;
-                        }
-                        Valor::Entero(i) => {
-                            suma += i as f64;
-                            count += 1;
-                        }
-                        _ => {}
+    let (linea, columna) = match error.pos {
+        Some(pos) => (Valor::Entero(pos.linea as i64), Valor::Entero(pos.columna as i64)),
+        None => (Valor::Nulo, Valor::Nulo),
+    };
+    let mut campos = HashMap::new();
+    campos.insert("tipo".to_string(), Valor::Cadena(diagnostico::clase(error.codigo).to_string()));
+    campos.insert("codigo".to_string(), Valor::Cadena(error.codigo.to_string()));
+    campos.insert("mensaje".to_string(), Valor::Cadena(error.mensaje));
+    campos.insert("linea".to_string(), linea);
+    campos.insert("columna".to_string(), columna);
+    Valor::Objeto(campos)
+}
+
+/// Indica si el valor coincide con el patrón y agrega a `variables` los
+/// valores que el patrón guarda.
+fn coincide(patron: &Patron, valor: &Valor, variables: &mut Vec<(String, Valor)>, entorno: &mut Entorno) -> Result<bool, Diagnostico> {
+    let coincide = match patron {
+        Patron::Comodin => true,
+        Patron::Literal(literal) => {
+            let literal = evaluar_expresion(literal, entorno)?;
+            comparar(OperadorBinario::Igual, valor, &literal).unwrap_or(false)
+        }
+        Patron::Rango { desde, hasta, inclusivo } => {
+            let desde = evaluar_expresion(desde, entorno)?;
+            let hasta = evaluar_expresion(hasta, entorno)?;
+            let hasta_operador = if *inclusivo { OperadorBinario::MenorIgual } else { OperadorBinario::Menor };
+            comparar(OperadorBinario::MayorIgual, valor, &desde).unwrap_or(false)
+                && comparar(hasta_operador, valor, &hasta).unwrap_or(false)
+        }
+        Patron::Nombre(nombre) if entorno.obtener_objeto(nombre).is_some() || entorno.obtener_enumeracion(nombre).is_some() => {
+            valor.es_de_tipo(&Tipo::Nombre(nombre.clone()))
+        }
+        Patron::Nombre(nombre) => {
+            variables.push((nombre.clone(), valor.clone()));
+            true
+        }
+        Patron::Tipo { tipo, variable } => {
+            let es_del_tipo = valor.es_de_tipo(tipo);
+            if let Some(variable) = variable.as_ref().filter(|_| es_del_tipo) {
+                variables.push((variable.clone(), valor.clone()));
+            }
+            es_del_tipo
+        }
+        Patron::Lista(patrones) => match valor {
+            Valor::Lista(elementos) if elementos.len() == patrones.len() => {
+                coinciden_todos(patrones, elementos, variables, entorno)?
+            }
+            _ => false,
+        },
+        Patron::Jsn(campos) => match valor {
+            Valor::Objeto(mapa) | Valor::Instancia(_, mapa) => {
+                let mut todos = true;
+                for (clave, patron) in campos {
+                    todos = match mapa.get(clave) {
+                        Some(campo) => coincide(patron, campo, variables, entorno)?,
+                        None => false,
+                    };
+                    if !todos {
+                        break;
                     }
                 }
-                if count > 0 {
-                    entorno.establecer(variable_resultado, Valor::Numero(suma / count as f64));
-                } else {
-                    entorno.establecer(variable_resultado, Valor::Numero(0.0));
-                }
+                todos
             }
-        }
-        return Ok(());
-    }
-    
-    // Verificar si es una función definida por el usuario
-    let nombre_funcion = if let Some(pos) = llamada.find('(') {
-        &llamada[..pos]
-    } else {
-        return Err("Sintaxis de llamada de función inválida".to_string());
+            _ => false,
+        },
+        Patron::Variante { enumeracion, variante, datos: patrones } => match valor {
+            Valor::Enumeracion(tipo, nombre, datos) if tipo == enumeracion && nombre == variante => match patrones {
+                Some(patrones) if patrones.len() == datos.len() => coinciden_todos(patrones, datos, variables, entorno)?,
+                Some(_) => false,
+                None => true,
+            },
+            _ => false,
+        },
     };
-    
-    if let Some(def_funcion) = entorno.obtener_funcion(nombre_funcion).cloned() {
-        return ejecutar_funcion_usuario(&def_funcion, llamada, variable_resultado, entorno);
+    Ok(coincide)
+}
+
+fn coinciden_todos(patrones: &[Patron], valores: &[Valor], variables: &mut Vec<(String, Valor)>, entorno: &mut Entorno) -> Result<bool, Diagnostico> {
+    for (patron, valor) in patrones.iter().zip(valores) {
+        if !coincide(patron, valor, variables, entorno)? {
+            return Ok(false);
+        }
     }
-    
-    Err("Función no reconocida".to_string())
-}
-
-fn extraer_argumentos_funcion(llamada: &str) -> Result<Vec<String>, String> {
-    let inicio = llamada.find('(').ok_or("Función inválida")?;
-    let fin = llamada.rfind(')').ok_or("Función inválida")?;
-    let contenido = &llamada[inicio + 1..fin];
-    if contenido.trim().is_empty() {
-        return Ok(Vec::new());
-    }
-    Ok(contenido.split(',').map(|s| s.trim().to_string()).collect())
-}
-
-fn procesar_asignacion_compuesta(linea: &str, entorno: &mut Entorno, linea_num: usize) -> Result<(), String> {
-    if let Some(pos) = linea.find("+=") {
-        let variable = linea[..pos].trim();
-        let valor_expr = linea[pos + 2..].trim();
-        
-        let valor_actual = entorno.obtener(variable).cloned()
-            .ok_or_else(|| formatear_error(linea_num, "Variable no encontrada"))?;
-        let valor_nuevo = evaluar_expresion_valor(valor_expr, entorno)?;
-        
-        match (valor_actual, valor_nuevo) {
-            (Valor::Entero(a), Valor::Entero(b)) => {
-                entorno.establecer(variable, Valor::Entero(a + b));
-            }
-            (Valor::Numero(a), Valor::Numero(b)) => {
-                entorno.establecer(variable, Valor::Numero(a + b));
-            }
-            (Valor::Cadena(a), Valor::Cadena(b)) => {
-                entorno.establecer(variable, Valor::Cadena(a + &b));
-            }
-            _ => return Err(formatear_error(linea_num, "Tipos incompatibles para +=")),
-        }
-    } else if let Some(pos) = linea.find("-=") {
-        let variable = linea[..pos].trim();
-        let valor_expr = linea[pos + 2..].trim();
-        
-        let valor_actual = entorno.obtener(variable).cloned()
-            .ok_or_else(|| formatear_error(linea_num, "Variable no encontrada"))?;
-        let valor_nuevo = evaluar_expresion_valor(valor_expr, entorno)?;
-        
-        match (valor_actual, valor_nuevo) {
-            (Valor::Entero(a), Valor::Entero(b)) => {
-                entorno.establecer(variable, Valor::Entero(a - b));
-            }
-            (Valor::Numero(a), Valor::Numero(b)) => {
-                entorno.establecer(variable, Valor::Numero(a - b));
-            }
-            _ => return Err(formatear_error(linea_num, "Tipos incompatibles para -=")),
-        }
-    } else if let Some(pos) = linea.find("*=") {
-        let variable = linea[..pos].trim();
-        let valor_expr = linea[pos + 2..].trim();
-        
-        let valor_actual = entorno.obtener(variable).cloned()
-            .ok_or_else(|| formatear_error(linea_num, "Variable no encontrada"))?;
-        let valor_nuevo = evaluar_expresion_valor(valor_expr, entorno)?;
-        
-        match (valor_actual, valor_nuevo) {
-            (Valor::Entero(a), Valor::Entero(b)) => {
-                entorno.establecer(variable, Valor::Entero(a * b));
-            }
-            (Valor::Numero(a), Valor::Numero(b)) => {
-                entorno.establecer(variable, Valor::Numero(a * b));
-            }
-            _ => return Err(formatear_error(linea_num, "Tipos incompatibles para *=")),
-        }
-    } else if let Some(pos) = linea.find("/=") {
-        let variable = linea[..pos].trim();
-        let valor_expr = linea[pos + 2..].trim();
-        
-        let valor_actual = entorno.obtener(variable).cloned()
-            .ok_or_else(|| formatear_error(linea_num, "Variable no encontrada"))?;
-        let valor_nuevo = evaluar_expresion_valor(valor_expr, entorno)?;
-        
-        match (valor_actual, valor_nuevo) {
-            (Valor::Entero(a), Valor::Entero(b)) => {
-                if b == 0 { return Err(formatear_error(linea_num, "División por cero")); }
-                entorno.establecer(variable, Valor::Entero(a / b));
-            }
-            (Valor::Numero(a), Valor::Numero(b)) => {
-                if b == 0.0 { return Err(formatear_error(linea_num, "División por cero")); }
-                entorno.establecer(variable, Valor::Numero(a / b));
-            }
-            _ => return Err(formatear_error(linea_num, "Tipos incompatibles para /=")),
-        }
-    } else if let Some(pos) = linea.find("%=") {
-        let variable = linea[..pos].trim();
-        let valor_expr = linea[pos + 2..].trim();
-        
-        let valor_actual = entorno.obtener(variable).cloned()
-            .ok_or_else(|| formatear_error(linea_num, "Variable no encontrada"))?;
-        let valor_nuevo = evaluar_expresion_valor(valor_expr, entorno)?;
-        
-        match (valor_actual, valor_nuevo) {
-            (Valor::Entero(a), Valor::Entero(b)) => {
-                if b == 0 { return Err(formatear_error(linea_num, "División por cero en módulo")); }
-                entorno.establecer(variable, Valor::Entero(a % b));
-            }
-            (Valor::Numero(a), Valor::Numero(b)) => {
-                if b == 0.0 { return Err(formatear_error(linea_num, "División por cero en módulo")); }
-                entorno.establecer(variable, Valor::Numero(a % b));
+    Ok(true)
+}
+
+fn procesar_bucle_para(inicio: &Sentencia, condicion: &Expresion, paso: &Sentencia, cuerpo: &[Sentencia], etiqueta: Option<&str>, entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    // La variable declarada en `inicio` solo existe dentro del bucle
+    en_bloque(entorno, |entorno| {
+        ejecutar_sentencia(inicio, entorno)?;
+        while evaluar_condicion(condicion, entorno)? {
+            match vuelta(ejecutar_bloque(cuerpo, entorno)?, etiqueta) {
+                Vuelta::Siguiente => {}
+                Vuelta::Salir => break,
+                Vuelta::Propagar(flujo) => return Ok(flujo),
             }
-            _ => return Err(formatear_error(linea_num, "Tipos incompatibles para %=")),
+            ejecutar_sentencia(paso, entorno)?;
         }
-    }
-    Ok(())
+        Ok(Flujo::Normal)
+    })
 }
 
-fn manejar_impresion<F>(linea: &str, comando: &str, linea_num: usize, entorno: &mut Entorno, func: F) -> Result<(), String>
-where
-    F: Fn(&str),
-{
-    let inicio = linea.find('(').ok_or_else(|| formatear_error(linea_num, "Función de impresión inválida"))?;
-    let fin = linea.rfind(')').ok_or_else(|| formatear_error(linea_num, "Función de impresión inválida"))?;
-    let contenido = &linea[inicio + 1..fin];
-    
-    if contenido.trim().is_empty() {
-        func("");
-        return Ok(());
+fn procesar_bucle_mientras(condicion: &Expresion, cuerpo: &[Sentencia], etiqueta: Option<&str>, entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    while evaluar_condicion(condicion, entorno)? {
+        match vuelta(ejecutar_bloque(cuerpo, entorno)?, etiqueta) {
+            Vuelta::Siguiente => {}
+            Vuelta::Salir => break,
+            Vuelta::Propagar(flujo) => return Ok(flujo),
+        }
     }
-    
-    let texto = evaluar_cadena_para_impresion(contenido, entorno, linea_num)?;
-    func(&texto);
-    Ok(())
+    Ok(Flujo::Normal)
 }
 
-fn evaluar_cadena_para_impresion(expr: &str, entorno: &mut Entorno, linea_num: usize) -> Result<String, String> {
-    let texto = expr.trim();
-    
-    // Si es una cadena literal
-    if texto.starts_with('"') && texto.ends_with('"') {
-        return Ok(texto.trim_matches('"').to_string());
-    }
-    
-    // Si contiene concatenación con +
-    if texto.contains(" + ") {
-        return evaluar_concatenacion(texto, entorno, linea_num);
-    }
-    
-    // Si es una variable simple
-    if let Some(valor) = entorno.obtener(texto) {
-        return Ok(valor.a_cadena());
-    }
-    
-    // Si es una expresión con paréntesis y método
-    if texto.contains('(') && texto.contains(')') && texto.contains(".cadena()") {
-        let partes: Vec<&str> = texto.split(".cadena()").collect();
-        if partes.len() == 2 && partes[1].is_empty() {
-            let expr_base = partes[0];
-            if expr_base.starts_with('(') && expr_base.ends_with(')') {
-                let expr_interna = &expr_base[1..expr_base.len()-1];
-                match evaluar_expresion_valor(expr_interna, entorno) {
-                    Ok(valor) => return Ok(valor.a_cadena()),
-                    Err(_) => {}
-                }
-            }
+fn procesar_bucle_hacer(cuerpo: &[Sentencia], condicion: &Expresion, etiqueta: Option<&str>, entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    loop {
+        match vuelta(ejecutar_bloque(cuerpo, entorno)?, etiqueta) {
+            Vuelta::Siguiente => {}
+            Vuelta::Salir => break,
+            Vuelta::Propagar(flujo) => return Ok(flujo),
         }
+        if !evaluar_condicion(condicion, entorno)? { break; }
     }
-    
-    // Intentar evaluar como expresión
-    match evaluar_expresion_valor(texto, entorno) {
-        Ok(valor) => Ok(valor.a_cadena()),
-        Err(_) => Ok(texto.to_string()),
-    }
+    Ok(Flujo::Normal)
 }
 
-fn evaluar_concatenacion(expr: &str, entorno: &mut Entorno, linea_num: usize) -> Result<String, String> {
-    // Necesitamos encontrar correctamente las partes, teniendo en cuenta paréntesis
-    let mut partes = Vec::new();
-    let mut parte_actual = String::new();
-    let mut nivel_parentesis = 0;
-    let mut i = 0;
-    let chars: Vec<char> = expr.chars().collect();
-    
-    while i < chars.len() {
-        let c = chars[i];
-        
-        if c == '(' {
-            nivel_parentesis += 1;
-            parte_actual.push(c);
-        } else if c == ')' {
-            nivel_parentesis -= 1;
-            parte_actual.push(c);
-        } else if c == '+' && nivel_parentesis == 0 {
-            // Solo dividir por + si estamos fuera de paréntesis
-            if i > 0 && i < chars.len() - 1 && chars[i-1] == ' ' && chars[i+1] == ' ' {
-                partes.push(parte_actual.trim().to_string());
-                parte_actual.clear();
-                i += 2; // Saltar " + "
-                continue;
-            } else {
-                parte_actual.push(c);
+fn procesar_bucle_foreach(clave: Option<&VariableBucle>, variable: &VariableBucle, iterable: &Expresion, cuerpo: &[Sentencia], etiqueta: Option<&str>, pos: Posicion, entorno: &mut Entorno) -> Result<Flujo, Diagnostico> {
+    for (valor_clave, valor) in recorrido(iterable, clave.is_some(), entorno)? {
+        // Cada vuelta tiene su propio bloque, con las variables del bucle
+        let flujo = en_bloque(entorno, |entorno| {
+            if let Some(clave) = clave {
+                declarar_variable_bucle(clave, valor_clave, iterable, pos, entorno)?;
             }
-        } else {
-            parte_actual.push(c);
+            declarar_variable_bucle(variable, valor, iterable, pos, entorno)?;
+            ejecutar_sentencias(cuerpo, entorno)
+        })?;
+        match vuelta(flujo, etiqueta) {
+            Vuelta::Siguiente => {}
+            Vuelta::Salir => break,
+            Vuelta::Propagar(flujo) => return Ok(flujo),
         }
-        i += 1;
-    }
-    
-    if !parte_actual.is_empty() {
-        partes.push(parte_actual.trim().to_string());
     }
-    
-    let mut resultado = String::new();
-    
-    for parte in partes {
-        let parte_trim = parte.trim();
-        
-        let valor_str = if parte_trim.starts_with('"') && parte_trim.ends_with('"') {
-            // Es una cadena literal
-            parte_trim.trim_matches('"').to_string()
-        } else if parte_trim.contains(".cadena()") && parte_trim.starts_with('(') {
-            // Es una expresión con paréntesis y .cadena()
-            let pos_metodo = parte_trim.find(".cadena()").unwrap();
-            if parte_trim[..pos_metodo].ends_with(')') {
-                let expr_interna = &parte_trim[1..pos_metodo-1];
-                match evaluar_expresion_valor(expr_interna, entorno) {
-                    Ok(valor) => valor.a_cadena(),
-                    Err(e) => return Err(e),
+    Ok(Flujo::Normal)
+}
+
+fn declarar_variable_bucle(variable: &VariableBucle, valor: Valor, iterable: &Expresion, pos: Posicion, entorno: &mut Entorno) -> Result<(), Diagnostico> {
+    let valor = match &variable.tipo {
+        Some(tipo) => convertir_a_tipo(tipo, &variable.nombre, valor, entorno).map_err(|e| e.con_posicion(iterable.pos))?,
+        None => valor,
+    };
+    let enlace = Enlace { tipo: variable.tipo.clone(), mutable: variable.mutable, declarada_en: Some(pos) };
+    entorno.declarar(&variable.nombre, valor, enlace)
+}
+
+/// Pares (clave o posición, valor) que recorre un `para ... en`. Con una sola
+/// variable, un jsn se recorre por sus claves, en orden alfabético. Los
+/// rangos se recorren sin construir la lista.
+fn recorrido(iterable: &Expresion, con_clave: bool, entorno: &mut Entorno) -> Result<Box<dyn Iterator<Item = (Valor, Valor)>>, Diagnostico> {
+    if let NodoExpresion::Rango { desde, hasta, inclusivo, paso } = &iterable.nodo {
+        let rango = evaluar_rango(desde, hasta, *inclusivo, paso.as_deref(), entorno).map_err(|e| e.con_posicion(iterable.pos))?;
+        return Ok(Box::new(rango.map(Valor::Entero).enumerate().map(con_posicion)));
+    }
+    let pares: Box<dyn Iterator<Item = (Valor, Valor)>> = match evaluar_expresion(iterable, entorno)? {
+        Valor::Lista(elementos) => Box::new(elementos.into_iter().enumerate().map(con_posicion)),
+        Valor::Cadena(texto) => {
+            let caracteres: Vec<Valor> = texto.chars().map(|c| Valor::Cadena(c.to_string())).collect();
+            Box::new(caracteres.into_iter().enumerate().map(con_posicion))
+        }
+        Valor::Objeto(campos) => {
+            let mut campos: Vec<(String, Valor)> = campos.into_iter().collect();
+            campos.sort_by(|a, b| a.0.cmp(&b.0));
+            Box::new(campos.into_iter().map(move |(clave, valor)| {
+                if con_clave {
+                    (Valor::Cadena(clave), valor)
+                } else {
+                    (Valor::Nulo, Valor::Cadena(clave))
                 }
-            } else {
-                return Err(formatear_error(linea_num, "Expresión con método inválida"));
-            }
-        } else if parte_trim.ends_with(".cadena()") {
-            // Es una variable con método de conversión
-            let base = parte_trim.trim_end_matches(".cadena()");
-            if let Some(valor) = entorno.obtener(base) {
-                valor.a_cadena()
-            } else {
-                return Err(formatear_error(linea_num, &format!("Variable '{}' no encontrada", base)));
-            }
-        } else if let Some(valor) = entorno.obtener(parte_trim) {
-            // Es una variable simple
-            valor.a_cadena()
-        } else {
-            // Intentar evaluar como expresión
-            match evaluar_expresion_valor(parte_trim, entorno) {
-                Ok(valor) => valor.a_cadena(),
-                Err(_) => parte_trim.to_string(),
-            }
-        };
-        
-        resultado.push_str(&valor_str);
-    }
-    
-    Ok(resultado)
+            }))
+        }
+        otro => {
+            return Err(Diagnostico::en(
+                diagnostico::TIPOS_INCOMPATIBLES,
+                iterable.pos,
+                format!("Solo se pueden recorrer listas, cadenas, jsn y rangos, se obtuvo {}", otro.nombre_tipo()),
+            ))
+        }
+    };
+    Ok(pares)
 }
 
-// Función para dividir elementos de lista respetando corchetes anidados
-fn dividir_elementos_lista(contenido: &str) -> Result<Vec<String>, String> {
-    let mut elementos = Vec::new();
-    let mut elemento_actual = String::new();
-    let mut nivel_corchetes = 0;
-    let mut en_cadena = false;
-    
-    for c in contenido.chars() {
-        match c {
-            '"' => {
-                en_cadena = !en_cadena;
-                elemento_actual.push(c);
-            }
-            '[' if !en_cadena => {
-                nivel_corchetes += 1;
-                elemento_actual.push(c);
-            }
-            ']' if !en_cadena => {
-                nivel_corchetes -= 1;
-                elemento_actual.push(c);
-            }
-            ',' if !en_cadena && nivel_corchetes == 0 => {
-                elementos.push(elemento_actual.trim().to_string());
-                elemento_actual.clear();
-            }
-            _ => {
-                elemento_actual.push(c);
-            }
+fn con_posicion((posicion, valor): (usize, Valor)) -> (Valor, Valor) {
+    (Valor::Entero(posicion as i64), valor)
+}
+
+/// Enteros de un rango, calculados a medida que se recorren.
+struct RangoEnteros {
+    /// `None` cuando el rango terminó, también si el siguiente valor
+    /// desbordaría.
+    actual: Option<i64>,
+    hasta: i64,
+    paso: i64,
+    inclusivo: bool,
+}
+
+impl Iterator for RangoEnteros {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        let actual = self.actual?;
+        let dentro = match (self.paso > 0, self.inclusivo) {
+            (true, false) => actual < self.hasta,
+            (true, true) => actual <= self.hasta,
+            (false, false) => actual > self.hasta,
+            (false, true) => actual >= self.hasta,
+        };
+        if !dentro {
+            self.actual = None;
+            return None;
         }
+        self.actual = actual.checked_add(self.paso);
+        Some(actual)
     }
-    
-    if !elemento_actual.trim().is_empty() {
-        elementos.push(elemento_actual.trim().to_string());
-    }
-    
-    Ok(elementos)
 }
 
-// Función para parsear un elemento de lista (puede ser literal o lista anidada)
-fn parsear_elemento_lista(texto: &str, entorno: &mut Entorno) -> Result<Valor, String> {
-    let texto = texto.trim();
-    
-    if texto.starts_with('[') && texto.ends_with(']') {
-        // Es una lista anidada
-        let contenido = &texto[1..texto.len() - 1];
-        let mut elementos = Vec::new();
-        if !contenido.trim().is_empty() {
-            let elementos_texto = dividir_elementos_lista(contenido)?;
-            for elemento_texto in elementos_texto {
-                elementos.push(parsear_elemento_lista(elemento_texto.trim(), entorno)?);
-            }
-        }
-        Ok(Valor::Lista(elementos))
-    } else {
-        // Es un literal o expresión
-        if let Ok(literal) = parsear_literal(texto) {
-            Ok(literal)
-        } else {
-            // Intentar evaluar como expresión (para variables)
-            evaluar_expresion_valor(texto, entorno)
+impl RangoEnteros {
+    /// Número de enteros que quedan por recorrer.
+    fn longitud(&self) -> u128 {
+        let Some(actual) = self.actual else {
+            return 0;
+        };
+        let (distancia, paso) = (self.hasta as i128 - actual as i128, self.paso as i128);
+        // Un rango exclusivo termina un entero antes en el sentido del paso
+        let distancia = if self.inclusivo { distancia } else { distancia - paso.signum() };
+        if distancia.signum() == -paso.signum() {
+            return 0;
         }
+        (distancia / paso) as u128 + 1
     }
 }
 
-fn formatear_error(linea: usize, mensaje: &str) -> String {
-    format!("Error en línea {}: {}", linea + 1, mensaje)
+/// Máximo de elementos de un rango usado como lista, como en
+/// `lista<entero> l = 0..10`. Al recorrerlo con `para` no hay límite, porque
+/// sus enteros se calculan a medida que se necesitan.
+const MAXIMO_RANGO_COMO_LISTA: u128 = 1_000_000;
+
+fn evaluar_rango(desde: &Expresion, hasta: &Expresion, inclusivo: bool, paso: Option<&Expresion>, entorno: &mut Entorno) -> Result<RangoEnteros, Diagnostico> {
+    let desde = entero_de_rango(desde, entorno)?;
+    let hasta = entero_de_rango(hasta, entorno)?;
+    let paso = match paso {
+        Some(expresion) => match entero_de_rango(expresion, entorno)? {
+            0 => {
+                return Err(Diagnostico::en(
+                    diagnostico::ERROR_DE_EJECUCION,
+                    expresion.pos,
+                    "El paso de un rango no puede ser cero",
+                ))
+            }
+            paso => paso,
+        },
+        None => 1,
+    };
+    Ok(RangoEnteros { actual: Some(desde), hasta, paso, inclusivo })
 }
 
+fn entero_de_rango(expresion: &Expresion, entorno: &mut Entorno) -> Result<i64, Diagnostico> {
+    match evaluar_expresion(expresion, entorno)? {
+        Valor::Entero(entero) => Ok(entero),
+        otro => Err(Diagnostico::en(
+            diagnostico::TIPOS_INCOMPATIBLES,
+            expresion.pos,
+            format!("Los límites y el paso de un rango deben ser enteros, se obtuvo {}", otro.nombre_tipo()),
+        )),
+    }
+}
 
+fn llamar_funcion(nombre: &str, argumentos: &[Expresion], entorno: &mut Entorno) -> Result<Valor, Diagnostico> {
+    let mut args = Vec::with_capacity(argumentos.len());
+    for argumento in argumentos {
+        args.push(evaluar_expresion(argumento, entorno)?);
+    }
 
-fn valor_desde_expresion(expresion: &str, linea_num: usize, entorno: &mut Entorno) -> Result<String, String> {
-    let texto = expresion.trim();
-    if texto.starts_with('"') && texto.ends_with('"') {
-        return Ok(texto.trim_matches('"').to_string());
+    if let Some(def_funcion) = entorno.obtener_funcion(nombre).cloned() {
+        return ejecutar_funcion_usuario(&def_funcion, args, entorno);
     }
+    if let Some(resultado) = llamar_funcion_integrada(nombre, &args) {
+        return resultado;
+    }
+    Err(Diagnostico::nuevo(
+        diagnostico::FUNCION_NO_ENCONTRADA,
+        format!("Función '{}' no reconocida", nombre),
+    ))
+}
 
-    if let Some(punto) = texto.find('.') {
-        let base = texto[..punto].trim();
-        let resto = texto[punto + 1..].trim();
-        if resto.ends_with(')') {
-            if let Some(paren) = resto.find('(') {
-                let metodo = resto[..paren].trim();
-                let args_str = &resto[paren + 1..resto.len() - 1];
-                let mut args = Vec::new();
-                if !args_str.trim().is_empty() {
-                    for arg in args_str.split(',') {
-                        args.push(obtener_valor(arg.trim(), entorno)?);
+fn llamar_funcion_integrada(nombre: &str, args: &[Valor]) -> Option<Result<Valor, Diagnostico>> {
+    let resultado = match nombre {
+        "sumar" => match args {
+            [Valor::Entero(a), Valor::Entero(b)] => a
+                .checked_add(*b)
+                .map(Valor::Entero)
+                .ok_or_else(|| desbordamiento(format!("sumar({}, {})", a, b))),
+            [Valor::Numero(a), Valor::Numero(b)] => Ok(Valor::Numero(a + b)),
+            _ => Err(Diagnostico::nuevo(diagnostico::TIPOS_INCOMPATIBLES, "Tipos incompatibles para suma")),
+        },
+        "saludar" => {
+            let nombre = args.first().map(|v| v.a_cadena()).unwrap_or_else(|| "Mundo".to_string());
+            let saludo = args.get(1).map(|v| v.a_cadena()).unwrap_or_else(|| "Hola".to_string());
+            Ok(Valor::Cadena(format!("{}, {}!", saludo, nombre)))
+        }
+        "calcular_promedio" => match args.first() {
+            Some(Valor::Lista(elementos)) => {
+                let mut suma = 0.0;
+                let mut count = 0;
+                for elem in elementos {
+                    match elem {
+                        Valor::Numero(n) => {
+                            suma += n;
+                            count += 1;
+                        }
+                        Valor::Entero(i) => {
+                            suma += *i as f64;
+                            count += 1;
+                        }
+                        _ => {}
                     }
                 }
-                if let Some(Valor::Instancia(t, campos)) = entorno.obtener(base).cloned() {
-                    let mut mapa = campos;
-                    if let Some(def) = entorno.obtener_objeto(&t) {
-                        let res = ejecutar_metodo(def, &mut mapa, metodo, args);
-                        let mut mutable = unsafe { &mut *(entorno as *const _ as *mut Entorno) };
-                        mutable.establecer(base, Valor::Instancia(t.clone(), mapa));
-                        if let Some(v) = res { return Ok(v.a_cadena()); } else { return Ok(String::new()); }
-                    } else {
-                        return Err(formatear_error(linea_num, "Objeto no definido"));
-                    }
-                } else if entorno.obtener_objeto(base).is_some() {
-                    let mut dummy = std::collections::HashMap::new();
-                    let def = entorno.obtener_objeto(base).unwrap();
-                    if let Some(v) = ejecutar_metodo(def, &mut dummy, metodo, args) { return Ok(v.a_cadena()); } else { return Ok(String::new()); }
+                if count > 0 {
+                    Ok(Valor::Numero(suma / count as f64))
                 } else {
-                    let es_var = entorno.obtener(base).is_some();
-                    let mut val = obtener_valor(base, entorno)?;
-                    if let Some(ret) = aplicar_metodo_valor(&mut val, metodo, args)? {
-                        if es_var {
-                            let mut m = unsafe { &mut *(entorno as *const _ as *mut Entorno) };
-                            m.establecer(base, val);
-                        }
-                        return Ok(ret.a_cadena());
-                    } else if es_var {
-                        let mut m = unsafe { &mut *(entorno as *const _ as *mut Entorno) };
-                        m.establecer(base, val);
-                        return Ok(String::new());
-                    }
+                    Ok(Valor::Numero(0.0))
                 }
             }
-        }
-    }
+            _ => Err(Diagnostico::nuevo(diagnostico::TIPOS_INCOMPATIBLES, "calcular_promedio espera una lista")),
+        },
+        "suma_envolvente" | "resta_envolvente" | "producto_envolvente" | "suma_saturada" | "resta_saturada"
+        | "producto_saturado" => match args {
+            [Valor::Entero(a), Valor::Entero(b)] => Ok(Valor::Entero(aritmetica_explicita(nombre, *a, *b))),
+            _ => Err(Diagnostico::nuevo(
+                diagnostico::TIPOS_INCOMPATIBLES,
+                format!("{} espera dos enteros", nombre),
+            )),
+        },
+        _ => return None,
+    };
+    Some(resultado)
+}
 
-    if let Some(base) = texto.strip_suffix(".cadena()") {
-        if let Some(valor) = entorno.obtener(base.trim()) {
-            return Ok(valor.a_cadena());
-        } else {
-            return Err(formatear_error(linea_num, "Variable no encontrada"));
-        }
+/// Operaciones entre enteros que no fallan al desbordarse: las envolventes
+/// dan la vuelta y las saturadas se quedan en el límite.
+fn aritmetica_explicita(nombre: &str, a: i64, b: i64) -> i64 {
+    match nombre {
+        "suma_envolvente" => a.wrapping_add(b),
+        "resta_envolvente" => a.wrapping_sub(b),
+        "producto_envolvente" => a.wrapping_mul(b),
+        "suma_saturada" => a.saturating_add(b),
+        "resta_saturada" => a.saturating_sub(b),
+        _ => a.saturating_mul(b),
     }
+}
 
-    if let Some(valor) = entorno.obtener(texto) {
-        Ok(valor.a_cadena())
-    } else {
-        Err(formatear_error(linea_num, "Variable no encontrada"))
-    }
-}
-
-fn obtener_valor(texto: &str, entorno: &Entorno) -> Result<Valor, String> {
-    if texto.starts_with('"') && texto.ends_with('"') {
-        return Ok(Valor::Cadena(texto.trim_matches('"').to_string()));
-    }
-    if texto == "verdadero" { return Ok(Valor::Bool(true)); }
-    if texto == "falso" { return Ok(Valor::Bool(false)); }
-    if let Ok(i) = texto.parse::<i64>() { return Ok(Valor::Entero(i)); }
-    if let Ok(n) = texto.parse::<f64>() { return Ok(Valor::Numero(n)); }
-    if let Some(v) = entorno.obtener(texto) { return Ok(v.clone()); }
-    Err("Valor no encontrado".to_string())
-}
-
-fn evaluar_comparacion(condicion: &str, entorno: &mut Entorno) -> Result<bool, String> {
-    // Buscar operadores de comparación
-    let ops = ["!=", "==", "<=", ">=", "<", ">"];
-    for op in &ops {
-        if let Some(pos) = condicion.find(op) {
-            let izq_expr = condicion[..pos].trim();
-            let der_expr = condicion[pos + op.len()..].trim();
-            
-            // Evaluar las expresiones del lado izquierdo y derecho
-            let izq = evaluar_expresion_valor(izq_expr, entorno)?;
-            let der = evaluar_expresion_valor(der_expr, entorno)?;
-            
-            return match (izq, der, *op) {
-                // Comparaciones entre enteros
-                (Valor::Entero(a), Valor::Entero(b), "<") => Ok(a < b),
-                (Valor::Entero(a), Valor::Entero(b), "<=") => Ok(a <= b),
-                (Valor::Entero(a), Valor::Entero(b), ">") => Ok(a > b),
-                (Valor::Entero(a), Valor::Entero(b), ">=") => Ok(a >= b),
-                (Valor::Entero(a), Valor::Entero(b), "==") => Ok(a == b),
-                (Valor::Entero(a), Valor::Entero(b), "!=") => Ok(a != b),
-                
-                // Comparaciones entre números
-                (Valor::Numero(a), Valor::Numero(b), "<") => Ok(a < b),
-                (Valor::Numero(a), Valor::Numero(b), "<=") => Ok(a <= b),
-                (Valor::Numero(a), Valor::Numero(b), ">") => Ok(a > b),
-                (Valor::Numero(a), Valor::Numero(b), ">=") => Ok(a >= b),
-                (Valor::Numero(a), Valor::Numero(b), "==") => Ok((a - b).abs() < f64::EPSILON),
-                (Valor::Numero(a), Valor::Numero(b), "!=") => Ok((a - b).abs() >= f64::EPSILON),
-                
-                // Comparaciones mixtas entero-número
-                (Valor::Entero(a), Valor::Numero(b), "<") => Ok((a as f64) < b),
-                (Valor::Entero(a), Valor::Numero(b), "<=") => Ok((a as f64) <= b),
-                (Valor::Entero(a), Valor::Numero(b), ">") => Ok((a as f64) > b),
-                (Valor::Entero(a), Valor::Numero(b), ">=") => Ok((a as f64) >= b),
-                (Valor::Entero(a), Valor::Numero(b), "==") => Ok(((a as f64) - b).abs() < f64::EPSILON),
-                (Valor::Entero(a), Valor::Numero(b), "!=") => Ok(((a as f64) - b).abs() >= f64::EPSILON),
-                
-                (Valor::Numero(a), Valor::Entero(b), "<") => Ok(a < (b as f64)),
-                (Valor::Numero(a), Valor::Entero(b), "<=") => Ok(a <= (b as f64)),
-                (Valor::Numero(a), Valor::Entero(b), ">") => Ok(a > (b as f64)),
-                (Valor::Numero(a), Valor::Entero(b), ">=") => Ok(a >= (b as f64)),
-                (Valor::Numero(a), Valor::Entero(b), "==") => Ok((a - (b as f64)).abs() < f64::EPSILON),
-                (Valor::Numero(a), Valor::Entero(b), "!=") => Ok((a - (b as f64)).abs() >= f64::EPSILON),
-                
-                // Comparaciones entre cadenas
-                (Valor::Cadena(a), Valor::Cadena(b), "==") => Ok(a == b),
-                (Valor::Cadena(a), Valor::Cadena(b), "!=") => Ok(a != b),
-                
-                // Comparaciones entre booleanos
-                (Valor::Bool(a), Valor::Bool(b), "==") => Ok(a == b),
-                (Valor::Bool(a), Valor::Bool(b), "!=") => Ok(a != b),
-                
-                _ => Err("Tipos incompatibles para comparación".to_string()),
-            };
-        }
+fn ejecutar_funcion_usuario(def_funcion: &DefFuncion, args: Vec<Valor>, entorno: &mut Entorno) -> Result<Valor, Diagnostico> {
+    // Verificar que el número de argumentos coincida
+    if args.len() != def_funcion.parametros.len() {
+        return Err(Diagnostico::nuevo(
+            diagnostico::NUMERO_DE_ARGUMENTOS,
+            format!(
+                "Función '{}' espera {} argumentos, pero se proporcionaron {}",
+                def_funcion.nombre,
+                def_funcion.parametros.len(),
+                args.len()
+            ),
+        ));
     }
     
-    // Si no hay operadores de comparación, intentar obtener un valor booleano directamente
-    if let Some(Valor::Bool(b)) = entorno.obtener(condicion.trim()) {
-        return Ok(*b);
+    // Crear un nuevo entorno para la función con las definiciones visibles
+    // desde la llamada
+    let mut entorno_funcion = entorno.para_funcion();
+    
+    // Asignar valores a los parámetros
+    for (parametro, valor_arg) in def_funcion.parametros.iter().zip(args) {
+        let valor_arg = ajustar_a_tipo_declarado(&parametro.tipo, valor_arg).map_err(|valor| {
+            Diagnostico::nuevo(
+                codigo_incompatible(&valor),
+                format!(
+                    "El parámetro '{}' de '{}' es de tipo {}, pero se pasó un valor de tipo {}",
+                    parametro.nombre,
+                    def_funcion.nombre,
+                    parametro.tipo,
+                    valor.nombre_tipo()
+                ),
+            )
+        })?;
+        let enlace = Enlace {
+            tipo: Some(parametro.tipo.clone()),
+            mutable: parametro.mutable,
+            declarada_en: Some(parametro.pos),
+        };
+        entorno_funcion.declarar(&parametro.nombre, valor_arg, enlace)?;
     }
     
-    Err("Condición inválida".to_string())
+    // Ejecutar el cuerpo de la función; si termina sin `retornar` el valor
+    // es vacio y un error de tipo se sitúa en la llamada
+    let (valor, pos_retorno) = match ejecutar_sentencias(&def_funcion.cuerpo, &mut entorno_funcion)? {
+        Flujo::Retorno(valor, pos) => (valor, Some(pos)),
+        _ => (Valor::Vacio, None),
+    };
+    ajustar_a_tipo_declarado(&def_funcion.tipo_retorno, valor).map_err(|valor| {
+        let codigo = codigo_incompatible(&valor);
+        let tipo = &def_funcion.tipo_retorno;
+        let mensaje = match (tipo.resuelto(), valor) {
+            (Tipo::Vacio, valor) => format!(
+                "La función '{}' es de tipo {}, pero retornó un valor de tipo {}",
+                def_funcion.nombre,
+                tipo,
+                valor.nombre_tipo()
+            ),
+            (_, Valor::Vacio) => format!(
+                "La función '{}' debe retornar {}, pero terminó sin retornar un valor",
+                def_funcion.nombre, tipo
+            ),
+            (_, valor) => format!(
+                "La función '{}' debe retornar {}, se obtuvo {}",
+                def_funcion.nombre,
+                tipo,
+                valor.nombre_tipo()
+            ),
+        };
+        let error = Diagnostico::nuevo(codigo, mensaje);
+        match pos_retorno {
+            Some(pos) => error.con_posicion(pos),
+            None => error,
+        }
+    })
 }
 
-fn evaluar_bool(expr: &str, entorno: &mut Entorno) -> Result<bool, String> {
-    // Primero verificar operadores lógicos && y ||
-    if let Some(pos) = expr.find("&&") {
-        let izquierda = &expr[..pos].trim();
-        let derecha = &expr[pos + 2..].trim();
-        return Ok(evaluar_bool(izquierda, entorno)? && evaluar_bool(derecha, entorno)?);
-    }
-    if let Some(pos) = expr.find("||") {
-        let izquierda = &expr[..pos].trim();
-        let derecha = &expr[pos + 2..].trim();
-        return Ok(evaluar_bool(izquierda, entorno)? || evaluar_bool(derecha, entorno)?);
-    }
-    if let Some(pos) = expr.find(" y ") {
-        let izquierda = &expr[..pos];
-        let derecha = &expr[pos + 3..];
-        return Ok(evaluar_bool(izquierda, entorno)? && evaluar_bool(derecha, entorno)?);
-    }
-    if let Some(pos) = expr.find(" o ") {
-        let izquierda = &expr[..pos];
-        let derecha = &expr[pos + 3..];
-        return Ok(evaluar_bool(izquierda, entorno)? || evaluar_bool(derecha, entorno)?);
+/// Un nulo donde no se admite tiene su propio código de error.
+fn codigo_incompatible(valor: &Valor) -> &'static str {
+    match valor {
+        Valor::Nulo => diagnostico::VALOR_NULO,
+        _ => diagnostico::TIPOS_INCOMPATIBLES,
     }
-    evaluar_comparacion(expr, entorno)
 }
 
-fn evaluar_expresion_valor(expr: &str, entorno: &mut Entorno) -> Result<Valor, String> {
-    let texto = expr.trim();
-    
-    // Operador ternario
-    if texto.contains('?') && texto.contains(':') {
-        let q = texto.find('?').ok_or("Expresión ternaria inválida")?;
-        let rest = &texto[q + 1..];
-        let c_pos = rest.find(':').ok_or("Expresión ternaria inválida")? + q + 1;
-        let condicion = texto[..q].trim();
-        let verdadero = &texto[q + 1..c_pos].trim();
-        let falso = &texto[c_pos + 1..].trim();
-        if evaluar_bool(condicion, entorno)? {
-            return evaluar_expresion_valor(verdadero, entorno);
-        } else {
-            return evaluar_expresion_valor(falso, entorno);
-        }
-    }
-    
-    // Llamadas a métodos
-    if texto.contains('.') && texto.contains('(') && texto.ends_with(')') {
-        // Buscar el punto que está fuera de cadenas
-        let mut punto_valido = None;
-        let mut en_cadena = false;
-        let chars: Vec<char> = texto.chars().collect();
-        
-        for (i, &c) in chars.iter().enumerate() {
-            if c == '"' {
-                en_cadena = !en_cadena;
-            } else if c == '.' && !en_cadena {
-                punto_valido = Some(i);
-                break;
+/// Comprueba un valor que cruza el límite de una llamada, como argumento o
+/// como valor retornado, contra el tipo declarado. A diferencia de una
+/// declaración no hay conversiones: solo se amplía un entero a número,
+/// también dentro de una `lista<número>`. Si el valor no es del tipo se
+/// devuelve como error.
+fn ajustar_a_tipo_declarado(tipo: &Tipo, valor: Valor) -> Result<Valor, Valor> {
+    match (tipo.resuelto(), valor) {
+        (Tipo::Opcional(_), Valor::Nulo) => Ok(Valor::Nulo),
+        (Tipo::Opcional(tipo), valor) => ajustar_a_tipo_declarado(tipo, valor),
+        (Tipo::Numero, Valor::Entero(i)) => Ok(Valor::Numero(i as f64)),
+        (Tipo::Lista(Some(tipo_elemento)), Valor::Lista(elementos)) => {
+            let mut ajustados = Vec::with_capacity(elementos.len());
+            let mut valido = true;
+            for elemento in elementos {
+                match ajustar_a_tipo_declarado(tipo_elemento, elemento) {
+                    Ok(elemento) => ajustados.push(elemento),
+                    Err(elemento) => {
+                        valido = false;
+                        ajustados.push(elemento);
+                    }
+                }
             }
+            if valido { Ok(Valor::Lista(ajustados)) } else { Err(Valor::Lista(ajustados)) }
         }
-        
-        if let Some(punto) = punto_valido {
-            let base = texto[..punto].trim();
-            let resto = texto[punto + 1..].trim();
-            if let Some(paren) = resto.find('(') {
-                let metodo = resto[..paren].trim();
-                let args_str = &resto[paren + 1..resto.len() - 1];
-                let mut args = Vec::new();
-                if !args_str.trim().is_empty() {
-                    for arg in args_str.split(',') {
-                        args.push(evaluar_expresion_valor(arg.trim(), entorno)?);
+        (tipo, valor) if valor.es_de_tipo(tipo) => Ok(valor),
+        (_, valor) => Err(valor),
+    }
+}
+
+fn evaluar_expresion(expresion: &Expresion, entorno: &mut Entorno) -> Result<Valor, Diagnostico> {
+    // El error se sitúa en el nodo más interno que lo produjo
+    evaluar_nodo(expresion, entorno).map_err(|e| e.con_posicion(expresion.pos))
+}
+
+fn evaluar_nodo(expresion: &Expresion, entorno: &mut Entorno) -> Result<Valor, Diagnostico> {
+    match &expresion.nodo {
+        NodoExpresion::Entero(i) => Ok(Valor::Entero(*i)),
+        NodoExpresion::Numero(n) => Ok(Valor::Numero(*n)),
+        NodoExpresion::Cadena(c) => Ok(Valor::Cadena(c.clone())),
+        NodoExpresion::Interpolacion(partes) => {
+            let mut texto = String::new();
+            for parte in partes {
+                match parte {
+                    ParteCadena::Texto(fragmento) => texto.push_str(fragmento),
+                    ParteCadena::Expresion(expresion) => {
+                        texto.push_str(&evaluar_expresion(expresion, entorno)?.a_cadena())
                     }
                 }
-                
-                // Intentar obtener valor de la variable base
-                if let Ok(mut val) = obtener_valor(base, entorno) {
-                    if let Some(resultado) = aplicar_metodo_valor(&mut val, metodo, args)? {
-                        entorno.establecer(base, val);
-                        return Ok(resultado);
-                    }
+            }
+            Ok(Valor::Cadena(texto))
+        }
+        NodoExpresion::Bool(b) => Ok(Valor::Bool(*b)),
+        NodoExpresion::Nulo => Ok(Valor::Nulo),
+        NodoExpresion::Variable(nombre) => entorno
+            .obtener(nombre)
+            .cloned()
+            .ok_or_else(|| no_encontrada(nombre)),
+        NodoExpresion::Lista(elementos) => {
+            let mut valores = Vec::with_capacity(elementos.len());
+            for elemento in elementos {
+                valores.push(evaluar_expresion(elemento, entorno)?);
+            }
+            Ok(Valor::Lista(valores))
+        }
+        NodoExpresion::Jsn(campos) => {
+            let mut mapa = HashMap::new();
+            for (clave, valor) in campos {
+                mapa.insert(clave.clone(), evaluar_expresion(valor, entorno)?);
+            }
+            Ok(Valor::Objeto(mapa))
+        }
+        NodoExpresion::Unaria { operador, operando } => {
+            let valor = evaluar_expresion(operando, entorno)?;
+            match (operador, valor) {
+                (OperadorUnario::Negacion, Valor::Bool(b)) => Ok(Valor::Bool(!b)),
+                (OperadorUnario::Negacion, _) => Err(Diagnostico::nuevo(diagnostico::TIPOS_INCOMPATIBLES, "Operando de negación no es booleano")),
+                (OperadorUnario::Negativo, Valor::Entero(i)) => i
+                    .checked_neg()
+                    .map(Valor::Entero)
+                    .ok_or_else(|| desbordamiento(format!("-({})", i))),
+                (OperadorUnario::Negativo, Valor::Numero(n)) => Ok(Valor::Numero(-n)),
+                (OperadorUnario::Negativo, otro) => {
+                    Err(Diagnostico::nuevo(
+                        diagnostico::TIPOS_INCOMPATIBLES,
+                        format!("No se puede negar un valor de tipo {}", otro.nombre_tipo()),
+                    ))
                 }
             }
         }
-    }
-    for op in &[" && ", " y ", " || ", " o "] {
-        if let Some(pos) = texto.find(op) {
-            let izq = texto[..pos].trim();
-            let der = texto[pos + op.len()..].trim();
-            
-            let val_izq = evaluar_expresion_valor(izq, entorno)?;
-            let val_der = evaluar_expresion_valor(der, entorno)?;
-            
-            let bool_izq = match val_izq {
-                Valor::Bool(b) => b,
-                _ => return Err("Operando izquierdo no es booleano".to_string()),
-            };
-            
-            let bool_der = match val_der {
-                Valor::Bool(b) => b,
-                _ => return Err("Operando derecho no es booleano".to_string()),
-            };
-            
-            let resultado = match *op {
-                " && " | " y " => bool_izq && bool_der,
-                " || " | " o " => bool_izq || bool_der,
-                _ => unreachable!(),
-            };
-            
-            return Ok(Valor::Bool(resultado));
-        }
-    }
-    
-    // Operador de negación
-    if texto.starts_with('!') {
-        let operando = &texto[1..];
-        let val = evaluar_expresion_valor(operando, entorno)?;
-        match val {
-            Valor::Bool(b) => return Ok(Valor::Bool(!b)),
-            _ => return Err("Operando de negación no es booleano".to_string()),
-        }
-    }
-    for op in &["==", "!=", "<=", ">=", "<", ">"] {
-        if let Some(pos) = encontrar_operador_principal(texto, op) {
-            let izq = texto[..pos].trim();
-            let der = texto[pos + op.len()..].trim();
-            
-            let val_izq = evaluar_expresion_valor(izq, entorno)?;
-            let val_der = evaluar_expresion_valor(der, entorno)?;
-            
-            let resultado = match (val_izq, val_der, *op) {
-                (Valor::Entero(a), Valor::Entero(b), "==") => a == b,
-                (Valor::Entero(a), Valor::Entero(b), "!=") => a != b,
-                (Valor::Entero(a), Valor::Entero(b), "<") => a < b,
-                (Valor::Entero(a), Valor::Entero(b), "<=") => a <= b,
-                (Valor::Entero(a), Valor::Entero(b), ">") => a > b,
-                (Valor::Entero(a), Valor::Entero(b), ">=") => a >= b,
-                
-                (Valor::Numero(a), Valor::Numero(b), "==") => (a - b).abs() < f64::EPSILON,
-                (Valor::Numero(a), Valor::Numero(b), "!=") => (a - b).abs() >= f64::EPSILON,
-                (Valor::Numero(a), Valor::Numero(b), "<") => a < b,
-                (Valor::Numero(a), Valor::Numero(b), "<=") => a <= b,
-                (Valor::Numero(a), Valor::Numero(b), ">") => a > b,
-                (Valor::Numero(a), Valor::Numero(b), ">=") => a >= b,
-                
-                (Valor::Entero(a), Valor::Numero(b), "==") => ((a as f64) - b).abs() < f64::EPSILON,
-                (Valor::Entero(a), Valor::Numero(b), "!=") => ((a as f64) - b).abs() >= f64::EPSILON,
-                (Valor::Entero(a), Valor::Numero(b), "<") => (a as f64) < b,
-                (Valor::Entero(a), Valor::Numero(b), "<=") => (a as f64) <= b,
-                (Valor::Entero(a), Valor::Numero(b), ">") => (a as f64) > b,
-                (Valor::Entero(a), Valor::Numero(b), ">=") => (a as f64) >= b,
-                
-                (Valor::Numero(a), Valor::Entero(b), "==") => (a - (b as f64)).abs() < f64::EPSILON,
-                (Valor::Numero(a), Valor::Entero(b), "!=") => (a - (b as f64)).abs() >= f64::EPSILON,
-                (Valor::Numero(a), Valor::Entero(b), "<") => a < (b as f64),
-                (Valor::Numero(a), Valor::Entero(b), "<=") => a <= (b as f64),
-                (Valor::Numero(a), Valor::Entero(b), ">") => a > (b as f64),
-                (Valor::Numero(a), Valor::Entero(b), ">=") => a >= (b as f64),
-                
-                (Valor::Cadena(a), Valor::Cadena(b), "==") => a == b,
-                (Valor::Cadena(a), Valor::Cadena(b), "!=") => a != b,
-                
-                (Valor::Bool(a), Valor::Bool(b), "==") => a == b,
-                (Valor::Bool(a), Valor::Bool(b), "!=") => a != b,
-                
-                _ => return Err("Tipos incompatibles para comparación".to_string()),
-            };
-            
-            return Ok(Valor::Bool(resultado));
+        NodoExpresion::Binaria { operador: OperadorBinario::Y, izquierda, derecha } => {
+            if !evaluar_operando_logico(izquierda, "izquierdo", entorno)? {
+                return Ok(Valor::Bool(false));
+            }
+            Ok(Valor::Bool(evaluar_operando_logico(derecha, "derecho", entorno)?))
         }
-    }
-    
-    // Operaciones aritméticas
-    if let Ok(resultado) = evaluar_operacion_aritmetica(texto, entorno) {
-        return Ok(resultado);
-    }
-    
-    // Concatenación de cadenas
-    if texto.contains(" + ") && !texto.chars().all(|c| c.is_ascii_digit() || c.is_whitespace() || c == '+' || c == '.' || c == '-') {
-        let resultado = evaluar_concatenacion(texto, entorno, 0)?;
-        return Ok(Valor::Cadena(resultado));
-    }
-    
-    // Literales básicos
-    if texto == "verdadero" { return Ok(Valor::Bool(true)); }
-    if texto == "falso" { return Ok(Valor::Bool(false)); }
-    
-    // Parsear literal
-    if let Ok(l) = parsear_literal(texto) {
-        return Ok(l);
-    }
-    
-    // Obtener valor de variable
-    if let Ok(v) = obtener_valor(texto, entorno) {
-        return Ok(v);
-    }
-    
-    // Método de conversión o acceso
-    if let Ok(cadena) = valor_desde_expresion(texto, 0, entorno) {
-        return Ok(Valor::Cadena(cadena));
-    }
-    
-    Err("Expresión inválida".to_string())
-}
-
-fn evaluar_operacion_aritmetica(expr: &str, entorno: &mut Entorno) -> Result<Valor, String> {
-    // Buscar operadores en orden de precedencia (menor a mayor)
-    for op in &["+", "-"] {
-        if let Some(pos) = encontrar_operador_principal(expr, op) {
-            let izq = expr[..pos].trim();
-            let der = expr[pos + op.len()..].trim();
-            
-            let val_izq = evaluar_expresion_valor(izq, entorno)?;
-            let val_der = evaluar_expresion_valor(der, entorno)?;
-            
-            match (val_izq, val_der, *op) {
-                (Valor::Entero(a), Valor::Entero(b), "+") => return Ok(Valor::Entero(a + b)),
-                (Valor::Entero(a), Valor::Entero(b), "-") => return Ok(Valor::Entero(a - b)),
-                (Valor::Numero(a), Valor::Numero(b), "+") => return Ok(Valor::Numero(a + b)),
-                (Valor::Numero(a), Valor::Numero(b), "-") => return Ok(Valor::Numero(a - b)),
-                (Valor::Entero(a), Valor::Numero(b), "+") => return Ok(Valor::Numero(a as f64 + b)),
-                (Valor::Entero(a), Valor::Numero(b), "-") => return Ok(Valor::Numero(a as f64 - b)),
-                (Valor::Numero(a), Valor::Entero(b), "+") => return Ok(Valor::Numero(a + b as f64)),
-                (Valor::Numero(a), Valor::Entero(b), "-") => return Ok(Valor::Numero(a - b as f64)),
-                _ => {}
+        NodoExpresion::Binaria { operador: OperadorBinario::O, izquierda, derecha } => {
+            if evaluar_operando_logico(izquierda, "izquierdo", entorno)? {
+                return Ok(Valor::Bool(true));
+            }
+            Ok(Valor::Bool(evaluar_operando_logico(derecha, "derecho", entorno)?))
+        }
+        NodoExpresion::Binaria { operador: OperadorBinario::Coalescencia, izquierda, derecha } => {
+            match evaluar_expresion(izquierda, entorno)? {
+                Valor::Nulo => evaluar_expresion(derecha, entorno),
+                valor => Ok(valor),
             }
         }
-    }
-    
-    for op in &["*", "/", "%"] {
-        if let Some(pos) = encontrar_operador_principal(expr, op) {
-            let izq = expr[..pos].trim();
-            let der = expr[pos + op.len()..].trim();
-            
-            let val_izq = evaluar_expresion_valor(izq, entorno)?;
-            let val_der = evaluar_expresion_valor(der, entorno)?;
-            
-            match (val_izq, val_der, *op) {
-                (Valor::Entero(a), Valor::Entero(b), "*") => return Ok(Valor::Entero(a * b)),
-                (Valor::Entero(a), Valor::Entero(b), "/") => return Ok(Valor::Entero(a / b)),
-                (Valor::Entero(a), Valor::Entero(b), "%") => return Ok(Valor::Entero(a % b)),
-                (Valor::Numero(a), Valor::Numero(b), "*") => return Ok(Valor::Numero(a * b)),
-                (Valor::Numero(a), Valor::Numero(b), "/") => return Ok(Valor::Numero(a / b)),
-                (Valor::Numero(a), Valor::Numero(b), "%") => return Ok(Valor::Numero(a % b)),
-                (Valor::Entero(a), Valor::Numero(b), "*") => return Ok(Valor::Numero(a as f64 * b)),
-                (Valor::Entero(a), Valor::Numero(b), "/") => return Ok(Valor::Numero(a as f64 / b)),
-                (Valor::Numero(a), Valor::Entero(b), "*") => return Ok(Valor::Numero(a * b as f64)),
-                (Valor::Numero(a), Valor::Entero(b), "/") => return Ok(Valor::Numero(a / b as f64)),
-                _ => {}
+        NodoExpresion::Binaria { operador, izquierda, derecha } => {
+            let izq = evaluar_expresion(izquierda, entorno)?;
+            let der = evaluar_expresion(derecha, entorno)?;
+            evaluar_operacion_binaria(*operador, izq, der)
+        }
+        NodoExpresion::Ternaria { condicion, si_verdadero, si_falso } => {
+            match evaluar_expresion(condicion, entorno)? {
+                Valor::Bool(true) => evaluar_expresion(si_verdadero, entorno),
+                Valor::Bool(false) => evaluar_expresion(si_falso, entorno),
+                _ => Err(Diagnostico::en(
+                    diagnostico::TIPOS_INCOMPATIBLES,
+                    condicion.pos,
+                    "La condición del operador ternario no es booleana",
+                )),
             }
         }
+        NodoExpresion::Llamada { funcion, argumentos } => match &funcion.nodo {
+            NodoExpresion::Miembro { objeto, nombre, opcional } => {
+                llamar_metodo(objeto, nombre, *opcional, argumentos, entorno)
+            }
+            NodoExpresion::Variable(nombre) => match funcion_impresion(nombre) {
+                Some(imprimir) => {
+                    manejar_impresion(argumentos, entorno, imprimir)?;
+                    Ok(Valor::Vacio)
+                }
+                None => llamar_funcion(nombre, argumentos, entorno),
+            },
+            _ => Err(Diagnostico::nuevo(diagnostico::TIPOS_INCOMPATIBLES, format!("La expresión '{}' no se puede llamar", funcion))),
+        },
+        NodoExpresion::Miembro { objeto, nombre, opcional } => {
+            if let Some(def) = enumeracion_nombrada(objeto, entorno) {
+                return construir_variante(def, nombre, Vec::new(), &[]);
+            }
+            let valor = evaluar_expresion(objeto, entorno)?;
+            acceder_miembro(valor, nombre, *opcional)
+        }
+        NodoExpresion::Indice { objeto, indice } => {
+            let valor = evaluar_expresion(objeto, entorno)?;
+            let indice = evaluar_expresion(indice, entorno)?;
+            acceder_indice(valor, indice)
+        }
+        NodoExpresion::Nuevo { tipo, argumentos } => {
+            let mut args = Vec::with_capacity(argumentos.len());
+            for argumento in argumentos {
+                args.push(evaluar_expresion(argumento, entorno)?);
+            }
+            let def = entorno
+                .obtener_objeto(tipo)
+                .ok_or_else(|| objeto_no_definido(tipo))?;
+            Ok(instanciar_objeto(def, args))
+        }
+        NodoExpresion::Rango { desde, hasta, inclusivo, paso } => {
+            let rango = evaluar_rango(desde, hasta, *inclusivo, paso.as_deref(), entorno)?;
+            if rango.longitud() > MAXIMO_RANGO_COMO_LISTA {
+                return Err(Diagnostico::nuevo(
+                    diagnostico::RANGO_DEMASIADO_GRANDE,
+                    format!(
+                        "El rango tiene {} elementos y como lista admite como máximo {}; recórrelo con 'para' sin guardarlo en una lista",
+                        rango.longitud(),
+                        MAXIMO_RANGO_COMO_LISTA
+                    ),
+                ));
+            }
+            Ok(Valor::Lista(rango.map(Valor::Entero).collect()))
+        }
     }
-    
-    Err("No es una operación aritmética válida".to_string())
 }
 
-fn encontrar_operador_principal(expr: &str, op: &str) -> Option<usize> {
-    let mut nivel_parentesis = 0;
-    
-    for (char_pos, c) in expr.char_indices() {
-        match c {
-            '(' => nivel_parentesis += 1,
-            ')' => nivel_parentesis -= 1,
-            _ => {
-                if nivel_parentesis == 0 && expr[char_pos..].starts_with(op) {
-                    // Para operadores de comparación de dos caracteres, verificar que no sea parte de otro operador
-                    let es_operador_completo = if op.len() == 2 {
-                        // Para ==, !=, <=, >=
-                        true
-                    } else {
-                        // Para operadores de un carácter como <, >
-                        let siguiente_char = expr[char_pos..].chars().nth(1);
-                        !matches!(siguiente_char, Some('='))
-                    };
-                    
-                    // Verificar que no es parte de otro operador por la izquierda
-                    let anterior_char = if char_pos > 0 {
-                        expr[..char_pos].chars().last()
-                    } else {
-                        None
-                    };
-                    let no_es_parte_izquierda = !matches!(anterior_char, Some('=') | Some('!') | Some('<') | Some('>'));
-                    
-                    if es_operador_completo && no_es_parte_izquierda {
-                        return Some(char_pos);
-                    }
+fn evaluar_operando_logico(expresion: &Expresion, lado: &str, entorno: &mut Entorno) -> Result<bool, Diagnostico> {
+    match evaluar_expresion(expresion, entorno)? {
+        Valor::Bool(b) => Ok(b),
+        _ => Err(Diagnostico::en(diagnostico::TIPOS_INCOMPATIBLES, expresion.pos, format!("Operando {} no es booleano", lado))),
+    }
+}
+
+fn evaluar_operacion_binaria(operador: OperadorBinario, izq: Valor, der: Valor) -> Result<Valor, Diagnostico> {
+    use OperadorBinario::*;
+
+    match (operador, izq, der) {
+        // Concatenación de cadenas
+        (Suma, Valor::Cadena(a), b) => Ok(Valor::Cadena(a + &b.a_cadena())),
+        (Suma, a, Valor::Cadena(b)) => Ok(Valor::Cadena(a.a_cadena() + &b)),
+
+        // Operaciones aritméticas: entre enteros, la división por cero y el
+        // desbordamiento son errores
+        (Division | Modulo, Valor::Entero(_), Valor::Entero(0)) => Err(division_por_cero()),
+        (Suma | Resta | Multiplicacion | Division | Modulo, Valor::Entero(a), Valor::Entero(b)) => {
+            let resultado = match operador {
+                Suma => a.checked_add(b),
+                Resta => a.checked_sub(b),
+                Multiplicacion => a.checked_mul(b),
+                Division => a.checked_div(b),
+                _ => a.checked_rem(b),
+            };
+            resultado
+                .map(Valor::Entero)
+                .ok_or_else(|| desbordamiento(format!("{} {} {}", a, operador, b)))
+        }
+        (Suma | Resta | Multiplicacion | Division | Modulo, a, b) => {
+            let (a, b) = match (&a, &b) {
+                (Valor::Entero(_) | Valor::Numero(_), Valor::Entero(_) | Valor::Numero(_)) => {
+                    (a.convertir_a_numero()?, b.convertir_a_numero()?)
                 }
-            }
+                _ => {
+                    return Err(Diagnostico::nuevo(
+                        diagnostico::TIPOS_INCOMPATIBLES,
+                        format!("Tipos incompatibles para '{}': {} y {}", operador, a.nombre_tipo(), b.nombre_tipo()),
+                    ))
+                }
+            };
+            if matches!(operador, Division | Modulo) && b == 0.0 {
+                return Err(division_por_cero());
+            }
+            let resultado = match operador {
+                Suma => a + b,
+                Resta => a - b,
+                Multiplicacion => a * b,
+                Division => a / b,
+                _ => a % b,
+            };
+            Ok(Valor::Numero(resultado))
         }
+
+        // Comparaciones
+        (_, a, b) => Ok(Valor::Bool(comparar(operador, &a, &b)?)),
     }
-    
-    None
-}
-
-fn extraer_bloque(lineas: &[String], inicio: usize) -> Result<(Vec<String>, usize), String> {
-    let mut bloque = Vec::new();
-    let mut nivel = lineas[inicio].matches('{').count() as i32 - lineas[inicio].matches('}').count() as i32;
-    let mut i = inicio + 1;
-    while i < lineas.len() {
-        let linea = &lineas[i];
-        nivel += linea.matches('{').count() as i32;
-        if linea.contains('}') {
-            nivel -= linea.matches('}').count() as i32;
-            if nivel == 0 {
-                return Ok((bloque, i));
+}
+
+fn comparar(operador: OperadorBinario, izq: &Valor, der: &Valor) -> Result<bool, Diagnostico> {
+    use OperadorBinario::*;
+
+    match (izq, der) {
+        // Cualquier valor se puede comparar con nulo
+        (Valor::Nulo, _) | (_, Valor::Nulo) if matches!(operador, Igual | Distinto) => {
+            let iguales = matches!((izq, der), (Valor::Nulo, Valor::Nulo));
+            Ok(iguales == (operador == Igual))
+        }
+        (Valor::Entero(a), Valor::Entero(b)) => Ok(match operador {
+            Igual => a == b,
+            Distinto => a != b,
+            Menor => a < b,
+            MenorIgual => a <= b,
+            Mayor => a > b,
+            _ => a >= b,
+        }),
+        (Valor::Entero(_) | Valor::Numero(_), Valor::Entero(_) | Valor::Numero(_)) => {
+            let a = izq.convertir_a_numero()?;
+            let b = der.convertir_a_numero()?;
+            Ok(match operador {
+                Igual => (a - b).abs() < f64::EPSILON,
+                Distinto => (a - b).abs() >= f64::EPSILON,
+                Menor => a < b,
+                MenorIgual => a <= b,
+                Mayor => a > b,
+                _ => a >= b,
+            })
+        }
+        (Valor::Cadena(a), Valor::Cadena(b)) if matches!(operador, Igual | Distinto) => {
+            Ok((a == b) == (operador == Igual))
+        }
+        (Valor::Bool(a), Valor::Bool(b)) if matches!(operador, Igual | Distinto) => {
+            Ok((a == b) == (operador == Igual))
+        }
+        // Dos variantes son iguales si son la misma y tienen los mismos datos
+        (Valor::Enumeracion(tipo_a, variante_a, datos_a), Valor::Enumeracion(tipo_b, variante_b, datos_b))
+            if matches!(operador, Igual | Distinto) && tipo_a == tipo_b =>
+        {
+            let mut iguales = variante_a == variante_b && datos_a.len() == datos_b.len();
+            for (a, b) in datos_a.iter().zip(datos_b) {
+                if !iguales {
+                    break;
+                }
+                iguales = comparar(Igual, a, b)?;
             }
+            Ok(iguales == (operador == Igual))
         }
-        bloque.push(linea.clone());
-        i += 1;
+        _ => Err(Diagnostico::nuevo(diagnostico::TIPOS_INCOMPATIBLES, "Tipos incompatibles para comparación")),
     }
-    Err("Bloque sin cerrar".to_string())
 }
 
-fn procesar_condicional(lineas: &[String], inicio: usize, entorno: &mut Entorno, base: usize) -> Result<usize, String> {
-    let mut i = inicio;
-    let mut ejecutado = false;
-    loop {
-        let linea = lineas[i].trim();
-        let palabra = if linea.starts_with("sino si") {
-            "sino si"
-        } else if linea.starts_with("sino") {
-            "sino"
-        } else {
-            "si"
-        };
-
-        let condicion = if palabra == "sino" {
-            "verdadero"
-        } else {
-            let ini = linea.find('(').ok_or_else(|| formatear_error(base + i, "Condicional inválido"))?;
-            let fin = linea.rfind(')').ok_or_else(|| formatear_error(base + i, "Condicional inválido"))?;
-            &linea[ini + 1..fin]
-        };
-
-        let (bloque, fin_bloque) = extraer_bloque(lineas, i)?;
-        if !ejecutado && evaluar_bool(condicion, entorno)? {
-            procesar_lineas(&bloque, entorno, base + i + 1)?;
-            ejecutado = true;
-        }
-        i = fin_bloque + 1;
-        if i >= lineas.len() { break; }
-        let siguiente = lineas[i].trim();
-        if siguiente.starts_with("sino si") || siguiente.starts_with("sino") {
-            continue;
-        }
-        break;
-    }
-    Ok(i)
-}
-
-fn procesar_bucle_para(linea: &str, bloque: &[String], entorno: &mut Entorno, linea_num: usize) -> Result<(), String> {
-    let texto = linea.trim();
-    let inicio_paren = texto.find('(').ok_or_else(|| formatear_error(linea_num, "Bucle para inválido"))?;
-    let fin_paren = texto.rfind(')').ok_or_else(|| formatear_error(linea_num, "Bucle para inválido"))?;
-    let contenido = &texto[inicio_paren + 1..fin_paren];
-    let partes: Vec<&str> = contenido.split(';').collect();
-    if partes.len() != 3 {
-        return Err(formatear_error(linea_num, "Bucle para inválido"));
-    }
-    procesar_declaracion(partes[0].trim(), entorno).map_err(|e| formatear_error(linea_num, &e))?;
-    while evaluar_bool(partes[1].trim(), entorno)? {
-        match procesar_lineas(bloque, entorno, linea_num + 1) {
-            Ok(()) => {},
-            Err(error) if error == "ROMPER" => break,
-            Err(error) if error == "CONTINUAR" => {
-                aplicar_incremento(partes[2].trim(), entorno)?;
-                continue;
-            },
-            Err(error) => return Err(error),
-        }
-        aplicar_incremento(partes[2].trim(), entorno)?;
+/// La enumeración nombrada por `objeto` en `Estado.Pendiente` o
+/// `Estado.Pagado(10)`, si no hay una variable con ese nombre.
+fn enumeracion_nombrada<'a>(objeto: &Expresion, entorno: &'a Entorno) -> Option<&'a DefEnumeracion> {
+    match &objeto.nodo {
+        NodoExpresion::Variable(nombre) if entorno.obtener(nombre).is_none() => entorno.obtener_enumeracion(nombre),
+        _ => None,
     }
-    Ok(())
 }
 
-fn procesar_bucle_mientras(linea: &str, bloque: &[String], entorno: &mut Entorno, linea_num: usize) -> Result<(), String> {
-    let ini = linea.find('(').ok_or_else(|| formatear_error(linea_num, "Bucle mientras inválido"))?;
-    let fin = linea.rfind(')').ok_or_else(|| formatear_error(linea_num, "Bucle mientras inválido"))?;
-    let condicion = &linea[ini + 1..fin];
-    while evaluar_bool(condicion, entorno)? {
-        match procesar_lineas(bloque, entorno, linea_num + 1) {
-            Ok(()) => {},
-            Err(error) if error == "ROMPER" => break,
-            Err(error) if error == "CONTINUAR" => continue,
-            Err(error) => return Err(error),
-        }
+/// Construye una variante con sus datos, que deben ser de los tipos
+/// declarados; como en los argumentos de una función, solo se amplía un
+/// entero a número.
+fn construir_variante(def: &DefEnumeracion, variante: &str, args: Vec<Valor>, argumentos: &[Expresion]) -> Result<Valor, Diagnostico> {
+    let declarada = def.variante(variante).ok_or_else(|| {
+        Diagnostico::nuevo(
+            diagnostico::MIEMBRO_NO_ENCONTRADO,
+            format!("La enumeración '{}' no tiene la variante '{}'", def.nombre, variante),
+        )
+    })?;
+    if args.len() != declarada.datos.len() {
+        return Err(Diagnostico::nuevo(
+            diagnostico::NUMERO_DE_ARGUMENTOS,
+            format!(
+                "La variante '{}.{}' espera {} datos, pero se proporcionaron {}",
+                def.nombre,
+                variante,
+                declarada.datos.len(),
+                args.len()
+            ),
+        ));
     }
-    Ok(())
+    let mut datos = Vec::with_capacity(args.len());
+    for (i, (tipo, valor)) in declarada.datos.iter().zip(args).enumerate() {
+        let dato = ajustar_a_tipo_declarado(tipo, valor).map_err(|valor| {
+            let error = Diagnostico::nuevo(
+                codigo_incompatible(&valor),
+                format!(
+                    "El dato {} de '{}.{}' es de tipo {}, pero se pasó un valor de tipo {}",
+                    i + 1,
+                    def.nombre,
+                    variante,
+                    tipo,
+                    valor.nombre_tipo()
+                ),
+            );
+            match argumentos.get(i) {
+                Some(argumento) => error.con_posicion(argumento.pos),
+                None => error,
+            }
+        })?;
+        datos.push(dato);
+    }
+    Ok(Valor::Enumeracion(def.nombre.clone(), variante.to_string(), datos))
 }
 
-fn procesar_bucle_hacer(bloque: &[String], condicion: &str, entorno: &mut Entorno, linea_num: usize) -> Result<(), String> {
-    loop {
-        match procesar_lineas(bloque, entorno, linea_num + 1) {
-            Ok(()) => {},
-            Err(error) if error == "ROMPER" => break,
-            Err(error) if error == "CONTINUAR" => {
-                if !evaluar_bool(condicion, entorno)? { break; }
-                continue;
-            },
-            Err(error) => return Err(error),
-        }
-        if !evaluar_bool(condicion, entorno)? { break; }
+fn objeto_no_definido(tipo: &str) -> Diagnostico {
+    Diagnostico::nuevo(diagnostico::TIPO_DESCONOCIDO, format!("Objeto '{}' no definido", tipo))
+}
+
+fn propiedad_inexistente(nombre: &str) -> Diagnostico {
+    Diagnostico::nuevo(diagnostico::MIEMBRO_NO_ENCONTRADO, format!("La propiedad '{}' no existe", nombre))
+}
+
+fn fuera_de_rango(indice: i64, longitud: usize) -> Diagnostico {
+    Diagnostico::nuevo(
+        diagnostico::INDICE_FUERA_DE_RANGO,
+        format!("Índice {} fuera de rango (longitud {})", indice, longitud),
+    )
+}
+
+fn miembro_de_nulo(nombre: &str) -> Diagnostico {
+    Diagnostico::nuevo(
+        diagnostico::VALOR_NULO,
+        format!("No se puede acceder a '{}' de un valor nulo; usa '?.' si el valor puede ser nulo", nombre),
+    )
+}
+
+/// Con `?.` un objeto nulo da nulo, igual que una clave que no existe en
+/// un jsn.
+fn acceder_miembro(valor: Valor, nombre: &str, opcional: bool) -> Result<Valor, Diagnostico> {
+    match valor {
+        Valor::Nulo if opcional => Ok(Valor::Nulo),
+        Valor::Nulo => Err(miembro_de_nulo(nombre)),
+        Valor::Objeto(mut mapa) if opcional => Ok(mapa.remove(nombre).unwrap_or(Valor::Nulo)),
+        Valor::Objeto(mut mapa) | Valor::Instancia(_, mut mapa) => mapa
+            .remove(nombre)
+            .ok_or_else(|| propiedad_inexistente(nombre)),
+        otro => Err(Diagnostico::nuevo(
+            diagnostico::MIEMBRO_NO_ENCONTRADO,
+            format!("Un valor de tipo {} no tiene la propiedad '{}'", otro.nombre_tipo(), nombre),
+        )),
     }
-    Ok(())
 }
 
-fn procesar_bucle_foreach(linea: &str, bloque: &[String], entorno: &mut Entorno, linea_num: usize) -> Result<(), String> {
-    let ini = linea.find('(').ok_or_else(|| formatear_error(linea_num, "Bucle para inválido"))?;
-    let fin = linea.rfind(')').ok_or_else(|| formatear_error(linea_num, "Bucle para inválido"))?;
-    let contenido = &linea[ini + 1..fin];
-    let partes: Vec<&str> = contenido.split(" en ").collect();
-    if partes.len() != 2 { return Err(formatear_error(linea_num, "Bucle para inválido")); }
-    
-    // Extraer el nombre de la variable, considerando que puede tener tipo
-    let declaracion_var = partes[0].trim();
-    let var = if declaracion_var.contains(' ') {
-        // Si contiene espacios, es "tipo variable", tomamos la variable
-        declaracion_var.split_whitespace().last().unwrap_or(declaracion_var)
-    } else {
-        // Si no contiene espacios, es solo la variable
-        declaracion_var
-    };
-    
-    let lista_nombre = partes[1].trim();
-    let lista = entorno.obtener(lista_nombre).cloned().ok_or_else(|| formatear_error(linea_num, "Variable no encontrada"))?;
-    if let Valor::Lista(elementos) = lista {
-        for elem in elementos {
-            entorno.establecer(var, elem);
-            match procesar_lineas(bloque, entorno, linea_num + 1) {
-                Ok(()) => {},
-                Err(error) if error == "ROMPER" => break,
-                Err(error) if error == "CONTINUAR" => continue,
-                Err(error) => return Err(error),
+fn acceder_indice(valor: Valor, indice: Valor) -> Result<Valor, Diagnostico> {
+    match (valor, indice) {
+        (Valor::Lista(mut elementos), Valor::Entero(i)) => {
+            if i < 0 || i as usize >= elementos.len() {
+                return Err(fuera_de_rango(i, elementos.len()));
             }
+            Ok(elementos.swap_remove(i as usize))
         }
-        Ok(())
-    } else {
-        Err(formatear_error(linea_num, "Variable no es lista"))
+        (Valor::Cadena(texto), Valor::Entero(i)) => {
+            let caracter = if i < 0 { None } else { texto.chars().nth(i as usize) };
+            caracter
+                .map(|c| Valor::Cadena(c.to_string()))
+                .ok_or_else(|| fuera_de_rango(i, texto.chars().count()))
+        }
+        (Valor::Objeto(mut mapa), Valor::Cadena(clave)) => mapa
+            .remove(&clave)
+            .ok_or_else(|| propiedad_inexistente(&clave)),
+        (valor, indice) => Err(Diagnostico::nuevo(
+            diagnostico::TIPOS_INCOMPATIBLES,
+            format!("No se puede indexar un valor de tipo {} con {}", valor.nombre_tipo(), indice.nombre_tipo()),
+        )),
     }
 }
 
-fn evaluar_condicion(condicion: &str, entorno: &Entorno) -> Result<bool, String> {
-    let tokens: Vec<&str> = condicion.split_whitespace().collect();
-    if tokens.len() != 3 {
-        return Err("Condición inválida".to_string());
+fn llamar_metodo(objeto: &Expresion, metodo: &str, opcional: bool, argumentos: &[Expresion], entorno: &mut Entorno) -> Result<Valor, Diagnostico> {
+    let mut args = Vec::with_capacity(argumentos.len());
+    for argumento in argumentos {
+        args.push(evaluar_expresion(argumento, entorno)?);
     }
-    let izq = obtener_entero(tokens[0], entorno)?;
-    let der = obtener_entero(tokens[2], entorno)?;
-    match tokens[1] {
-        "<" => Ok(izq < der),
-        "<=" => Ok(izq <= der),
-        ">" => Ok(izq > der),
-        ">=" => Ok(izq >= der),
-        "==" => Ok(izq == der),
-        "!=" => Ok(izq != der),
-        _ => Err("Operador de comparación inválido".to_string()),
+
+    if let Some(def) = enumeracion_nombrada(objeto, entorno) {
+        return construir_variante(def, metodo, args, argumentos);
     }
-}
 
-fn obtener_entero(texto: &str, entorno: &Entorno) -> Result<i64, String> {
-    if let Ok(i) = texto.parse::<i64>() {
-        return Ok(i);
+    // Método invocado sobre el nombre del objeto y no sobre una instancia
+    if let NodoExpresion::Variable(base) = &objeto.nodo {
+        if entorno.obtener(base).is_none() {
+            if let Some(def) = entorno.obtener_objeto(base) {
+                let mut campos = HashMap::new();
+                return Ok(ejecutar_metodo(def, &mut campos, metodo, args).unwrap_or(Valor::Vacio));
+            }
+        }
     }
-    if let Some(valor) = entorno.obtener(texto) {
-        if let Valor::Entero(i) = valor {
-            return Ok(*i);
+
+    // Si el valor está guardado en una variable, directamente o como
+    // elemento o propiedad, se recuerda dónde para guardarlo de nuevo
+    let ruta = ruta_de(objeto, entorno)?;
+    let mut valor = match &ruta {
+        Some((raiz, pasos)) => valor_en_ruta(raiz, pasos, entorno)?,
+        None => evaluar_expresion(objeto, entorno)?,
+    };
+    if let Valor::Nulo = valor {
+        return if opcional { Ok(Valor::Nulo) } else { Err(miembro_de_nulo(metodo)) };
+    }
+    let modifica = match &valor {
+        Valor::Lista(_) => metodo == "agregar",
+        Valor::Instancia(tipo, _) => entorno
+            .obtener_objeto(tipo)
+            .is_some_and(|def| def.modificadores.iter().any(|m| m == metodo)),
+        _ => false,
+    };
+    if let Some((raiz, pasos)) = ruta.as_ref().filter(|_| modifica) {
+        // Modificar un elemento o una propiedad también modifica la variable
+        comprobar_mutable(raiz, entorno)?;
+        // El elemento agregado se ajusta al tipo de los elementos de la lista
+        let mut tipo_lista = entorno.tipo_de(raiz).cloned();
+        for (paso, _) in pasos {
+            tipo_lista = match (tipo_lista.as_ref().map(Tipo::resuelto), paso) {
+                (Some(Tipo::Lista(Some(tipo_elemento))), Paso::Indice(_)) => Some((**tipo_elemento).clone()),
+                _ => None,
+            };
         }
+        if let (Some(tipo_lista), Some(argumento)) = (tipo_lista, args.first_mut()) {
+            if let Tipo::Lista(Some(tipo_elemento)) = tipo_lista.resuelto() {
+                let ruta = objeto.to_string();
+                let valor_argumento = std::mem::replace(argumento, Valor::Vacio);
+                let tipo_argumento = valor_argumento.nombre_tipo();
+                *argumento = convertir_a_tipo(tipo_elemento, &ruta, valor_argumento, entorno).map_err(|_| {
+                    Diagnostico::en(
+                        diagnostico::TIPOS_INCOMPATIBLES,
+                        argumentos[0].pos,
+                        format!("No se puede agregar un valor de tipo {} a la lista '{}' de tipo {}", tipo_argumento, ruta, tipo_lista),
+                    )
+                })?;
+            }
+        }
+    }
+
+    let resultado = if let Valor::Instancia(tipo, campos) = &mut valor {
+        let def = entorno
+            .obtener_objeto(tipo)
+            .ok_or_else(|| objeto_no_definido(tipo))?;
+        if !def.metodos.contains_key(metodo) {
+            return Err(Diagnostico::nuevo(
+                diagnostico::MIEMBRO_NO_ENCONTRADO,
+                format!("El objeto '{}' no tiene el método '{}'", tipo, metodo),
+            ));
+        }
+        ejecutar_metodo(def, campos, metodo, args).unwrap_or(Valor::Vacio)
+    } else {
+        let tipo = valor.nombre_tipo();
+        // Los métodos de los valores básicos solo fallan al convertir
+        aplicar_metodo_valor(&mut valor, metodo, args)
+            .map_err(|mensaje| Diagnostico::nuevo(diagnostico::CONVERSION_INVALIDA, mensaje))?
+            .ok_or_else(|| {
+                Diagnostico::nuevo(
+                    diagnostico::MIEMBRO_NO_ENCONTRADO,
+                    format!("Método '{}' no disponible para {}", metodo, tipo),
+                )
+            })?
+    };
+
+    // Los métodos que modifican el valor, como `lista.agregar(x)`, lo
+    // guardan de nuevo en la variable, también dentro de un elemento o una
+    // propiedad como en `m[0].agregar(x)`
+    match ruta.filter(|_| modifica) {
+        Some((raiz, pasos)) if pasos.is_empty() => entorno.establecer(&raiz, valor),
+        Some((raiz, pasos)) => {
+            let mut contenedor = entorno.obtener(&raiz).cloned().ok_or_else(|| no_encontrada(&raiz))?;
+            colocar_en_ruta(&mut contenedor, &pasos, valor)?;
+            entorno.establecer(&raiz, contenedor);
+        }
+        None => {}
     }
-    Err("Valor entero inválido".to_string())
+    Ok(resultado)
 }
 
 fn aplicar_metodo_valor(valor: &mut Valor, metodo: &str, args: Vec<Valor>) -> Result<Option<Valor>, String> {
     match valor {
         Valor::Lista(lista) => match metodo {
             "agregar" => {
-                if let Some(a) = args.get(0) { lista.push(a.clone()); }
-                Ok(None)
+                if let Some(a) = args.first() { lista.push(a.clone()); }
+                Ok(Some(Valor::Vacio))
             }
             "longitud" => Ok(Some(Valor::Entero(lista.len() as i64))),
             "cadena" => Ok(Some(Valor::Cadena(valor.a_cadena()))),
@@ -1839,104 +1667,172 @@ fn aplicar_metodo_valor(valor: &mut Valor, metodo: &str, args: Vec<Valor>) -> Re
             "cadena" => Ok(Some(Valor::Cadena(valor.a_cadena()))),
             _ => Ok(None),
         },
+        Valor::Enumeracion(..) => match metodo {
+            "cadena" => Ok(Some(Valor::Cadena(valor.a_cadena()))),
+            "jsn" => Ok(Some(valor.a_jsn())),
+            _ => Ok(None),
+        },
         _ => Ok(None),
     }
 }
 
-fn aplicar_incremento(expresion: &str, entorno: &mut Entorno) -> Result<(), String> {
-    if expresion.ends_with("++") {
-        let nombre = expresion.trim_end_matches("++").trim();
-        if let Some(Valor::Entero(i)) = entorno.obtener(nombre).cloned() {
-            entorno.establecer(nombre, Valor::Entero(i + 1));
-            return Ok(());
-        } else {
-            return Err("Variable no encontrada".to_string());
+fn parsear_jsn(texto: &str) -> Result<Valor, String> {
+    fn saltar(bl: &[u8], i: &mut usize) {
+        while *i < bl.len() && bl[*i].is_ascii_whitespace() {
+            *i += 1;
         }
-    } else if expresion.ends_with("--") {
-        let nombre = expresion.trim_end_matches("--").trim();
-        if let Some(Valor::Entero(i)) = entorno.obtener(nombre).cloned() {
-            entorno.establecer(nombre, Valor::Entero(i - 1));
-            return Ok(());
-        } else {
-            return Err("Variable no encontrada".to_string());
+    }
+
+    fn leer_cadena(bl: &[u8], i: &mut usize) -> Result<String, String> {
+        *i += 1; // salta la comilla inicial
+        let inicio = *i;
+        while *i < bl.len() {
+            if bl[*i] == b'"' {
+                let s = String::from_utf8(bl[inicio..*i].to_vec()).map_err(|_| "Cadena inválida".to_string())?;
+                *i += 1;
+                return Ok(s);
+            }
+            *i += 1;
         }
+        Err("Cadena sin cerrar".to_string())
     }
-    Err("Incremento inválido".to_string())
-}
 
-fn procesar_expresion(linea: &str, linea_num: usize, entorno: &mut Entorno) -> Result<(), String> {
-    let texto = linea.trim();
-    
-    // Manejar asignaciones simples (variable = valor)
-    if texto.contains('=') && !texto.contains("==") && !texto.contains("!=") && !texto.contains("<=") && !texto.contains(">=") {
-        let partes: Vec<&str> = texto.splitn(2, '=').collect();
-        if partes.len() == 2 {
-            let variable = partes[0].trim();
-            let valor_expr = partes[1].trim();
-            
-            // Verificar que la variable existe antes de asignar
-            if entorno.obtener(variable).is_none() {
-                return Err(format!("Variable '{}' no encontrada", variable));
+    fn leer_identificador(bl: &[u8], i: &mut usize) -> String {
+        let inicio = *i;
+        while *i < bl.len() && (bl[*i].is_ascii_alphanumeric() || bl[*i] == b'_') {
+            *i += 1;
+        }
+        String::from_utf8_lossy(&bl[inicio..*i]).to_string()
+    }
+
+    fn leer_valor(bl: &[u8], i: &mut usize) -> Result<Valor, String> {
+        saltar(bl, i);
+        if *i >= bl.len() {
+            return Err("JSON incompleto".to_string());
+        }
+        match bl[*i] {
+            b'{' => leer_objeto(bl, i),
+            b'[' => leer_lista(bl, i),
+            b'"' => Ok(Valor::Cadena(leer_cadena(bl, i)?)),
+            b'-' | b'0'..=b'9' => leer_numero(bl, i),
+            b'n' => {
+                if bl[*i..].starts_with(b"null") || bl[*i..].starts_with(b"nulo") {
+                    *i += 4;
+                    Ok(Valor::Nulo)
+                } else {
+                    Err("Valor nulo inválido".to_string())
+                }
             }
-            
-            let valor = evaluar_expresion_valor(valor_expr, entorno)?;
-            entorno.establecer(variable, valor);
-            return Ok(());
+            b'v' => {
+                if bl.len() >= *i + 9 && &bl[*i..*i + 9] == b"verdadero" {
+                    *i += 9;
+                    Ok(Valor::Bool(true))
+                } else {
+                    Err("Valor bool inválido".to_string())
+                }
+            }
+            b'f' => {
+                if bl.len() >= *i + 5 && (&bl[*i..*i + 5] == b"falso" || &bl[*i..*i + 5] == b"false") {
+                    *i += 5;
+                    Ok(Valor::Bool(false))
+                } else {
+                    Err("Valor bool inválido".to_string())
+                }
+            }
+            b't' => {
+                if bl.len() >= *i + 4 && &bl[*i..*i + 4] == b"true" {
+                    *i += 4;
+                    Ok(Valor::Bool(true))
+                } else {
+                    Err("Valor bool inválido".to_string())
+                }
+            }
+            _ => Err("JSON inválido".to_string()),
         }
     }
-    
-    if texto.contains('.') && texto.contains('(') && texto.ends_with(')') {
-        let _ = valor_desde_expresion(texto, linea_num, entorno)?;
-        return Ok(());
+
+    fn leer_numero(bl: &[u8], i: &mut usize) -> Result<Valor, String> {
+        let inicio = *i;
+        if bl[*i] == b'-' { *i += 1; }
+        while *i < bl.len() && (bl[*i].is_ascii_digit() || bl[*i] == b'.') {
+            *i += 1;
+        }
+        let s = String::from_utf8_lossy(&bl[inicio..*i]);
+        if s.contains('.') {
+            s.parse::<f64>().map(Valor::Numero).map_err(|_| "Número inválido".to_string())
+        } else {
+            s.parse::<i64>().map(Valor::Entero).map_err(|_| "Número inválido".to_string())
+        }
     }
-    if texto.ends_with("++") || texto.ends_with("--") {
-        aplicar_incremento(texto, entorno)
-    } else {
-        Err("Expresión no soportada".to_string())
-    }
-}
-
-fn procesar_objeto(lineas: &[String], inicio: usize) -> Result<(DefObjeto, usize), String> {
-    let cabecera = lineas[inicio].trim();
-    let nombre = cabecera
-        .trim_start_matches("objeto")
-        .trim()
-        .trim_end_matches('{')
-        .trim();
-    let mut campos = Vec::new();
-    let mut metodos: std::collections::HashMap<String, TipoMetodo> = std::collections::HashMap::new();
-    let mut i = inicio + 1;
-    while i < lineas.len() {
-        let linea = lineas[i].trim();
-        if linea.starts_with('}') {
-            let mut def = DefObjeto { nombre: nombre.to_string(), campos, metodos };
-            agregar_metodos_built_in(&mut def);
-            return Ok((def, i));
-        }
-        if linea.ends_with('{') {
-            let (_, fin) = extraer_bloque(lineas, i)?;
-            i = fin + 1;
-            continue;
-        }
-        if linea.ends_with(':') {
-            i += 1;
-            continue;
-        }
-        if !linea.is_empty() {
-            let partes: Vec<&str> = linea.split_whitespace().collect();
-            if partes.len() >= 2 {
-                campos.push(partes[1].to_string());
+
+    fn leer_lista(bl: &[u8], i: &mut usize) -> Result<Valor, String> {
+        *i += 1; // salta '['
+        let mut elementos = Vec::new();
+        loop {
+            saltar(bl, i);
+            if *i >= bl.len() { return Err("Lista sin cerrar".to_string()); }
+            if bl[*i] == b']' {
+                *i += 1;
+                break;
+            }
+            elementos.push(leer_valor(bl, i)?);
+            saltar(bl, i);
+            if *i < bl.len() && bl[*i] == b',' {
+                *i += 1;
+            } else if *i < bl.len() && bl[*i] == b']' {
+                *i += 1;
+                break;
+            } else {
+                return Err("Lista inválida".to_string());
+            }
+        }
+        Ok(Valor::Lista(elementos))
+    }
+
+    fn leer_objeto(bl: &[u8], i: &mut usize) -> Result<Valor, String> {
+        *i += 1; // salta '{'
+        let mut mapa = std::collections::HashMap::new();
+        loop {
+            saltar(bl, i);
+            if *i >= bl.len() { return Err("JSON sin cerrar".to_string()); }
+            if bl[*i] == b'}' {
+                *i += 1;
+                break;
+            }
+            let clave = if bl[*i] == b'"' { leer_cadena(bl, i)? } else { leer_identificador(bl, i) };
+            saltar(bl, i);
+            if *i >= bl.len() || bl[*i] != b':' { return Err("JSON inválido".to_string()); }
+            *i += 1;
+            let valor = leer_valor(bl, i)?;
+            mapa.insert(clave, valor);
+            saltar(bl, i);
+            if *i < bl.len() && bl[*i] == b',' {
+                *i += 1;
+                continue;
+            } else if *i < bl.len() && bl[*i] == b'}' {
+                *i += 1;
+                break;
+            } else {
+                return Err("JSON inválido".to_string());
             }
         }
-        i += 1;
+        Ok(Valor::Objeto(mapa))
+    }
+
+    let bytes = texto.as_bytes();
+    let mut i = 0;
+    let valor = leer_valor(bytes, &mut i)?;
+    saltar(bytes, &mut i);
+    if i != bytes.len() {
+        return Err("JSON inválido".to_string());
     }
-    Err("Objeto sin cerrar".to_string())
+    Ok(valor)
 }
 
 fn instanciar_objeto(obj: &DefObjeto, argumentos: Vec<Valor>) -> Valor {
     let mut mapa = std::collections::HashMap::new();
     if obj.nombre == "Empleado" {
-        let nombre = argumentos.get(0).cloned().unwrap_or(Valor::Cadena(String::new()));
+        let nombre = argumentos.first().cloned().unwrap_or(Valor::Cadena(String::new()));
         let edad = argumentos.get(1).cloned().unwrap_or(Valor::Entero(0));
         let salario = argumentos.get(2).cloned().unwrap_or(Valor::Numero(0.0));
         mapa.insert("nombre".to_string(), nombre);
@@ -1945,7 +1841,7 @@ fn instanciar_objeto(obj: &DefObjeto, argumentos: Vec<Valor>) -> Valor {
         mapa.insert("codigo_empleado".to_string(), Valor::Cadena("EMP001".to_string()));
     } else {
         for (i, campo) in obj.campos.iter().enumerate() {
-            mapa.insert(campo.clone(), argumentos.get(i).cloned().unwrap_or(Valor::Vacio));
+            mapa.insert(campo.clone(), argumentos.get(i).cloned().unwrap_or(Valor::Nulo));
         }
     }
     Valor::Instancia(obj.nombre.clone(), mapa)
@@ -1959,9 +1855,10 @@ fn agregar_metodos_built_in(def: &mut DefObjeto) {
             let salario = campos.get("salario").map(|v| v.a_cadena()).unwrap_or_default();
             Some(Valor::Cadena(format!("Empleado: {}, Edad: {}, Salario: {}", nombre, edad, salario)))
         });
+        def.modificadores.push("aumentar_salario".to_string());
         def.metodos.insert("aumentar_salario".to_string(), |campos, args| {
             let mut porcentaje = 10.0;
-            if let Some(arg) = args.get(0) {
+            if let Some(arg) = args.first() {
                 porcentaje = match arg {
                     Valor::Numero(n) => *n,
                     Valor::Entero(i) => *i as f64,
@@ -1988,79 +1885,3 @@ fn ejecutar_metodo(def: &DefObjeto, instancia: &mut std::collections::HashMap<St
     }
 }
 
-fn ejecutar_funcion_usuario(def_funcion: &DefFuncion, llamada: &str, variable_resultado: &str, entorno: &mut Entorno) -> Result<(), String> {
-    // Extraer argumentos de la llamada
-    let args = extraer_argumentos_funcion(llamada)?;
-    
-    // Verificar que el número de argumentos coincida
-    if args.len() != def_funcion.parametros.len() {
-        return Err(format!(
-            "Función '{}' espera {} argumentos, pero se proporcionaron {}",
-            def_funcion.nombre,
-            def_funcion.parametros.len(),
-            args.len()
-        ));
-    }
-    
-    // Crear un nuevo entorno para la función con copia de funciones del entorno padre
-    let mut entorno_funcion = Entorno::nuevo();
-    
-    // Copiar las definiciones de funciones del entorno padre
-    for (_nombre, def_func) in &entorno.funciones {
-        entorno_funcion.definir_funcion(def_func.clone());
-    }
-    
-    // Asignar valores a los parámetros
-    for (i, (nombre_param, _tipo_param)) in def_funcion.parametros.iter().enumerate() {
-        let valor_arg = evaluar_expresion_valor(&args[i], entorno)?;
-        entorno_funcion.establecer(nombre_param, valor_arg);
-    }
-    
-    // Ejecutar el cuerpo de la función
-    let mut valor_retorno = Valor::Vacio;
-    match procesar_lineas(&def_funcion.cuerpo, &mut entorno_funcion, 0) {
-        Ok(_) => {
-            // La función terminó sin retornar explícitamente
-        }
-        Err(resultado) => {
-            // Verificar si es un retorno
-            if resultado.starts_with("RETORNO:") {
-                let valor_retorno_str = &resultado[8..];
-                if valor_retorno_str.trim().is_empty() || valor_retorno_str.trim() == "vacio" {
-                    valor_retorno = Valor::Vacio;
-                } else {
-                    valor_retorno = evaluar_expresion_valor(valor_retorno_str, &mut entorno_funcion)
-                        .unwrap_or(Valor::Vacio);
-                }
-            } else {
-                return Err(resultado);
-            }
-        }
-    }
-    
-    // Establecer el resultado en el entorno padre
-    if !variable_resultado.is_empty() {
-        entorno.establecer(variable_resultado, valor_retorno);
-    }
-    
-    Ok(())
-}
-
-fn procesar_llamada_funcion_sin_asignacion(linea: &str, entorno: &mut Entorno) -> Result<(), String> {
-    let llamada = linea.trim();
-    
-    // Extraer el nombre de la función
-    let nombre_funcion = if let Some(pos) = llamada.find('(') {
-        &llamada[..pos]
-    } else {
-        return Err("Sintaxis de llamada de función inválida".to_string());
-    };
-    
-    // Verificar si es una función definida por el usuario
-    if let Some(def_funcion) = entorno.obtener_funcion(nombre_funcion).cloned() {
-        return ejecutar_funcion_usuario(&def_funcion, llamada, "", entorno);
-    }
-    
-    Err("Función no reconocida".to_string())
-}
-
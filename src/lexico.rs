@@ -0,0 +1,594 @@
+use std::fmt;
+use crate::diagnostico::{self, Diagnostico};
+
+/// Posición de un token dentro del archivo fuente (ambas empiezan en 1).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Posicion {
+    pub linea: usize,
+    pub columna: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TipoToken {
+    // Literales e identificadores
+    Identificador(String),
+    LiteralEntero(i64),
+    LiteralNumero(f64),
+    LiteralCadena(String),
+    /// Cadena con `${expresión}` intercalados.
+    CadenaInterpolada(Vec<FragmentoCadena>),
+
+    // Tipos de datos
+    Vacio,
+    Entero,
+    Numero,
+    Cadena,
+    Bool,
+    Lista,
+    Jsn,
+
+    // Palabras clave
+    Mutable,
+    Mut,
+    /// `var`: declaración con el tipo inferido del valor inicial.
+    Var,
+    /// `tipo`: definición de un alias de tipo.
+    Tipo,
+    Si,
+    Sino,
+    Mientras,
+    Para,
+    Hacer,
+    Romper,
+    Continuar,
+    Retornar,
+    Objeto,
+    Enumeracion,
+    Segun,
+    Caso,
+    Intentar,
+    Atrapar,
+    Finalmente,
+    Lanzar,
+    Nuevo,
+    Asincrono,
+    Verdadero,
+    Falso,
+    Nulo,
+    En,
+    /// Palabra reservada que todavía no tiene significado en el lenguaje.
+    Reservada(String),
+
+    // Agrupación y puntuación
+    ParentesisAbre,
+    ParentesisCierra,
+    LlaveAbre,
+    LlaveCierra,
+    CorcheteAbre,
+    CorcheteCierra,
+    Coma,
+    Punto,
+    PuntoYComa,
+    DosPuntos,
+    Interrogacion,
+    /// `?.`
+    InterrogacionPunto,
+    /// `??`
+    DobleInterrogacion,
+    /// `..`
+    Rango,
+    /// `..=`
+    RangoInclusivo,
+    /// `=>`
+    Flecha,
+
+    // Asignación
+    Asignar,
+    MasAsignar,
+    MenosAsignar,
+    PorAsignar,
+    EntreAsignar,
+    ModuloAsignar,
+    Incremento,
+    Decremento,
+
+    // Operadores
+    Mas,
+    Menos,
+    Por,
+    Entre,
+    Modulo,
+    Igual,
+    Distinto,
+    Menor,
+    MenorIgual,
+    Mayor,
+    MayorIgual,
+    /// `&&` o `y`
+    Y,
+    /// `||` o `o`
+    O,
+    Negacion,
+
+    NuevaLinea,
+}
+
+/// Parte de una cadena interpolada: texto literal o los tokens de una
+/// expresión escrita entre `${` y `}`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FragmentoCadena {
+    Texto(String),
+    Codigo(Vec<Token>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub tipo: TipoToken,
+    /// Texto exacto del token en el código fuente.
+    pub lexema: String,
+    pub pos: Posicion,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.tipo {
+            TipoToken::NuevaLinea => write!(f, "fin de línea"),
+            _ => write!(f, "'{}'", self.lexema),
+        }
+    }
+}
+
+// Función para verificar si un nombre es palabra reservada
+pub fn es_palabra_reservada(nombre: &str) -> bool {
+    let palabras_reservadas = [
+        "vacio", "entero", "número", "numero", "cadena", "bool", "verdadero", "falso", "nulo",
+        "lista", "jsn", "mut", "var", "tipo", "publico", "privado", "libre", "fn", "retornar",
+        "objeto", "enumeracion", "segun", "caso", "nuevo", "ambiente", "asincrono", "esperar", "si", "sino", "mientras",
+        "para", "hacer", "romper", "continuar", "intentar", "atrapar", "finalmente",
+        "lanzar", "excepción", "importar", "exportar", "desde", "como", "y", "o", "en"
+    ];
+
+    palabras_reservadas.contains(&nombre)
+}
+
+fn palabra_clave(texto: &str) -> Option<TipoToken> {
+    let tipo = match texto {
+        "vacio" => TipoToken::Vacio,
+        "entero" => TipoToken::Entero,
+        "número" => TipoToken::Numero,
+        "cadena" => TipoToken::Cadena,
+        "bool" => TipoToken::Bool,
+        "lista" => TipoToken::Lista,
+        "jsn" => TipoToken::Jsn,
+        "mutable" => TipoToken::Mutable,
+        "mut" => TipoToken::Mut,
+        "var" => TipoToken::Var,
+        "tipo" => TipoToken::Tipo,
+        "si" => TipoToken::Si,
+        "sino" => TipoToken::Sino,
+        "mientras" => TipoToken::Mientras,
+        "para" => TipoToken::Para,
+        "hacer" => TipoToken::Hacer,
+        "romper" => TipoToken::Romper,
+        "continuar" => TipoToken::Continuar,
+        "retornar" => TipoToken::Retornar,
+        "objeto" => TipoToken::Objeto,
+        "enumeracion" => TipoToken::Enumeracion,
+        "segun" => TipoToken::Segun,
+        "caso" => TipoToken::Caso,
+        "intentar" => TipoToken::Intentar,
+        "atrapar" => TipoToken::Atrapar,
+        "finalmente" => TipoToken::Finalmente,
+        "lanzar" => TipoToken::Lanzar,
+        "nuevo" => TipoToken::Nuevo,
+        "asincrono" => TipoToken::Asincrono,
+        "verdadero" => TipoToken::Verdadero,
+        "falso" => TipoToken::Falso,
+        "nulo" => TipoToken::Nulo,
+        "en" => TipoToken::En,
+        "y" => TipoToken::Y,
+        "o" => TipoToken::O,
+        otro if es_palabra_reservada(otro) => TipoToken::Reservada(otro.to_string()),
+        _ => return None,
+    };
+    Some(tipo)
+}
+
+struct Lexico {
+    caracteres: Vec<char>,
+    indice: usize,
+    linea: usize,
+    columna: usize,
+    tokens: Vec<Token>,
+    errores: Vec<Diagnostico>,
+}
+
+/// Convierte el código fuente en una lista de tokens. Los comentarios se
+/// descartan y cada salto de línea significativo produce un `NuevaLinea`.
+/// Un error no detiene el análisis: se devuelven todos los encontrados.
+pub fn tokenizar(fuente: &str) -> Result<Vec<Token>, Vec<Diagnostico>> {
+    let mut lexico = Lexico {
+        caracteres: fuente.chars().collect(),
+        indice: 0,
+        linea: 1,
+        columna: 1,
+        tokens: Vec::new(),
+        errores: Vec::new(),
+    };
+    lexico.ejecutar();
+    if lexico.errores.is_empty() {
+        Ok(lexico.tokens)
+    } else {
+        Err(lexico.errores)
+    }
+}
+
+impl Lexico {
+    fn actual(&self) -> Option<char> {
+        self.caracteres.get(self.indice).copied()
+    }
+
+    fn siguiente(&self) -> Option<char> {
+        self.caracteres.get(self.indice + 1).copied()
+    }
+
+    fn avanzar(&mut self) -> Option<char> {
+        let c = self.actual()?;
+        self.indice += 1;
+        if c == '\n' {
+            self.linea += 1;
+            self.columna = 1;
+        } else {
+            self.columna += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, codigo: &'static str, pos: Posicion, mensaje: &str) -> Diagnostico {
+        Diagnostico::en(codigo, pos, mensaje)
+    }
+
+    fn agregar(&mut self, tipo: TipoToken, inicio: usize, pos: Posicion) {
+        let lexema: String = self.caracteres[inicio..self.indice].iter().collect();
+        self.tokens.push(Token { tipo, lexema, pos });
+    }
+
+    fn agregar_nueva_linea(&mut self, pos: Posicion) {
+        let ya_hay_salto = matches!(
+            self.tokens.last(),
+            None | Some(Token { tipo: TipoToken::NuevaLinea, .. })
+        );
+        if !ya_hay_salto {
+            self.tokens.push(Token { tipo: TipoToken::NuevaLinea, lexema: "\n".to_string(), pos });
+        }
+    }
+
+    fn ejecutar(&mut self) {
+        while let Some(c) = self.actual() {
+            let pos = Posicion { linea: self.linea, columna: self.columna };
+            let inicio = self.indice;
+
+            if c == '\n' {
+                self.avanzar();
+                self.agregar_nueva_linea(pos);
+                continue;
+            }
+            if c.is_whitespace() {
+                self.avanzar();
+                continue;
+            }
+            if c == '/' && self.siguiente() == Some('/') {
+                while let Some(c) = self.actual() {
+                    if c == '\n' {
+                        break;
+                    }
+                    self.avanzar();
+                }
+                continue;
+            }
+            if c == '/' && self.siguiente() == Some('*') {
+                if let Err(error) = self.comentario_bloque(pos) {
+                    self.errores.push(error);
+                }
+                continue;
+            }
+            if c.is_ascii_digit() {
+                if let Err(error) = self.numero(inicio, pos) {
+                    self.errores.push(error);
+                }
+                continue;
+            }
+            if c.is_alphabetic() || c == '_' {
+                while let Some(c) = self.actual() {
+                    if !c.is_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    self.avanzar();
+                }
+                let texto: String = self.caracteres[inicio..self.indice].iter().collect();
+                let tipo = palabra_clave(&texto).unwrap_or(TipoToken::Identificador(texto));
+                self.agregar(tipo, inicio, pos);
+                continue;
+            }
+            if c == '"' || c == '\'' {
+                // Una cadena sin cerrar termina al final de la línea
+                if let Err(error) = self.cadena(c, inicio, pos) {
+                    self.errores.push(error);
+                }
+                continue;
+            }
+
+            self.avanzar();
+            let siguiente = self.actual();
+            let doble = |esperado: char| siguiente == Some(esperado);
+            let tipo = match c {
+                '(' => TipoToken::ParentesisAbre,
+                ')' => TipoToken::ParentesisCierra,
+                '{' => TipoToken::LlaveAbre,
+                '}' => TipoToken::LlaveCierra,
+                '[' => TipoToken::CorcheteAbre,
+                ']' => TipoToken::CorcheteCierra,
+                ',' => TipoToken::Coma,
+                '.' if doble('.') => {
+                    self.avanzar();
+                    if self.actual() == Some('=') {
+                        self.avanzar();
+                        TipoToken::RangoInclusivo
+                    } else {
+                        TipoToken::Rango
+                    }
+                }
+                '.' => TipoToken::Punto,
+                ';' => TipoToken::PuntoYComa,
+                ':' => TipoToken::DosPuntos,
+                '?' if doble('?') => { self.avanzar(); TipoToken::DobleInterrogacion }
+                '?' if doble('.') => { self.avanzar(); TipoToken::InterrogacionPunto }
+                '?' => TipoToken::Interrogacion,
+                '+' if doble('+') => { self.avanzar(); TipoToken::Incremento }
+                '+' if doble('=') => { self.avanzar(); TipoToken::MasAsignar }
+                '+' => TipoToken::Mas,
+                '-' if doble('-') => { self.avanzar(); TipoToken::Decremento }
+                '-' if doble('=') => { self.avanzar(); TipoToken::MenosAsignar }
+                '-' => TipoToken::Menos,
+                '*' if doble('=') => { self.avanzar(); TipoToken::PorAsignar }
+                '*' => TipoToken::Por,
+                '/' if doble('=') => { self.avanzar(); TipoToken::EntreAsignar }
+                '/' => TipoToken::Entre,
+                '%' if doble('=') => { self.avanzar(); TipoToken::ModuloAsignar }
+                '%' => TipoToken::Modulo,
+                '=' if doble('=') => { self.avanzar(); TipoToken::Igual }
+                '=' if doble('>') => { self.avanzar(); TipoToken::Flecha }
+                '=' => TipoToken::Asignar,
+                '!' if doble('=') => { self.avanzar(); TipoToken::Distinto }
+                '!' => TipoToken::Negacion,
+                '<' if doble('=') => { self.avanzar(); TipoToken::MenorIgual }
+                '<' => TipoToken::Menor,
+                '>' if doble('=') => { self.avanzar(); TipoToken::MayorIgual }
+                '>' => TipoToken::Mayor,
+                '&' if doble('&') => { self.avanzar(); TipoToken::Y }
+                '|' if doble('|') => { self.avanzar(); TipoToken::O }
+                otro => {
+                    let error = self.error(diagnostico::CARACTER_INESPERADO, pos, &format!("Carácter inesperado '{}'", otro));
+                    self.errores.push(error);
+                    continue;
+                }
+            };
+            self.agregar(tipo, inicio, pos);
+        }
+    }
+
+    /// Los comentarios de bloque se pueden anidar: `/* a /* b */ c */`.
+    fn comentario_bloque(&mut self, pos: Posicion) -> Result<(), Diagnostico> {
+        self.avanzar();
+        self.avanzar();
+        let linea_inicial = self.linea;
+        let mut nivel = 1;
+        while nivel > 0 {
+            match self.actual() {
+                None => return Err(self.error(diagnostico::COMENTARIO_SIN_CERRAR, pos, "Comentario de bloque sin cerrar")),
+                Some('/') if self.siguiente() == Some('*') => {
+                    self.avanzar();
+                    self.avanzar();
+                    nivel += 1;
+                }
+                Some('*') if self.siguiente() == Some('/') => {
+                    self.avanzar();
+                    self.avanzar();
+                    nivel -= 1;
+                }
+                Some(_) => {
+                    self.avanzar();
+                }
+            }
+        }
+        // Un comentario que ocupa varias líneas separa las sentencias que lo rodean
+        if self.linea != linea_inicial {
+            self.agregar_nueva_linea(pos);
+        }
+        Ok(())
+    }
+
+    fn numero(&mut self, inicio: usize, pos: Posicion) -> Result<(), Diagnostico> {
+        while matches!(self.actual(), Some(c) if c.is_ascii_digit()) {
+            self.avanzar();
+        }
+        let mut es_decimal = false;
+        if self.actual() == Some('.') && matches!(self.siguiente(), Some(c) if c.is_ascii_digit()) {
+            es_decimal = true;
+            self.avanzar();
+            while matches!(self.actual(), Some(c) if c.is_ascii_digit()) {
+                self.avanzar();
+            }
+        }
+        let texto: String = self.caracteres[inicio..self.indice].iter().collect();
+        let tipo = if es_decimal {
+            TipoToken::LiteralNumero(texto.parse::<f64>().map_err(|_| self.error(diagnostico::NUMERO_INVALIDO, pos, "Número inválido"))?)
+        } else {
+            TipoToken::LiteralEntero(texto.parse::<i64>().map_err(|_| self.error(diagnostico::NUMERO_INVALIDO, pos, "Entero fuera de rango"))?)
+        };
+        self.agregar(tipo, inicio, pos);
+        Ok(())
+    }
+
+    fn cadena(&mut self, comilla: char, inicio: usize, pos: Posicion) -> Result<(), Diagnostico> {
+        self.avanzar();
+        let mut contenido = String::new();
+        let mut fragmentos = Vec::new();
+        loop {
+            match self.actual() {
+                None => {
+                    let mensaje = format!("Cadena sin cerrar: falta la comilla de cierre {} antes del final del archivo", comilla);
+                    return Err(self.error(diagnostico::CADENA_SIN_CERRAR, pos, &mensaje));
+                }
+                Some('\n') => {
+                    let mensaje = format!("Cadena sin cerrar: falta la comilla de cierre {} antes del final de la línea", comilla);
+                    return Err(self.error(diagnostico::CADENA_SIN_CERRAR, pos, &mensaje));
+                }
+                Some('\\') => {
+                    let pos_escape = Posicion { linea: self.linea, columna: self.columna };
+                    self.avanzar();
+                    match self.escape() {
+                        Ok(c) => contenido.push(c),
+                        // Se informa el error pero se sigue leyendo la cadena
+                        Err(mensaje) => {
+                            let error = self.error(diagnostico::ESCAPE_INVALIDO, pos_escape, &mensaje);
+                            self.errores.push(error);
+                        }
+                    }
+                }
+                Some('$') if self.siguiente() == Some('{') => {
+                    if !contenido.is_empty() {
+                        fragmentos.push(FragmentoCadena::Texto(std::mem::take(&mut contenido)));
+                    }
+                    match self.interpolacion() {
+                        Ok(tokens) => fragmentos.push(FragmentoCadena::Codigo(tokens)),
+                        // Si la interpolación llegó al final de la línea, la cadena
+                        // termina ahí; si no, se sigue leyendo el resto del texto
+                        Err(error) if matches!(self.actual(), None | Some('\n')) => return Err(error),
+                        Err(error) => self.errores.push(error),
+                    }
+                }
+                Some(c) if c == comilla => {
+                    self.avanzar();
+                    break;
+                }
+                Some(c) => {
+                    contenido.push(c);
+                    self.avanzar();
+                }
+            }
+        }
+        if fragmentos.is_empty() {
+            self.agregar(TipoToken::LiteralCadena(contenido), inicio, pos);
+        } else {
+            if !contenido.is_empty() {
+                fragmentos.push(FragmentoCadena::Texto(contenido));
+            }
+            self.agregar(TipoToken::CadenaInterpolada(fragmentos), inicio, pos);
+        }
+        Ok(())
+    }
+
+    /// Lee `${expresión}` dentro de una cadena y devuelve los tokens de la
+    /// expresión, con sus posiciones reales en el archivo, seguidos de la
+    /// llave de cierre. La expresión puede contener a su vez cadenas y llaves.
+    fn interpolacion(&mut self) -> Result<Vec<Token>, Diagnostico> {
+        let pos = Posicion { linea: self.linea, columna: self.columna };
+        self.avanzar();
+        self.avanzar();
+        let pos_codigo = Posicion { linea: self.linea, columna: self.columna };
+        let mut codigo = Vec::new();
+        let mut nivel = 0;
+        let mut comilla: Option<char> = None;
+        let cierre = loop {
+            let pos_actual = Posicion { linea: self.linea, columna: self.columna };
+            let c = match self.actual() {
+                None | Some('\n') => {
+                    return Err(self.error(diagnostico::INTERPOLACION_INVALIDA, pos, "Interpolación sin cerrar: falta '}'"))
+                }
+                Some(c) => c,
+            };
+            self.avanzar();
+            match (comilla, c) {
+                (Some(_), '\\') => {
+                    codigo.push(c);
+                    if let Some(escapado) = self.actual().filter(|c| *c != '\n') {
+                        codigo.push(escapado);
+                        self.avanzar();
+                    }
+                    continue;
+                }
+                (Some(abierta), c) if c == abierta => comilla = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => comilla = Some(c),
+                (None, '{') => nivel += 1,
+                (None, '}') if nivel == 0 => {
+                    break Token { tipo: TipoToken::LlaveCierra, lexema: "}".to_string(), pos: pos_actual };
+                }
+                (None, '}') => nivel -= 1,
+                (None, _) => {}
+            }
+            codigo.push(c);
+        };
+
+        if codigo.iter().all(|c| c.is_whitespace()) {
+            return Err(self.error(diagnostico::INTERPOLACION_INVALIDA, pos, "Interpolación vacía: se esperaba una expresión dentro de ${}"));
+        }
+        let mut lexico = Lexico {
+            caracteres: codigo,
+            indice: 0,
+            linea: pos_codigo.linea,
+            columna: pos_codigo.columna,
+            tokens: Vec::new(),
+            errores: Vec::new(),
+        };
+        lexico.ejecutar();
+        self.errores.append(&mut lexico.errores);
+        lexico.tokens.push(cierre);
+        Ok(lexico.tokens)
+    }
+
+    /// Lee la secuencia de escape que sigue a una barra invertida: `\n`, `\t`,
+    /// `\r`, `\0`, `\\`, `\"`, `\'`, `\$` o `\u{1F426}`.
+    fn escape(&mut self) -> Result<char, String> {
+        let c = match self.actual() {
+            None | Some('\n') => return Err("Secuencia de escape incompleta al final de la línea".to_string()),
+            Some(c) => c,
+        };
+        self.avanzar();
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            '\'' => Ok('\''),
+            '$' => Ok('$'),
+            'u' => {
+                if self.actual() != Some('{') {
+                    return Err("Se esperaba '{' después de \\u, como en \\u{1F426}".to_string());
+                }
+                self.avanzar();
+                let mut hexadecimal = String::new();
+                while let Some(c) = self.actual() {
+                    if c == '}' || c == '\n' || c == '"' || c == '\'' {
+                        break;
+                    }
+                    hexadecimal.push(c);
+                    self.avanzar();
+                }
+                if self.actual() != Some('}') {
+                    return Err("Falta '}' en la secuencia \\u{...}".to_string());
+                }
+                self.avanzar();
+                u32::from_str_radix(&hexadecimal, 16)
+                    .ok()
+                    .filter(|_| (1..=6).contains(&hexadecimal.len()))
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("'\\u{{{}}}' no es un carácter Unicode válido", hexadecimal))
+            }
+            otro => Err(format!("Secuencia de escape desconocida '\\{}'", otro)),
+        }
+    }
+}
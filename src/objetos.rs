@@ -8,10 +8,7 @@ pub struct DefObjeto {
     pub nombre: String,
     pub campos: Vec<String>,
     pub metodos: HashMap<String, TipoMetodo>,
-}
-
-#[derive(Clone, Debug)]
-pub struct Instancia {
-    pub tipo: String,
-    pub campos: HashMap<String, Valor>,
+    /// Métodos que modifican los campos de la instancia; no se pueden
+    /// llamar sobre una variable inmutable.
+    pub modificadores: Vec<String>,
 }
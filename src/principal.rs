@@ -1,8 +1,14 @@
 mod interprete;
+mod lexico;
+mod ast;
+mod sintactico;
 mod valores;
 mod entorno;
 mod consola;
+mod diagnostico;
 mod objetos;
+mod verificador;
+mod alias;
 
 use std::env;
 use std::fs;
@@ -16,17 +22,117 @@ fn mostrar_version() {
 
 fn mostrar_ayuda(_programa: &str) {
     println!("USO:");
-    println!("    quetzal <archivo.qz>");
+    println!("    quetzal [--sin-verificar] <archivo.qz>");
+    println!("    quetzal verificar [--tipos] <archivo.qz>");
+    println!("    quetzal explicar <código>");
     println!("    quetzal --version");
     println!("    quetzal --ayuda");
     println!();
     println!("OPCIONES:");
     println!("    --version       Muestra la versión del intérprete");
     println!("    --ayuda         Muestra esta información de ayuda");
+    println!("    --sin-verificar Ejecuta el programa sin la verificación de tipos previa");
+    println!("    --tipos         Con 'verificar', muestra el tipo inferido de cada 'var'");
+    println!();
+    println!("COMANDOS:");
+    println!("    verificar       Comprueba los tipos del programa sin ejecutarlo");
+    println!("    explicar        Explica en detalle un código de error, como Q0301");
     println!();
     println!("EJEMPLOS:");
     println!("    quetzal programa.qz");
     println!("    quetzal directorio/ejemplo.qz");
+    println!("    quetzal verificar programa.qz");
+    println!("    quetzal verificar --tipos programa.qz");
+    println!("    quetzal explicar Q0102");
+}
+
+fn explicar(codigo: Option<&str>) {
+    let codigo = match codigo {
+        Some(codigo) => codigo,
+        None => {
+            eprintln!("Error: Se requiere un código de error, por ejemplo: quetzal explicar Q0102");
+            std::process::exit(1);
+        }
+    };
+    match diagnostico::explicar(codigo) {
+        Some(texto) => println!("{}: {}", codigo.to_uppercase(), texto),
+        None => {
+            eprintln!("Error: Código de error desconocido '{}'", codigo);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn leer_archivo(ruta_archivo: &str) -> String {
+    match fs::read_to_string(ruta_archivo) {
+        Ok(texto) => texto,
+        Err(error) => {
+            eprintln!("Error: No se pudo leer el archivo '{}': {}", ruta_archivo, error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Muestra todos los errores separados por una línea en blanco y termina el
+/// proceso.
+fn reportar_errores(errores: &[diagnostico::Diagnostico], ruta_archivo: &str, contenido: &str, al_ejecutar: bool) -> ! {
+    for (i, error) in errores.iter().enumerate() {
+        if i > 0 {
+            eprintln!();
+        }
+        eprintln!("{}", error.mostrar(ruta_archivo, contenido));
+    }
+    if errores.len() > 1 {
+        eprintln!();
+        if al_ejecutar {
+            eprintln!("Se encontraron {} errores; el programa no se ejecutó.", errores.len());
+        } else {
+            eprintln!("Se encontraron {} errores.", errores.len());
+        }
+    }
+    std::process::exit(1);
+}
+
+/// Las advertencias se muestran sin detener el programa, seguidas de una
+/// línea en blanco.
+fn mostrar_advertencia(advertencia: &diagnostico::Diagnostico, ruta_archivo: &str, contenido: &str) {
+    eprintln!("{}", advertencia.mostrar(ruta_archivo, contenido));
+    eprintln!();
+}
+
+/// Con `mostrar_tipos` se imprime una línea `ruta:línea:columna: var nombre: tipo`
+/// por cada declaración con `var`, para los editores y otras herramientas.
+fn verificar(ruta_archivo: Option<&str>, mostrar_tipos: bool) {
+    let ruta_archivo = match ruta_archivo {
+        Some(ruta) => ruta,
+        None => {
+            eprintln!("Error: Se requiere un archivo, por ejemplo: quetzal verificar programa.qz");
+            std::process::exit(1);
+        }
+    };
+    let contenido = leer_archivo(ruta_archivo);
+    let resultado = interprete::analizar(&contenido)
+        .and_then(|programa| verificador::verificar(&programa));
+    let verificacion = match resultado {
+        Ok(verificacion) => verificacion,
+        Err(errores) => reportar_errores(&errores, ruta_archivo, &contenido, false),
+    };
+    for advertencia in &verificacion.advertencias {
+        mostrar_advertencia(advertencia, ruta_archivo, &contenido);
+    }
+    if mostrar_tipos {
+        for inferencia in verificacion.inferencias {
+            let tipo = match inferencia.tipo {
+                Some(tipo) => tipo.to_string(),
+                None => "desconocido".to_string(),
+            };
+            println!(
+                "{}:{}:{}: var {}: {}",
+                ruta_archivo, inferencia.pos.linea, inferencia.pos.columna, inferencia.nombre, tipo
+            );
+        }
+    }
+    println!("{}: sin errores", ruta_archivo);
 }
 
 fn main() {
@@ -39,9 +145,9 @@ fn main() {
         std::process::exit(1);
     }
 
-    let argumento = &argumentos[1];
-    
-    match argumento.as_str() {
+    let mut indice_archivo = 1;
+    let mut con_verificacion = true;
+    match argumentos[1].as_str() {
         "--version" => {
             mostrar_version();
             return;
@@ -50,28 +156,39 @@ fn main() {
             mostrar_ayuda(&argumentos[0]);
             return;
         }
-        _ => {
-            // Es un archivo
-            if argumento.starts_with("--") {
-                eprintln!("Error: Opción desconocida '{}'", argumento);
-                eprintln!();
-                mostrar_ayuda(&argumentos[0]);
-                std::process::exit(1);
-            }
+        "explicar" => {
+            explicar(argumentos.get(2).map(String::as_str));
+            return;
+        }
+        "verificar" => {
+            let mostrar_tipos = argumentos.get(2).is_some_and(|a| a == "--tipos");
+            let indice = if mostrar_tipos { 3 } else { 2 };
+            verificar(argumentos.get(indice).map(String::as_str), mostrar_tipos);
+            return;
+        }
+        "--sin-verificar" => {
+            con_verificacion = false;
+            indice_archivo = 2;
         }
+        _ => {}
     }
 
-    let ruta_archivo = &argumentos[1];
-    let contenido = match fs::read_to_string(ruta_archivo) {
-        Ok(texto) => texto,
-        Err(error) => {
-            eprintln!("Error: No se pudo leer el archivo '{}': {}", ruta_archivo, error);
+    let ruta_archivo = match argumentos.get(indice_archivo) {
+        Some(ruta) if !ruta.starts_with("--") => ruta,
+        otro => {
+            match otro {
+                Some(opcion) => eprintln!("Error: Opción desconocida '{}'", opcion),
+                None => eprintln!("Error: Se requiere un archivo."),
+            }
+            eprintln!();
+            mostrar_ayuda(&argumentos[0]);
             std::process::exit(1);
         }
     };
+    let contenido = leer_archivo(ruta_archivo);
 
-    if let Err(error) = interprete::interpretar(&contenido) {
-        eprintln!("{}", error);
-        std::process::exit(1);
+    let advertir = |advertencia: &diagnostico::Diagnostico| mostrar_advertencia(advertencia, ruta_archivo, &contenido);
+    if let Err(errores) = interprete::interpretar(&contenido, con_verificacion, advertir) {
+        reportar_errores(&errores, ruta_archivo, &contenido, true);
     }
 }
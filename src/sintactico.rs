@@ -0,0 +1,1333 @@
+use crate::ast::{
+    Atrapar, Caso, Expresion, NodoExpresion, NodoSentencia, OperadorBinario, OperadorUnario, Parametro, ParteCadena,
+    Patron, Sentencia, Tipo, Variante, VariableBucle,
+};
+use crate::diagnostico::{self, Diagnostico};
+use crate::lexico::{FragmentoCadena, Posicion, TipoToken, Token};
+
+// Analizador sintáctico de descenso recursivo. Convierte los tokens en un
+// árbol de sentencias una sola vez, antes de ejecutar el programa.
+//
+// Gramática de expresiones, de menor a mayor precedencia:
+//
+//   expresion      := ternaria
+//   ternaria       := coalescencia ( "?" expresion ":" expresion )?
+//   coalescencia   := logica_o ( "??" logica_o )*
+//   logica_o       := logica_y ( ( "||" | "o" ) logica_y )*
+//   logica_y       := igualdad ( ( "&&" | "y" ) igualdad )*
+//   igualdad       := comparacion ( ( "==" | "!=" ) comparacion )*
+//   comparacion    := aditiva ( ( "<" | "<=" | ">" | ">=" ) aditiva )*
+//   aditiva        := multiplicativa ( ( "+" | "-" ) multiplicativa )*
+//   multiplicativa := unaria ( ( "*" | "/" | "%" ) unaria )*
+//   unaria         := ( "!" | "-" ) unaria | postfija
+//   postfija       := primaria ( "(" argumentos ")" | ( "." | "?." ) nombre | "[" expresion "]" )*
+//   primaria       := literal | cadena | nombre | "nulo" | "(" expresion ")" | lista | jsn
+//                   | "nuevo" Nombre "(" argumentos ")"
+//
+// Una cadena puede contener `${expresion}`; el analizador léxico entrega los
+// tokens de cada expresión y aquí se analizan como expresiones completas.
+//
+// Un salto de línea termina la sentencia, salvo dentro de paréntesis,
+// corchetes o un literal jsn, o después de un operador binario, `?`, `:` o
+// `=`: en esos casos la sentencia continúa en la línea siguiente.
+//
+// Todos los operadores binarios asocian a la izquierda (`10 - 3 - 2` es
+// `(10 - 3) - 2`); el ternario asocia a la derecha.
+
+/// Analiza el programa completo. Tras un error el analizador se recupera en
+/// el siguiente límite de línea o de bloque y continúa, de modo que se
+/// informan todos los errores de sintaxis del archivo a la vez.
+pub fn analizar(tokens: Vec<Token>) -> Result<Vec<Sentencia>, Vec<Diagnostico>> {
+    let mut analizador = Analizador::nuevo(tokens);
+    let programa = analizador.programa();
+    if analizador.errores.is_empty() {
+        Ok(programa)
+    } else {
+        Err(analizador.errores)
+    }
+}
+
+struct Analizador {
+    tokens: Vec<Token>,
+    indice: usize,
+    /// Mientras sea mayor que cero los saltos de línea se ignoran: dentro de
+    /// paréntesis, corchetes o un literal jsn una sentencia puede continuar
+    /// en las líneas siguientes.
+    ignorar_saltos: usize,
+    /// Etiquetas de los bucles que encierran la sentencia actual dentro de la
+    /// función actual, del más externo al más interno; `romper` y
+    /// `continuar` solo son válidos si hay alguno.
+    bucles: Vec<Option<String>>,
+    /// Verdadero dentro del cuerpo de una función, donde se permite `retornar`.
+    en_funcion: bool,
+    errores: Vec<Diagnostico>,
+}
+
+fn es_tipo(tipo: &TipoToken) -> bool {
+    matches!(
+        tipo,
+        TipoToken::Vacio
+            | TipoToken::Entero
+            | TipoToken::Numero
+            | TipoToken::Cadena
+            | TipoToken::Bool
+            | TipoToken::Lista
+            | TipoToken::Jsn
+    )
+}
+
+/// Las palabras reservadas se aceptan como nombres de miembros y claves jsn
+/// (`x.cadena()`, `{ tipo: "a" }`), pero no como nombres de variables.
+fn es_palabra(token: &Token) -> bool {
+    token.lexema.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && !matches!(token.tipo, TipoToken::LiteralCadena(_))
+}
+
+impl Analizador {
+    fn nuevo(tokens: Vec<Token>) -> Self {
+        Self { tokens, indice: 0, ignorar_saltos: 0, bucles: Vec::new(), en_funcion: false, errores: Vec::new() }
+    }
+
+    // ---------------------------------------------------------------
+    // Utilidades
+    // ---------------------------------------------------------------
+
+    fn posicion_actual(&self) -> usize {
+        let mut i = self.indice;
+        if self.ignorar_saltos > 0 {
+            while matches!(self.tokens.get(i), Some(Token { tipo: TipoToken::NuevaLinea, .. })) {
+                i += 1;
+            }
+        }
+        i
+    }
+
+    fn actual(&self) -> Option<&Token> {
+        self.tokens.get(self.posicion_actual())
+    }
+
+    fn tipo_actual(&self) -> Option<&TipoToken> {
+        self.actual().map(|t| &t.tipo)
+    }
+
+    /// Tipo del token `n` posiciones después del actual, sin saltar líneas.
+    fn mirar(&self, n: usize) -> Option<&TipoToken> {
+        self.tokens.get(self.posicion_actual() + n).map(|t| &t.tipo)
+    }
+
+    fn verificar(&self, tipo: &TipoToken) -> bool {
+        self.tipo_actual() == Some(tipo)
+    }
+
+    fn avanzar(&mut self) -> Result<Token, Diagnostico> {
+        let i = self.posicion_actual();
+        match self.tokens.get(i) {
+            Some(token) => {
+                self.indice = i + 1;
+                match token.tipo {
+                    TipoToken::ParentesisAbre | TipoToken::CorcheteAbre => self.ignorar_saltos += 1,
+                    TipoToken::ParentesisCierra | TipoToken::CorcheteCierra => {
+                        self.ignorar_saltos = self.ignorar_saltos.saturating_sub(1)
+                    }
+                    _ => {}
+                }
+                Ok(token.clone())
+            }
+            None => Err(self.error(diagnostico::TOKEN_INESPERADO, "Fin de archivo inesperado")),
+        }
+    }
+
+    fn coincidir(&mut self, tipo: &TipoToken) -> bool {
+        if self.verificar(tipo) {
+            let _ = self.avanzar();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn consumir(&mut self, tipo: &TipoToken, descripcion: &str) -> Result<Token, Diagnostico> {
+        if self.verificar(tipo) {
+            return self.avanzar();
+        }
+        Err(self.error(diagnostico::TOKEN_INESPERADO, &format!("Se esperaba {} pero se encontró {}", descripcion, self.describir_actual())))
+    }
+
+    fn describir_actual(&self) -> String {
+        match self.actual() {
+            Some(token) => token.to_string(),
+            None => "el fin del archivo".to_string(),
+        }
+    }
+
+    fn posicion(&self) -> Posicion {
+        match self.actual().or_else(|| self.tokens.last()) {
+            Some(token) => token.pos,
+            None => Posicion { linea: 1, columna: 1 },
+        }
+    }
+
+    fn error(&self, codigo: &'static str, mensaje: &str) -> Diagnostico {
+        Diagnostico::en(codigo, self.posicion(), mensaje)
+    }
+
+    fn saltar_lineas(&mut self) {
+        while self.verificar(&TipoToken::NuevaLinea) {
+            self.indice = self.posicion_actual() + 1;
+        }
+    }
+
+    /// Una sentencia termina en un salto de línea, en la llave que cierra el
+    /// bloque o al final del archivo.
+    fn fin_de_sentencia(&mut self) -> Result<(), Diagnostico> {
+        match self.tipo_actual() {
+            None | Some(TipoToken::LlaveCierra) => Ok(()),
+            Some(TipoToken::NuevaLinea) => {
+                self.avanzar()?;
+                Ok(())
+            }
+            _ => Err(self.error(diagnostico::TOKEN_INESPERADO, &format!("Se esperaba fin de línea pero se encontró {}", self.describir_actual()))),
+        }
+    }
+
+    fn nombre(&mut self, contexto: &str) -> Result<String, Diagnostico> {
+        let token = match self.actual() {
+            Some(token) => token.clone(),
+            None => return Err(self.error(diagnostico::TOKEN_INESPERADO, &format!("Falta nombre de {}", contexto))),
+        };
+        match token.tipo {
+            TipoToken::Identificador(nombre) => {
+                self.avanzar()?;
+                Ok(nombre)
+            }
+            _ if es_palabra(&token) => Err(self.error(diagnostico::PALABRA_RESERVADA, &format!(
+                "'{}' es una palabra reservada y no puede usarse como nombre de {}",
+                token.lexema, contexto
+            ))),
+            _ => Err(self.error(diagnostico::TOKEN_INESPERADO, &format!("Se esperaba nombre de {} pero se encontró {}", contexto, token))),
+        }
+    }
+
+    // ---------------------------------------------------------------
+    // Sentencias
+    // ---------------------------------------------------------------
+
+    fn programa(&mut self) -> Vec<Sentencia> {
+        let mut sentencias = Vec::new();
+        loop {
+            self.saltar_lineas();
+            match self.tipo_actual() {
+                None => break,
+                Some(TipoToken::LlaveCierra) => {
+                    let error = self.error(diagnostico::TOKEN_INESPERADO, "'}' no cierra ningún bloque");
+                    self.errores.push(error);
+                    self.indice = self.posicion_actual() + 1;
+                }
+                Some(_) => self.sentencia_recuperable(&mut sentencias),
+            }
+        }
+        sentencias
+    }
+
+    /// Analiza una sentencia y, si falla, registra el error y avanza hasta
+    /// un punto seguro para continuar.
+    fn sentencia_recuperable(&mut self, sentencias: &mut Vec<Sentencia>) {
+        match self.sentencia() {
+            Ok(sentencia) => sentencias.push(sentencia),
+            Err(error) => {
+                self.errores.push(error);
+                self.sincronizar();
+            }
+        }
+    }
+
+    /// Descarta tokens hasta el final de la línea o hasta la llave que cierra
+    /// el bloque actual. Los bloques abiertos en la línea del error se
+    /// descartan completos para no producir errores en cascada.
+    fn sincronizar(&mut self) {
+        self.ignorar_saltos = 0;
+        let mut nivel = 0;
+        while let Some(tipo) = self.tipo_actual().cloned() {
+            match tipo {
+                TipoToken::NuevaLinea if nivel == 0 => {
+                    self.indice = self.posicion_actual() + 1;
+                    return;
+                }
+                TipoToken::LlaveCierra if nivel == 0 => return,
+                TipoToken::LlaveAbre => nivel += 1,
+                TipoToken::LlaveCierra => nivel -= 1,
+                _ => {}
+            }
+            self.indice = self.posicion_actual() + 1;
+        }
+    }
+
+    fn bloque(&mut self) -> Result<Vec<Sentencia>, Diagnostico> {
+        let inicio = self.consumir(&TipoToken::LlaveAbre, "'{'")?;
+        let mut sentencias = Vec::new();
+        loop {
+            self.saltar_lineas();
+            match self.tipo_actual() {
+                None => return Err(Diagnostico::en(diagnostico::DELIMITADOR_SIN_CERRAR, inicio.pos, "Bloque sin cerrar")),
+                Some(TipoToken::LlaveCierra) => {
+                    self.avanzar()?;
+                    break;
+                }
+                Some(_) => self.sentencia_recuperable(&mut sentencias),
+            }
+        }
+        Ok(sentencias)
+    }
+
+    fn sentencia(&mut self) -> Result<Sentencia, Diagnostico> {
+        let pos = self.posicion();
+        let nodo = match self.tipo_actual() {
+            Some(TipoToken::Si) => self.condicional()?,
+            Some(TipoToken::Mientras) => self.bucle_mientras(None)?,
+            Some(TipoToken::Hacer) => self.bucle_hacer(None)?,
+            Some(TipoToken::Para) => self.bucle_para(None)?,
+            Some(TipoToken::Segun) => self.segun()?,
+            Some(TipoToken::Intentar) => self.intentar()?,
+            Some(TipoToken::Lanzar) => {
+                self.avanzar()?;
+                NodoSentencia::Lanzar(self.expresion()?)
+            }
+            Some(TipoToken::Objeto) => self.objeto()?,
+            Some(TipoToken::Enumeracion) => self.enumeracion()?,
+            Some(TipoToken::Tipo) => self.alias()?,
+            Some(TipoToken::Asincrono) => {
+                self.avanzar()?;
+                self.declaracion(true)?
+            }
+            Some(TipoToken::Retornar) => {
+                if !self.en_funcion {
+                    return Err(self.error(diagnostico::FUERA_DE_FUNCION, "'retornar' solo puede usarse dentro de una función"));
+                }
+                self.avanzar()?;
+                match self.tipo_actual() {
+                    None | Some(TipoToken::NuevaLinea) | Some(TipoToken::LlaveCierra) => NodoSentencia::Retornar(None),
+                    _ => NodoSentencia::Retornar(Some(self.expresion()?)),
+                }
+            }
+            Some(TipoToken::Romper) => {
+                if self.bucles.is_empty() {
+                    return Err(self.error(diagnostico::FUERA_DE_BUCLE, "'romper' solo puede usarse dentro de un bucle"));
+                }
+                self.avanzar()?;
+                NodoSentencia::Romper(self.etiqueta_destino()?)
+            }
+            Some(TipoToken::Continuar) => {
+                if self.bucles.is_empty() {
+                    return Err(self.error(diagnostico::FUERA_DE_BUCLE, "'continuar' solo puede usarse dentro de un bucle"));
+                }
+                self.avanzar()?;
+                NodoSentencia::Continuar(self.etiqueta_destino()?)
+            }
+            Some(TipoToken::Identificador(_)) if self.mirar(1) == Some(&TipoToken::DosPuntos) => self.bucle_etiquetado()?,
+            _ if self.es_inicio_declaracion() => self.declaracion(true)?,
+            _ => self.sentencia_simple()?,
+        };
+        self.fin_de_sentencia()?;
+        Ok(Sentencia { nodo, pos })
+    }
+
+    /// `tipo nombre`, `mutable tipo nombre`, `tipo mut nombre` o, para objetos
+    /// definidos por el usuario, `Nombre nombre`.
+    fn es_inicio_declaracion(&self) -> bool {
+        match self.tipo_actual() {
+            Some(TipoToken::Mutable) | Some(TipoToken::Var) => true,
+            Some(tipo) if es_tipo(tipo) => true,
+            Some(TipoToken::Identificador(_)) => match self.mirar(1) {
+                Some(TipoToken::Identificador(_)) | Some(TipoToken::Mut) => true,
+                // `Persona? p`, sin confundirlo con el ternario `a ? b : c`
+                Some(TipoToken::Interrogacion) => match self.mirar(2) {
+                    Some(TipoToken::Mut) => true,
+                    Some(TipoToken::Identificador(_)) => matches!(
+                        self.mirar(3),
+                        None | Some(TipoToken::Asignar) | Some(TipoToken::NuevaLinea) | Some(TipoToken::LlaveCierra)
+                    ),
+                    _ => false,
+                },
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn tipo(&mut self) -> Result<Tipo, Diagnostico> {
+        let token = self.avanzar()?;
+        let tipo = match token.tipo {
+            TipoToken::Vacio => Tipo::Vacio,
+            TipoToken::Entero => Tipo::Entero,
+            TipoToken::Numero => Tipo::Numero,
+            TipoToken::Cadena => Tipo::Cadena,
+            TipoToken::Bool => Tipo::Bool,
+            TipoToken::Jsn => Tipo::Jsn,
+            TipoToken::Lista => {
+                if self.coincidir(&TipoToken::Menor) {
+                    let elemento = self.tipo()?;
+                    self.consumir(&TipoToken::Mayor, "'>'")?;
+                    Tipo::Lista(Some(Box::new(elemento)))
+                } else {
+                    Tipo::Lista(None)
+                }
+            }
+            TipoToken::Identificador(nombre) => Tipo::Nombre(nombre),
+            _ => return Err(Diagnostico::en(diagnostico::TOKEN_INESPERADO, token.pos, format!("Se esperaba un tipo pero se encontró {}", token))),
+        };
+        if self.verificar(&TipoToken::Interrogacion) {
+            let interrogacion = self.avanzar()?;
+            if tipo == Tipo::Vacio {
+                return Err(Diagnostico::en(diagnostico::TOKEN_INESPERADO, interrogacion.pos, "El tipo vacio no puede ser opcional"));
+            }
+            return Ok(Tipo::Opcional(Box::new(tipo)));
+        }
+        Ok(tipo)
+    }
+
+    /// Declaración de variable o, si al nombre le sigue '(', de función.
+    fn declaracion(&mut self, permitir_funcion: bool) -> Result<NodoSentencia, Diagnostico> {
+        let con_mutable = self.coincidir(&TipoToken::Mutable);
+        let tipo = if self.coincidir(&TipoToken::Var) { None } else { Some(self.tipo()?) };
+        let con_mut = self.coincidir(&TipoToken::Mut);
+
+        if let Some(tipo) = &tipo {
+            if permitir_funcion && !con_mutable && matches!(self.mirar(1), Some(TipoToken::ParentesisAbre)) {
+                let nombre = self.nombre("función")?;
+                return self.funcion(nombre, tipo.clone());
+            }
+        }
+
+        let nombre = self.nombre("variable")?;
+        let valor = if self.coincidir(&TipoToken::Asignar) {
+            self.saltar_lineas();
+            Some(self.expresion()?)
+        } else if tipo.is_none() {
+            return Err(self.error(
+                diagnostico::TOKEN_INESPERADO,
+                &format!("'var {}' necesita un valor inicial para inferir su tipo", nombre),
+            ));
+        } else {
+            None
+        };
+        Ok(NodoSentencia::Declaracion { tipo, nombre, mutable: con_mutable || con_mut, valor })
+    }
+
+    fn funcion(&mut self, nombre: String, tipo_retorno: Tipo) -> Result<NodoSentencia, Diagnostico> {
+        self.consumir(&TipoToken::ParentesisAbre, "'('")?;
+        let mut parametros = Vec::new();
+        if !self.verificar(&TipoToken::ParentesisCierra) {
+            loop {
+                let pos = self.posicion();
+                let tipo = self.tipo()?;
+                let mutable = self.coincidir(&TipoToken::Mut);
+                let nombre = self.nombre("parámetro")?;
+                parametros.push(Parametro { nombre, tipo, mutable, pos });
+                if !self.coincidir(&TipoToken::Coma) {
+                    break;
+                }
+            }
+        }
+        self.consumir(&TipoToken::ParentesisCierra, "')'")?;
+
+        // Un `romper` dentro de la función no puede salir de un bucle externo
+        let bucles = std::mem::take(&mut self.bucles);
+        let en_funcion = std::mem::replace(&mut self.en_funcion, true);
+        let cuerpo = self.bloque();
+        self.bucles = bucles;
+        self.en_funcion = en_funcion;
+        let cuerpo = cuerpo?;
+        Ok(NodoSentencia::Funcion { nombre, parametros, tipo_retorno, cuerpo })
+    }
+
+    /// Asignaciones, incrementos y expresiones usadas como sentencia.
+    fn sentencia_simple(&mut self) -> Result<NodoSentencia, Diagnostico> {
+        if self.es_inicio_declaracion() {
+            return self.declaracion(false);
+        }
+
+        let expresion = self.expresion()?;
+        let operador = match self.tipo_actual() {
+            Some(TipoToken::Asignar) => None,
+            Some(TipoToken::MasAsignar) => Some(OperadorBinario::Suma),
+            Some(TipoToken::MenosAsignar) => Some(OperadorBinario::Resta),
+            Some(TipoToken::PorAsignar) => Some(OperadorBinario::Multiplicacion),
+            Some(TipoToken::EntreAsignar) => Some(OperadorBinario::Division),
+            Some(TipoToken::ModuloAsignar) => Some(OperadorBinario::Modulo),
+            Some(TipoToken::Incremento) | Some(TipoToken::Decremento) => {
+                let cambio = if self.avanzar()?.tipo == TipoToken::Incremento { 1 } else { -1 };
+                let nombre = self.destino_asignacion(&expresion)?;
+                return Ok(NodoSentencia::Incremento { nombre, cambio });
+            }
+            _ => return Ok(NodoSentencia::Expresion(expresion)),
+        };
+
+        self.avanzar()?;
+        self.saltar_lineas();
+        if operador.is_none() && matches!(expresion.nodo, NodoExpresion::Indice { .. }) {
+            let (nombre, indices) = self.destino_indice(expresion)?;
+            let valor = self.expresion()?;
+            return Ok(NodoSentencia::AsignacionIndice { nombre, indices, valor });
+        }
+        let nombre = self.destino_asignacion(&expresion)?;
+        let valor = self.expresion()?;
+        Ok(match operador {
+            None => NodoSentencia::Asignacion { nombre, valor },
+            Some(operador) => NodoSentencia::AsignacionCompuesta { nombre, operador, valor },
+        })
+    }
+
+    /// Separa `nombre[a][b]` en el nombre de la variable y sus índices.
+    fn destino_indice(&self, expresion: Expresion) -> Result<(String, Vec<Expresion>), Diagnostico> {
+        let mut indices = Vec::new();
+        let mut actual = expresion;
+        while let NodoExpresion::Indice { objeto, indice } = actual.nodo {
+            indices.push(*indice);
+            actual = *objeto;
+        }
+        indices.reverse();
+        let nombre = self.destino_asignacion(&actual)?;
+        Ok((nombre, indices))
+    }
+
+    fn destino_asignacion(&self, expresion: &Expresion) -> Result<String, Diagnostico> {
+        match &expresion.nodo {
+            NodoExpresion::Variable(nombre) => Ok(nombre.clone()),
+            _ => Err(Diagnostico::en(diagnostico::TOKEN_INESPERADO, expresion.pos, "Destino de asignación inválido")),
+        }
+    }
+
+    fn condicion_entre_parentesis(&mut self) -> Result<Expresion, Diagnostico> {
+        self.consumir(&TipoToken::ParentesisAbre, "'('")?;
+        let condicion = self.expresion()?;
+        self.consumir(&TipoToken::ParentesisCierra, "')'")?;
+        Ok(condicion)
+    }
+
+    fn condicional(&mut self) -> Result<NodoSentencia, Diagnostico> {
+        self.consumir(&TipoToken::Si, "'si'")?;
+        let condicion = self.condicion_entre_parentesis()?;
+        let cuerpo = self.bloque()?;
+        let mut ramas = vec![(condicion, cuerpo)];
+        let mut sino = None;
+
+        loop {
+            // `sino` puede ir en la misma línea que la llave o en la siguiente
+            let guardado = self.indice;
+            self.saltar_lineas();
+            if !self.coincidir(&TipoToken::Sino) {
+                self.indice = guardado;
+                break;
+            }
+            if self.coincidir(&TipoToken::Si) {
+                let condicion = self.condicion_entre_parentesis()?;
+                let cuerpo = self.bloque()?;
+                ramas.push((condicion, cuerpo));
+            } else {
+                sino = Some(self.bloque()?);
+                break;
+            }
+        }
+        Ok(NodoSentencia::Si { ramas, sino })
+    }
+
+    /// `intentar { ... }` seguido de `atrapar`, `finalmente` o ambos.
+    fn intentar(&mut self) -> Result<NodoSentencia, Diagnostico> {
+        self.consumir(&TipoToken::Intentar, "'intentar'")?;
+        let cuerpo = self.bloque()?;
+        let atrapar = if self.sigue_bloque(&TipoToken::Atrapar) {
+            let pos = self.avanzar()?.pos;
+            let variable = if self.coincidir(&TipoToken::ParentesisAbre) {
+                let variable = self.nombre("variable")?;
+                self.consumir(&TipoToken::ParentesisCierra, "')'")?;
+                Some(variable)
+            } else {
+                None
+            };
+            let cuerpo = self.bloque()?;
+            Some(Atrapar { variable, cuerpo, pos })
+        } else {
+            None
+        };
+        let finalmente = if self.sigue_bloque(&TipoToken::Finalmente) {
+            self.avanzar()?;
+            Some(self.bloque()?)
+        } else {
+            None
+        };
+        if atrapar.is_none() && finalmente.is_none() {
+            return Err(self.error(
+                diagnostico::TOKEN_INESPERADO,
+                &format!("Se esperaba 'atrapar' o 'finalmente' pero se encontró {}", self.describir_actual()),
+            ));
+        }
+        Ok(NodoSentencia::Intentar { cuerpo, atrapar, finalmente })
+    }
+
+    /// Indica si la palabra que continúa la sentencia, como `atrapar` tras
+    /// el bloque de `intentar`, viene en la misma línea que la llave o en la
+    /// siguiente; si no viene, no se consume nada.
+    fn sigue_bloque(&mut self, tipo: &TipoToken) -> bool {
+        let guardado = self.indice;
+        self.saltar_lineas();
+        if self.verificar(tipo) {
+            return true;
+        }
+        self.indice = guardado;
+        false
+    }
+
+    /// `segun (valor) { ... }`. Cada caso ocupa su propia línea; `otro`, si
+    /// lo hay, va al final.
+    fn segun(&mut self) -> Result<NodoSentencia, Diagnostico> {
+        let inicio = self.consumir(&TipoToken::Segun, "'segun'")?;
+        let valor = self.condicion_entre_parentesis()?;
+        self.consumir(&TipoToken::LlaveAbre, "'{'")?;
+        let mut casos = Vec::new();
+        let mut otro = None;
+        loop {
+            self.saltar_lineas();
+            let es_otro = matches!(self.tipo_actual(), Some(TipoToken::Identificador(nombre)) if nombre == "otro")
+                && self.mirar(1) == Some(&TipoToken::Flecha);
+            match self.tipo_actual() {
+                None => return Err(Diagnostico::en(diagnostico::DELIMITADOR_SIN_CERRAR, inicio.pos, "Segun sin cerrar")),
+                Some(TipoToken::LlaveCierra) => {
+                    self.avanzar()?;
+                    break;
+                }
+                _ if otro.is_some() => {
+                    return Err(self.error(diagnostico::TOKEN_INESPERADO, "'otro' debe ser el último caso de segun"));
+                }
+                Some(TipoToken::Caso) => casos.push(self.caso()?),
+                _ if es_otro => {
+                    self.avanzar()?;
+                    self.avanzar()?;
+                    otro = Some(self.cuerpo_caso()?);
+                }
+                _ => {
+                    return Err(self.error(
+                        diagnostico::TOKEN_INESPERADO,
+                        &format!("Se esperaba 'caso' u 'otro' pero se encontró {}", self.describir_actual()),
+                    ))
+                }
+            }
+        }
+        Ok(NodoSentencia::Segun { valor, casos, otro })
+    }
+
+    /// `caso patron, patron [si guarda] => cuerpo`
+    fn caso(&mut self) -> Result<Caso, Diagnostico> {
+        let pos = self.consumir(&TipoToken::Caso, "'caso'")?.pos;
+        let mut patrones = vec![self.patron()?];
+        while self.coincidir(&TipoToken::Coma) {
+            patrones.push(self.patron()?);
+        }
+        let guarda = if self.coincidir(&TipoToken::Si) { Some(self.expresion()?) } else { None };
+        self.consumir(&TipoToken::Flecha, "'=>'")?;
+        let cuerpo = self.cuerpo_caso()?;
+        Ok(Caso { patrones, guarda, cuerpo, pos })
+    }
+
+    /// Un bloque o una sola sentencia en la misma línea.
+    fn cuerpo_caso(&mut self) -> Result<Vec<Sentencia>, Diagnostico> {
+        if self.verificar(&TipoToken::LlaveAbre) {
+            let cuerpo = self.bloque()?;
+            self.fin_de_sentencia()?;
+            return Ok(cuerpo);
+        }
+        Ok(vec![self.sentencia()?])
+    }
+
+    fn patron(&mut self) -> Result<Patron, Diagnostico> {
+        match self.tipo_actual().cloned() {
+            Some(TipoToken::CorcheteAbre) => {
+                self.avanzar()?;
+                let mut elementos = Vec::new();
+                if !self.verificar(&TipoToken::CorcheteCierra) {
+                    loop {
+                        elementos.push(self.patron()?);
+                        if !self.coincidir(&TipoToken::Coma) {
+                            break;
+                        }
+                    }
+                }
+                self.consumir(&TipoToken::CorcheteCierra, "']'")?;
+                Ok(Patron::Lista(elementos))
+            }
+            Some(TipoToken::LlaveAbre) => {
+                self.avanzar()?;
+                let mut campos = Vec::new();
+                while !self.verificar(&TipoToken::LlaveCierra) {
+                    let clave = match self.actual() {
+                        Some(token) if es_palabra(token) => token.lexema.clone(),
+                        Some(Token { tipo: TipoToken::LiteralCadena(clave), .. }) => clave.clone(),
+                        _ => return Err(self.error(diagnostico::TOKEN_INESPERADO, &format!("Se esperaba una clave pero se encontró {}", self.describir_actual()))),
+                    };
+                    self.avanzar()?;
+                    let patron = if self.coincidir(&TipoToken::DosPuntos) { self.patron()? } else { Patron::Nombre(clave.clone()) };
+                    campos.push((clave, patron));
+                    if !self.coincidir(&TipoToken::Coma) {
+                        break;
+                    }
+                }
+                self.consumir(&TipoToken::LlaveCierra, "'}'")?;
+                Ok(Patron::Jsn(campos))
+            }
+            Some(tipo) if es_tipo(&tipo) => {
+                let tipo = self.tipo()?;
+                let variable = match self.tipo_actual() {
+                    Some(TipoToken::Identificador(_)) => Some(self.nombre("variable")?),
+                    _ => None,
+                };
+                Ok(Patron::Tipo { tipo, variable })
+            }
+            Some(TipoToken::Identificador(nombre)) => match self.mirar(1) {
+                Some(TipoToken::Identificador(_)) => {
+                    self.avanzar()?;
+                    let variable = self.nombre("variable")?;
+                    Ok(Patron::Tipo { tipo: Tipo::Nombre(nombre), variable: Some(variable) })
+                }
+                Some(TipoToken::Punto) => {
+                    self.avanzar()?;
+                    self.avanzar()?;
+                    let variante = self.nombre("variante")?;
+                    let datos = if self.coincidir(&TipoToken::ParentesisAbre) {
+                        let mut datos = Vec::new();
+                        if !self.verificar(&TipoToken::ParentesisCierra) {
+                            loop {
+                                datos.push(self.patron()?);
+                                if !self.coincidir(&TipoToken::Coma) {
+                                    break;
+                                }
+                            }
+                        }
+                        self.consumir(&TipoToken::ParentesisCierra, "')'")?;
+                        Some(datos)
+                    } else {
+                        None
+                    };
+                    Ok(Patron::Variante { enumeracion: nombre, variante, datos })
+                }
+                _ => {
+                    self.avanzar()?;
+                    Ok(if nombre == "_" { Patron::Comodin } else { Patron::Nombre(nombre) })
+                }
+            },
+            _ => {
+                let desde = self.patron_literal()?;
+                let inclusivo = match self.tipo_actual() {
+                    Some(TipoToken::Rango) => false,
+                    Some(TipoToken::RangoInclusivo) => true,
+                    _ => return Ok(Patron::Literal(desde)),
+                };
+                self.avanzar()?;
+                let hasta = self.patron_literal()?;
+                Ok(Patron::Rango { desde, hasta, inclusivo })
+            }
+        }
+    }
+
+    /// Un literal, con signo si es un número.
+    fn patron_literal(&mut self) -> Result<Expresion, Diagnostico> {
+        let pos = self.posicion();
+        let expresion = self.unaria()?;
+        let es_literal = match &expresion.nodo {
+            NodoExpresion::Entero(_)
+            | NodoExpresion::Numero(_)
+            | NodoExpresion::Cadena(_)
+            | NodoExpresion::Bool(_)
+            | NodoExpresion::Nulo => true,
+            NodoExpresion::Unaria { operador: OperadorUnario::Negativo, operando } => {
+                matches!(operando.nodo, NodoExpresion::Entero(_) | NodoExpresion::Numero(_))
+            }
+            _ => false,
+        };
+        if !es_literal {
+            return Err(Diagnostico::en(
+                diagnostico::TOKEN_INESPERADO,
+                pos,
+                format!("'{}' no es un patrón válido; usa un literal, un nombre, una lista o un jsn", expresion),
+            ));
+        }
+        Ok(expresion)
+    }
+
+    fn cuerpo_bucle(&mut self, etiqueta: &Option<String>) -> Result<Vec<Sentencia>, Diagnostico> {
+        self.bucles.push(etiqueta.clone());
+        let cuerpo = self.bloque();
+        self.bucles.pop();
+        cuerpo
+    }
+
+    /// `etiqueta: bucle`
+    fn bucle_etiquetado(&mut self) -> Result<NodoSentencia, Diagnostico> {
+        let pos = self.posicion();
+        let etiqueta = self.nombre("etiqueta")?;
+        self.consumir(&TipoToken::DosPuntos, "':'")?;
+        if self.bucles.iter().any(|bucle| bucle.as_ref() == Some(&etiqueta)) {
+            return Err(Diagnostico::en(
+                diagnostico::ETIQUETA_INVALIDA,
+                pos,
+                format!("La etiqueta '{}' ya la usa un bucle que contiene a este", etiqueta),
+            ));
+        }
+        let etiqueta = Some(etiqueta);
+        match self.tipo_actual() {
+            Some(TipoToken::Mientras) => self.bucle_mientras(etiqueta),
+            Some(TipoToken::Hacer) => self.bucle_hacer(etiqueta),
+            Some(TipoToken::Para) => self.bucle_para(etiqueta),
+            _ => Err(self.error(
+                diagnostico::ETIQUETA_INVALIDA,
+                &format!("Se esperaba un bucle después de la etiqueta pero se encontró {}", self.describir_actual()),
+            )),
+        }
+    }
+
+    /// Etiqueta opcional después de `romper` o `continuar`; debe ser la de
+    /// un bucle que contenga la sentencia.
+    fn etiqueta_destino(&mut self) -> Result<Option<String>, Diagnostico> {
+        let Some(TipoToken::Identificador(etiqueta)) = self.tipo_actual().cloned() else {
+            return Ok(None);
+        };
+        if !self.bucles.iter().any(|bucle| bucle.as_ref() == Some(&etiqueta)) {
+            return Err(self.error(
+                diagnostico::ETIQUETA_INVALIDA,
+                &format!("Ningún bucle que contenga esta sentencia tiene la etiqueta '{}'", etiqueta),
+            ));
+        }
+        self.avanzar()?;
+        Ok(Some(etiqueta))
+    }
+
+    fn bucle_mientras(&mut self, etiqueta: Option<String>) -> Result<NodoSentencia, Diagnostico> {
+        self.consumir(&TipoToken::Mientras, "'mientras'")?;
+        let condicion = self.condicion_entre_parentesis()?;
+        let cuerpo = self.cuerpo_bucle(&etiqueta)?;
+        Ok(NodoSentencia::Mientras { condicion, cuerpo, etiqueta })
+    }
+
+    fn bucle_hacer(&mut self, etiqueta: Option<String>) -> Result<NodoSentencia, Diagnostico> {
+        self.consumir(&TipoToken::Hacer, "'hacer'")?;
+        let cuerpo = self.cuerpo_bucle(&etiqueta)?;
+        self.saltar_lineas();
+        self.consumir(&TipoToken::Mientras, "'mientras' al final del bucle hacer")?;
+        let condicion = self.condicion_entre_parentesis()?;
+        Ok(NodoSentencia::Hacer { cuerpo, condicion, etiqueta })
+    }
+
+    /// Distingue `para (inicio; condición; paso)` de `para (tipo x en lista)`
+    /// buscando un ';' dentro de los paréntesis.
+    fn es_para_clasico(&self) -> bool {
+        let mut nivel = 0;
+        let mut i = self.posicion_actual();
+        while let Some(token) = self.tokens.get(i) {
+            match token.tipo {
+                TipoToken::ParentesisAbre => nivel += 1,
+                TipoToken::ParentesisCierra => {
+                    nivel -= 1;
+                    if nivel == 0 {
+                        return false;
+                    }
+                }
+                TipoToken::PuntoYComa if nivel == 1 => return true,
+                TipoToken::LlaveAbre => return false,
+                _ => {}
+            }
+            i += 1;
+        }
+        false
+    }
+
+    fn bucle_para(&mut self, etiqueta: Option<String>) -> Result<NodoSentencia, Diagnostico> {
+        self.consumir(&TipoToken::Para, "'para'")?;
+
+        if self.es_para_clasico() {
+            self.consumir(&TipoToken::ParentesisAbre, "'('")?;
+            let pos = self.posicion();
+            let inicio = Sentencia { nodo: self.sentencia_simple()?, pos };
+            self.consumir(&TipoToken::PuntoYComa, "';'")?;
+            let condicion = self.expresion()?;
+            self.consumir(&TipoToken::PuntoYComa, "';'")?;
+            let pos = self.posicion();
+            let paso = Sentencia { nodo: self.sentencia_simple()?, pos };
+            self.consumir(&TipoToken::ParentesisCierra, "')'")?;
+            let cuerpo = self.cuerpo_bucle(&etiqueta)?;
+            return Ok(NodoSentencia::Para {
+                inicio: Box::new(inicio),
+                condicion,
+                paso: Box::new(paso),
+                cuerpo,
+                etiqueta,
+            });
+        }
+
+        self.consumir(&TipoToken::ParentesisAbre, "'('")?;
+        let primera = self.variable_bucle()?;
+        let (clave, variable) = if self.coincidir(&TipoToken::Coma) {
+            (Some(primera), self.variable_bucle()?)
+        } else {
+            (None, primera)
+        };
+        self.consumir(&TipoToken::En, "'en'")?;
+        let iterable = self.expresion()?;
+        self.consumir(&TipoToken::ParentesisCierra, "')'")?;
+        let cuerpo = self.cuerpo_bucle(&etiqueta)?;
+        Ok(NodoSentencia::ParaCada { clave, variable, iterable, cuerpo, etiqueta })
+    }
+
+    /// El tipo de la variable del bucle es opcional: `para (x en lista)`
+    fn variable_bucle(&mut self) -> Result<VariableBucle, Diagnostico> {
+        let (tipo, mutable) = if matches!(self.mirar(1), Some(TipoToken::En) | Some(TipoToken::Coma)) {
+            (None, false)
+        } else {
+            (Some(self.tipo()?), self.coincidir(&TipoToken::Mut))
+        };
+        let nombre = self.nombre("variable")?;
+        Ok(VariableBucle { nombre, tipo, mutable })
+    }
+
+    /// `enumeracion Nombre { Variante, Variante(tipo, ...), ... }`; las
+    /// variantes se separan con comas y pueden ocupar varias líneas.
+    fn enumeracion(&mut self) -> Result<NodoSentencia, Diagnostico> {
+        let inicio = self.consumir(&TipoToken::Enumeracion, "'enumeracion'")?;
+        let nombre = self.nombre("enumeración")?;
+        self.consumir(&TipoToken::LlaveAbre, "'{'")?;
+        let mut variantes = Vec::new();
+        loop {
+            self.saltar_lineas();
+            match self.tipo_actual() {
+                None => return Err(Diagnostico::en(diagnostico::DELIMITADOR_SIN_CERRAR, inicio.pos, "Enumeración sin cerrar")),
+                Some(TipoToken::LlaveCierra) => break,
+                _ => {}
+            }
+            let pos = self.posicion();
+            let variante = self.nombre("variante")?;
+            let mut datos = Vec::new();
+            if self.coincidir(&TipoToken::ParentesisAbre) {
+                loop {
+                    datos.push(self.tipo()?);
+                    if !self.coincidir(&TipoToken::Coma) {
+                        break;
+                    }
+                }
+                self.consumir(&TipoToken::ParentesisCierra, "')'")?;
+            }
+            variantes.push(Variante { nombre: variante, datos, pos });
+            self.saltar_lineas();
+            if !self.coincidir(&TipoToken::Coma) {
+                break;
+            }
+        }
+        self.saltar_lineas();
+        self.consumir(&TipoToken::LlaveCierra, "'}'")?;
+        Ok(NodoSentencia::Enumeracion { nombre, variantes })
+    }
+
+    /// `tipo Nombre = tipo`
+    fn alias(&mut self) -> Result<NodoSentencia, Diagnostico> {
+        self.consumir(&TipoToken::Tipo, "'tipo'")?;
+        let nombre = self.nombre("tipo")?;
+        self.consumir(&TipoToken::Asignar, "'='")?;
+        let tipo = self.tipo()?;
+        Ok(NodoSentencia::Alias { nombre, tipo })
+    }
+
+    /// Solo se registran los campos del objeto (`tipo nombre`); los bloques de
+    /// métodos y las secciones como `publico:` se omiten.
+    fn objeto(&mut self) -> Result<NodoSentencia, Diagnostico> {
+        let inicio = self.consumir(&TipoToken::Objeto, "'objeto'")?;
+        let nombre = self.nombre("objeto")?;
+        self.consumir(&TipoToken::LlaveAbre, "'{'")?;
+        let mut campos = Vec::new();
+        loop {
+            self.saltar_lineas();
+            match self.tipo_actual() {
+                None => return Err(Diagnostico::en(diagnostico::DELIMITADOR_SIN_CERRAR, inicio.pos, "Objeto sin cerrar")),
+                Some(TipoToken::LlaveCierra) => {
+                    self.avanzar()?;
+                    break;
+                }
+                _ => {}
+            }
+
+            let inicio_linea = self.indice;
+            let mut linea = Vec::new();
+            while !matches!(self.tipo_actual(), None | Some(TipoToken::NuevaLinea)) {
+                let token = self.avanzar()?;
+                if token.tipo == TipoToken::LlaveAbre {
+                    self.omitir_bloque(token.pos)?;
+                    linea.push(token);
+                    break;
+                }
+                linea.push(token);
+            }
+
+            match linea.last().map(|t| &t.tipo) {
+                Some(TipoToken::LlaveAbre) | Some(TipoToken::DosPuntos) => {}
+                _ => {
+                    let fin_linea = self.indice;
+                    self.indice = inicio_linea;
+                    let campo = self.campo();
+                    self.indice = fin_linea;
+                    match campo {
+                        Ok(campo) => campos.push(campo),
+                        Err(error) => {
+                            // Se descarta el resto del objeto para no informar
+                            // su llave de cierre como un error más
+                            self.omitir_bloque(inicio.pos)?;
+                            return Err(error);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(NodoSentencia::Objeto { nombre, campos })
+    }
+
+    /// Nombre de un campo declarado como `tipo nombre`, `mutable tipo nombre`
+    /// o `tipo mut nombre`; lo que siga al nombre en la línea se ignora.
+    fn campo(&mut self) -> Result<String, Diagnostico> {
+        self.coincidir(&TipoToken::Mutable);
+        self.tipo()?;
+        self.coincidir(&TipoToken::Mut);
+        self.nombre("campo")
+    }
+
+    /// Avanza hasta la llave que cierra un bloque ya abierto.
+    fn omitir_bloque(&mut self, pos: Posicion) -> Result<(), Diagnostico> {
+        let mut nivel = 1;
+        while nivel > 0 {
+            match self.avanzar() {
+                Ok(token) if token.tipo == TipoToken::LlaveAbre => nivel += 1,
+                Ok(token) if token.tipo == TipoToken::LlaveCierra => nivel -= 1,
+                Ok(_) => {}
+                Err(_) => return Err(Diagnostico::en(diagnostico::DELIMITADOR_SIN_CERRAR, pos, "Bloque sin cerrar")),
+            }
+        }
+        Ok(())
+    }
+
+    // ---------------------------------------------------------------
+    // Expresiones
+    // ---------------------------------------------------------------
+
+    fn expresion(&mut self) -> Result<Expresion, Diagnostico> {
+        self.ternaria()
+    }
+
+    fn ternaria(&mut self) -> Result<Expresion, Diagnostico> {
+        let condicion = self.coalescencia()?;
+        if !self.verificar(&TipoToken::Interrogacion) {
+            return Ok(condicion);
+        }
+        let pos = self.avanzar()?.pos;
+        self.saltar_lineas();
+        let si_verdadero = self.expresion()?;
+        self.saltar_lineas();
+        self.consumir(&TipoToken::DosPuntos, "':' en la expresión ternaria")?;
+        self.saltar_lineas();
+        let si_falso = self.expresion()?;
+        Ok(Expresion {
+            nodo: NodoExpresion::Ternaria {
+                condicion: Box::new(condicion),
+                si_verdadero: Box::new(si_verdadero),
+                si_falso: Box::new(si_falso),
+            },
+            pos,
+        })
+    }
+
+    /// Analiza una cadena de operadores binarios del mismo nivel, asociando
+    /// a la izquierda.
+    fn binaria(
+        &mut self,
+        operando: fn(&mut Self) -> Result<Expresion, Diagnostico>,
+        operador: fn(&TipoToken) -> Option<OperadorBinario>,
+    ) -> Result<Expresion, Diagnostico> {
+        let mut izquierda = operando(self)?;
+        while let Some(op) = self.tipo_actual().and_then(operador) {
+            let pos = self.avanzar()?.pos;
+            // Una línea que termina en un operador continúa en la siguiente
+            self.saltar_lineas();
+            let derecha = operando(self)?;
+            izquierda = Expresion {
+                nodo: NodoExpresion::Binaria {
+                    operador: op,
+                    izquierda: Box::new(izquierda),
+                    derecha: Box::new(derecha),
+                },
+                pos,
+            };
+        }
+        Ok(izquierda)
+    }
+
+    fn coalescencia(&mut self) -> Result<Expresion, Diagnostico> {
+        self.binaria(Self::logica_o, |t| match t {
+            TipoToken::DobleInterrogacion => Some(OperadorBinario::Coalescencia),
+            _ => None,
+        })
+    }
+
+    fn logica_o(&mut self) -> Result<Expresion, Diagnostico> {
+        self.binaria(Self::logica_y, |t| match t {
+            TipoToken::O => Some(OperadorBinario::O),
+            _ => None,
+        })
+    }
+
+    fn logica_y(&mut self) -> Result<Expresion, Diagnostico> {
+        self.binaria(Self::igualdad, |t| match t {
+            TipoToken::Y => Some(OperadorBinario::Y),
+            _ => None,
+        })
+    }
+
+    fn igualdad(&mut self) -> Result<Expresion, Diagnostico> {
+        self.binaria(Self::comparacion, |t| match t {
+            TipoToken::Igual => Some(OperadorBinario::Igual),
+            TipoToken::Distinto => Some(OperadorBinario::Distinto),
+            _ => None,
+        })
+    }
+
+    fn comparacion(&mut self) -> Result<Expresion, Diagnostico> {
+        self.binaria(Self::rango, |t| match t {
+            TipoToken::Menor => Some(OperadorBinario::Menor),
+            TipoToken::MenorIgual => Some(OperadorBinario::MenorIgual),
+            TipoToken::Mayor => Some(OperadorBinario::Mayor),
+            TipoToken::MayorIgual => Some(OperadorBinario::MayorIgual),
+            _ => None,
+        })
+    }
+
+    /// `desde..hasta`, `desde..=hasta` y, después de cualquiera de los dos,
+    /// `paso n`. `paso` no es una palabra reservada.
+    fn rango(&mut self) -> Result<Expresion, Diagnostico> {
+        let desde = self.aditiva()?;
+        let inclusivo = match self.tipo_actual() {
+            Some(TipoToken::Rango) => false,
+            Some(TipoToken::RangoInclusivo) => true,
+            _ => return Ok(desde),
+        };
+        let pos = self.avanzar()?.pos;
+        let hasta = self.aditiva()?;
+        let paso = if matches!(self.tipo_actual(), Some(TipoToken::Identificador(nombre)) if nombre == "paso") {
+            self.avanzar()?;
+            Some(Box::new(self.aditiva()?))
+        } else {
+            None
+        };
+        Ok(Expresion {
+            nodo: NodoExpresion::Rango { desde: Box::new(desde), hasta: Box::new(hasta), inclusivo, paso },
+            pos,
+        })
+    }
+
+    fn aditiva(&mut self) -> Result<Expresion, Diagnostico> {
+        self.binaria(Self::multiplicativa, |t| match t {
+            TipoToken::Mas => Some(OperadorBinario::Suma),
+            TipoToken::Menos => Some(OperadorBinario::Resta),
+            _ => None,
+        })
+    }
+
+    fn multiplicativa(&mut self) -> Result<Expresion, Diagnostico> {
+        self.binaria(Self::unaria, |t| match t {
+            TipoToken::Por => Some(OperadorBinario::Multiplicacion),
+            TipoToken::Entre => Some(OperadorBinario::Division),
+            TipoToken::Modulo => Some(OperadorBinario::Modulo),
+            _ => None,
+        })
+    }
+
+    fn unaria(&mut self) -> Result<Expresion, Diagnostico> {
+        let operador = match self.tipo_actual() {
+            Some(TipoToken::Negacion) => OperadorUnario::Negacion,
+            Some(TipoToken::Menos) => OperadorUnario::Negativo,
+            _ => return self.postfija(),
+        };
+        let pos = self.avanzar()?.pos;
+        let operando = self.unaria()?;
+        Ok(Expresion {
+            nodo: NodoExpresion::Unaria { operador, operando: Box::new(operando) },
+            pos,
+        })
+    }
+
+    fn postfija(&mut self) -> Result<Expresion, Diagnostico> {
+        let mut expresion = self.primaria()?;
+        loop {
+            match self.tipo_actual() {
+                Some(TipoToken::ParentesisAbre) => {
+                    let pos = expresion.pos;
+                    let argumentos = self.argumentos()?;
+                    expresion = Expresion {
+                        nodo: NodoExpresion::Llamada { funcion: Box::new(expresion), argumentos },
+                        pos,
+                    };
+                }
+                Some(TipoToken::Punto) | Some(TipoToken::InterrogacionPunto) => {
+                    let opcional = self.avanzar()?.tipo == TipoToken::InterrogacionPunto;
+                    let token = self.avanzar()?;
+                    if !es_palabra(&token) {
+                        return Err(Diagnostico::en(diagnostico::TOKEN_INESPERADO, token.pos, format!("Se esperaba un nombre después de '.' pero se encontró {}", token)));
+                    }
+                    expresion = Expresion {
+                        nodo: NodoExpresion::Miembro { objeto: Box::new(expresion), nombre: token.lexema, opcional },
+                        pos: token.pos,
+                    };
+                }
+                Some(TipoToken::CorcheteAbre) => {
+                    let pos = self.avanzar()?.pos;
+                    let indice = self.expresion()?;
+                    self.consumir(&TipoToken::CorcheteCierra, "']'")?;
+                    expresion = Expresion {
+                        nodo: NodoExpresion::Indice { objeto: Box::new(expresion), indice: Box::new(indice) },
+                        pos,
+                    };
+                }
+                _ => return Ok(expresion),
+            }
+        }
+    }
+
+    fn argumentos(&mut self) -> Result<Vec<Expresion>, Diagnostico> {
+        self.consumir(&TipoToken::ParentesisAbre, "'('")?;
+        let mut argumentos = Vec::new();
+        if !self.verificar(&TipoToken::ParentesisCierra) {
+            loop {
+                argumentos.push(self.expresion()?);
+                if !self.coincidir(&TipoToken::Coma) {
+                    break;
+                }
+            }
+        }
+        self.consumir(&TipoToken::ParentesisCierra, "')'")?;
+        Ok(argumentos)
+    }
+
+    fn primaria(&mut self) -> Result<Expresion, Diagnostico> {
+        let token = match self.actual() {
+            Some(token) => token.clone(),
+            None => return Err(self.error(diagnostico::TOKEN_INESPERADO, "Se esperaba una expresión pero se encontró el fin del archivo")),
+        };
+        let pos = token.pos;
+        let nodo = match token.tipo {
+            TipoToken::LiteralEntero(i) => {
+                self.avanzar()?;
+                NodoExpresion::Entero(i)
+            }
+            TipoToken::LiteralNumero(n) => {
+                self.avanzar()?;
+                NodoExpresion::Numero(n)
+            }
+            TipoToken::LiteralCadena(c) => {
+                self.avanzar()?;
+                NodoExpresion::Cadena(c)
+            }
+            TipoToken::CadenaInterpolada(fragmentos) => {
+                self.avanzar()?;
+                self.interpolacion(fragmentos)?
+            }
+            TipoToken::Verdadero => {
+                self.avanzar()?;
+                NodoExpresion::Bool(true)
+            }
+            TipoToken::Falso => {
+                self.avanzar()?;
+                NodoExpresion::Bool(false)
+            }
+            TipoToken::Nulo => {
+                self.avanzar()?;
+                NodoExpresion::Nulo
+            }
+            TipoToken::Identificador(nombre) => {
+                self.avanzar()?;
+                NodoExpresion::Variable(nombre)
+            }
+            TipoToken::ParentesisAbre => {
+                self.avanzar()?;
+                let expresion = self.expresion()?;
+                self.consumir(&TipoToken::ParentesisCierra, "')'")?;
+                return Ok(expresion);
+            }
+            TipoToken::CorcheteAbre => self.lista()?,
+            TipoToken::LlaveAbre => self.jsn()?,
+            TipoToken::Nuevo => {
+                self.avanzar()?;
+                let tipo = self.nombre("objeto")?;
+                let argumentos = self.argumentos()?;
+                NodoExpresion::Nuevo { tipo, argumentos }
+            }
+            _ => return Err(self.error(diagnostico::TOKEN_INESPERADO, &format!("Se esperaba una expresión pero se encontró {}", token))),
+        };
+        Ok(Expresion { nodo, pos })
+    }
+
+    /// Cada `${...}` de la cadena se analiza como una expresión completa.
+    fn interpolacion(&mut self, fragmentos: Vec<FragmentoCadena>) -> Result<NodoExpresion, Diagnostico> {
+        let mut partes = Vec::with_capacity(fragmentos.len());
+        for fragmento in fragmentos {
+            match fragmento {
+                FragmentoCadena::Texto(texto) => partes.push(ParteCadena::Texto(texto)),
+                FragmentoCadena::Codigo(tokens) => {
+                    let mut analizador = Analizador::nuevo(tokens);
+                    let expresion = analizador.expresion()?;
+                    analizador.consumir(&TipoToken::LlaveCierra, "'}' al final de la interpolación")?;
+                    partes.push(ParteCadena::Expresion(expresion));
+                }
+            }
+        }
+        Ok(NodoExpresion::Interpolacion(partes))
+    }
+
+    fn lista(&mut self) -> Result<NodoExpresion, Diagnostico> {
+        self.consumir(&TipoToken::CorcheteAbre, "'['")?;
+        let mut elementos = Vec::new();
+        while !self.verificar(&TipoToken::CorcheteCierra) {
+            elementos.push(self.expresion()?);
+            if !self.coincidir(&TipoToken::Coma) {
+                break;
+            }
+        }
+        self.consumir(&TipoToken::CorcheteCierra, "']'")?;
+        Ok(NodoExpresion::Lista(elementos))
+    }
+
+    /// Literal jsn: `{ clave: valor, "otra clave": valor }`. Puede ocupar
+    /// varias líneas.
+    fn jsn(&mut self) -> Result<NodoExpresion, Diagnostico> {
+        self.ignorar_saltos += 1;
+        let resultado = self.campos_jsn();
+        self.ignorar_saltos -= 1;
+        resultado
+    }
+
+    fn campos_jsn(&mut self) -> Result<NodoExpresion, Diagnostico> {
+        let inicio = self.consumir(&TipoToken::LlaveAbre, "'{'")?;
+        let mut campos = Vec::new();
+        while !self.verificar(&TipoToken::LlaveCierra) {
+            let token = match self.actual() {
+                Some(token) => token.clone(),
+                None => return Err(Diagnostico::en(diagnostico::DELIMITADOR_SIN_CERRAR, inicio.pos, "JSON sin cerrar")),
+            };
+            let clave = match token.tipo {
+                TipoToken::LiteralCadena(clave) => clave,
+                _ if es_palabra(&token) => token.lexema,
+                _ => return Err(self.error(diagnostico::TOKEN_INESPERADO, &format!("Se esperaba una clave jsn pero se encontró {}", token))),
+            };
+            self.avanzar()?;
+            self.consumir(&TipoToken::DosPuntos, "':'")?;
+            campos.push((clave, self.expresion()?));
+            if !self.coincidir(&TipoToken::Coma) {
+                break;
+            }
+        }
+        if self.actual().is_none() {
+            return Err(Diagnostico::en(diagnostico::DELIMITADOR_SIN_CERRAR, inicio.pos, "JSON sin cerrar"));
+        }
+        self.consumir(&TipoToken::LlaveCierra, "'}'")?;
+        Ok(NodoExpresion::Jsn(campos))
+    }
+}
@@ -1,8 +1,11 @@
 use std::collections::HashMap;
+use crate::ast::{Parametro, Sentencia, Tipo, Variante};
 
 #[derive(Clone, Debug)]
 pub enum Valor {
     Vacio,
+    /// Ausencia explícita de valor: el literal `nulo` o un campo que falta.
+    Nulo,
     Entero(i64),
     Numero(f64),
     Cadena(String),
@@ -10,34 +13,122 @@ pub enum Valor {
     Lista(Vec<Valor>),
     Objeto(HashMap<String, Valor>),
     Instancia(String, HashMap<String, Valor>),
-    Funcion(DefFuncion),
+    /// Variante de una enumeración: nombre de la enumeración, de la variante
+    /// y sus datos.
+    Enumeracion(String, String, Vec<Valor>),
+}
+
+#[derive(Clone, Debug)]
+pub struct DefEnumeracion {
+    pub nombre: String,
+    pub variantes: Vec<Variante>,
+}
+
+impl DefEnumeracion {
+    pub fn variante(&self, nombre: &str) -> Option<&Variante> {
+        self.variantes.iter().find(|v| v.nombre == nombre)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct DefFuncion {
     pub nombre: String,
-    pub parametros: Vec<(String, String)>, // (nombre, tipo)
-    pub tipo_retorno: String,
-    pub cuerpo: Vec<String>,
+    pub parametros: Vec<Parametro>,
+    pub tipo_retorno: Tipo,
+    pub cuerpo: Vec<Sentencia>,
 }
 
 impl Valor {
-    pub fn valor_por_defecto(tipo: &str) -> Option<Valor> {
+    pub fn valor_por_defecto(tipo: &Tipo) -> Option<Valor> {
         match tipo {
-            "vacio" => Some(Valor::Vacio),
-            "entero" => Some(Valor::Entero(0)),
-            "número" => Some(Valor::Numero(0.0)),
-            "cadena" => Some(Valor::Cadena(String::new())),
-            "bool" => Some(Valor::Bool(false)),
-            "lista" => Some(Valor::Lista(Vec::new())),
-            "jsn" => Some(Valor::Objeto(HashMap::new())),
-            _ => None,
+            Tipo::Vacio => Some(Valor::Vacio),
+            Tipo::Entero => Some(Valor::Entero(0)),
+            Tipo::Numero => Some(Valor::Numero(0.0)),
+            Tipo::Cadena => Some(Valor::Cadena(String::new())),
+            Tipo::Bool => Some(Valor::Bool(false)),
+            Tipo::Lista(_) => Some(Valor::Lista(Vec::new())),
+            Tipo::Jsn => Some(Valor::Objeto(HashMap::new())),
+            Tipo::Opcional(_) => Some(Valor::Nulo),
+            Tipo::Alias { tipo, .. } => Valor::valor_por_defecto(tipo),
+            Tipo::Nombre(_) | Tipo::Nulo => None,
+        }
+    }
+
+    /// Nombre del tipo del valor, tal como se escribe en Quetzal.
+    pub fn nombre_tipo(&self) -> String {
+        match self {
+            Valor::Vacio => "vacio".to_string(),
+            Valor::Nulo => "nulo".to_string(),
+            Valor::Entero(_) => "entero".to_string(),
+            Valor::Numero(_) => "número".to_string(),
+            Valor::Cadena(_) => "cadena".to_string(),
+            Valor::Bool(_) => "bool".to_string(),
+            Valor::Lista(elementos) => {
+                // Si todos los elementos tienen el mismo tipo se muestra como `lista<T>`
+                let tipos: Vec<String> = elementos.iter().map(|e| e.nombre_tipo()).collect();
+                match tipos.first() {
+                    Some(primero) if tipos.iter().all(|t| t == primero) => format!("lista<{}>", primero),
+                    _ => "lista".to_string(),
+                }
+            }
+            Valor::Objeto(_) => "jsn".to_string(),
+            Valor::Instancia(nombre, _) | Valor::Enumeracion(nombre, _, _) => nombre.clone(),
+        }
+    }
+
+    /// Tipo que toma una variable declarada con `var` a partir de su valor.
+    /// Una lista cuyos elementos son todos del mismo tipo es una `lista<T>`.
+    /// El nulo y el vacio no determinan ningún tipo.
+    pub fn tipo(&self) -> Option<Tipo> {
+        let tipo = match self {
+            Valor::Vacio | Valor::Nulo => return None,
+            Valor::Entero(_) => Tipo::Entero,
+            Valor::Numero(_) => Tipo::Numero,
+            Valor::Cadena(_) => Tipo::Cadena,
+            Valor::Bool(_) => Tipo::Bool,
+            Valor::Lista(elementos) => {
+                let tipos: Vec<Option<Tipo>> = elementos.iter().map(|e| e.tipo()).collect();
+                let elemento = match tipos.first() {
+                    Some(Some(primero)) if tipos.iter().all(|t| t.as_ref() == Some(primero)) => {
+                        Some(Box::new(primero.clone()))
+                    }
+                    _ => None,
+                };
+                Tipo::Lista(elemento)
+            }
+            Valor::Objeto(_) => Tipo::Jsn,
+            Valor::Instancia(nombre, _) | Valor::Enumeracion(nombre, _, _) => Tipo::Nombre(nombre.clone()),
+        };
+        Some(tipo)
+    }
+
+    /// Indica si el valor es exactamente del tipo indicado, sin conversiones.
+    pub fn es_de_tipo(&self, tipo: &Tipo) -> bool {
+        match (tipo, self) {
+            (Tipo::Vacio, Valor::Vacio)
+            | (Tipo::Entero, Valor::Entero(_))
+            | (Tipo::Numero, Valor::Numero(_))
+            | (Tipo::Cadena, Valor::Cadena(_))
+            | (Tipo::Bool, Valor::Bool(_))
+            | (Tipo::Lista(None), Valor::Lista(_))
+            | (Tipo::Jsn, Valor::Objeto(_)) => true,
+            (Tipo::Lista(Some(tipo_elemento)), Valor::Lista(elementos)) => {
+                elementos.iter().all(|e| e.es_de_tipo(tipo_elemento))
+            }
+            (Tipo::Nombre(nombre), Valor::Instancia(tipo_valor, _) | Valor::Enumeracion(tipo_valor, _, _)) => {
+                nombre == tipo_valor
+            }
+            (Tipo::Opcional(_), Valor::Nulo) => true,
+            (Tipo::Opcional(tipo), valor) => valor.es_de_tipo(tipo),
+            (Tipo::Alias { tipo, .. }, valor) => valor.es_de_tipo(tipo),
+            _ => false,
         }
     }
 
     pub fn a_cadena(&self) -> String {
         match self {
             Valor::Vacio => "vacio".to_string(),
+            Valor::Nulo => "nulo".to_string(),
             Valor::Entero(i) => i.to_string(),
             Valor::Numero(n) => n.to_string(),
             Valor::Cadena(c) => c.clone(),
@@ -48,28 +139,30 @@ impl Valor {
                 let partes: Vec<String> = lista.iter().map(|v| v.a_cadena()).collect();
                 format!("[{}]", partes.join(", "))
             }
-            Valor::Objeto(obj) => {
-                let partes: Vec<String> = obj
-                    .iter()
-                    .map(|(k, v)| format!("{}: {}", k, v.a_cadena()))
-                    .collect();
-                format!("{{{}}}", partes.join(", "))
+            Valor::Objeto(obj) => format!("{{{}}}", campos_a_cadena(obj)),
+            Valor::Instancia(nombre, campos) => format!("{} {{ {} }}", nombre, campos_a_cadena(campos)),
+            Valor::Enumeracion(nombre, variante, datos) if datos.is_empty() => format!("{}.{}", nombre, variante),
+            Valor::Enumeracion(nombre, variante, datos) => {
+                let partes: Vec<String> = datos.iter().map(|v| v.a_cadena()).collect();
+                format!("{}.{}({})", nombre, variante, partes.join(", "))
             }
-            Valor::Instancia(nombre, campos) => {
-                let partes: Vec<String> = campos
-                    .iter()
-                    .map(|(k, v)| format!("{}: {}", k, v.a_cadena()))
-                    .collect();
-                format!("{} {{ {} }}", nombre, partes.join(", "))
-            }
-            Valor::Funcion(func) => {
-                format!("funcion {}({})", func.nombre, 
-                    func.parametros.iter()
-                        .map(|(nombre, tipo)| format!("{}: {}", nombre, tipo))
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                )
+        }
+    }
+
+    /// Convierte el valor a jsn. Una variante de una enumeración queda como
+    /// `{ tipo, variante, datos }`, también dentro de listas y otros jsn.
+    pub fn a_jsn(&self) -> Valor {
+        match self {
+            Valor::Enumeracion(nombre, variante, datos) => {
+                let mut mapa = HashMap::new();
+                mapa.insert("tipo".to_string(), Valor::Cadena(nombre.clone()));
+                mapa.insert("variante".to_string(), Valor::Cadena(variante.clone()));
+                mapa.insert("datos".to_string(), Valor::Lista(datos.iter().map(|v| v.a_jsn()).collect()));
+                Valor::Objeto(mapa)
             }
+            Valor::Lista(elementos) => Valor::Lista(elementos.iter().map(|v| v.a_jsn()).collect()),
+            Valor::Objeto(mapa) => Valor::Objeto(mapa.iter().map(|(k, v)| (k.clone(), v.a_jsn())).collect()),
+            otro => otro.clone(),
         }
     }
 
@@ -105,3 +198,15 @@ impl Valor {
         }
     }
 }
+
+/// Campos de un jsn o de una instancia como `clave: valor`, en orden
+/// alfabético de clave para que la salida no cambie entre ejecuciones.
+fn campos_a_cadena(campos: &HashMap<String, Valor>) -> String {
+    let mut claves: Vec<&String> = campos.keys().collect();
+    claves.sort();
+    let partes: Vec<String> = claves
+        .into_iter()
+        .map(|clave| format!("{}: {}", clave, campos[clave].a_cadena()))
+        .collect();
+    partes.join(", ")
+}
@@ -0,0 +1,1422 @@
+use std::collections::{HashMap, HashSet};
+use crate::ast::{Caso, Expresion, NodoExpresion, NodoSentencia, OperadorBinario, OperadorUnario, Parametro, ParteCadena, Patron, Sentencia, Tipo, VariableBucle, Variante};
+use crate::diagnostico::{self, Diagnostico};
+use crate::entorno::{self, Enlace};
+use crate::interprete::{funcion_impresion, no_asignable};
+use crate::lexico::Posicion;
+
+// Verificador estático de tipos. Recorre el programa completo después del
+// análisis sintáctico y antes de ejecutar nada, y comprueba declaraciones,
+// asignaciones, argumentos de llamadas, valores de `retornar` y operandos.
+//
+// El tipo de una expresión es `Option<Tipo>`: `None` significa que no se
+// puede conocer sin ejecutar el programa (una propiedad de un jsn, el
+// resultado de convertir una cadena a jsn...). Con un tipo desconocido no se
+// informa ningún error, de modo que el verificador solo rechaza lo que
+// fallaría con seguridad al ejecutarse.
+
+/// Verifica los tipos del programa y devuelve todos los errores encontrados.
+/// Las advertencias no impiden ejecutar y solo se devuelven si no hay
+/// errores.
+pub fn verificar(programa: &[Sentencia]) -> Result<Verificacion, Vec<Diagnostico>> {
+    let mut verificador = Verificador::nuevo();
+    verificador.sentencias(programa);
+    if verificador.errores.is_empty() {
+        Ok(Verificacion { inferencias: verificador.inferencias, advertencias: verificador.advertencias })
+    } else {
+        Err(verificador.errores)
+    }
+}
+
+/// Resultado de verificar un programa sin errores.
+pub struct Verificacion {
+    /// Tipos inferidos para las declaraciones con `var`, en el orden en que
+    /// aparecen.
+    pub inferencias: Vec<Inferencia>,
+    pub advertencias: Vec<Diagnostico>,
+}
+
+/// Tipo inferido para una variable declarada con `var`. `tipo` es `None`
+/// si no se puede conocer antes de ejecutar, como el de una propiedad de un
+/// jsn.
+pub struct Inferencia {
+    pub nombre: String,
+    pub pos: Posicion,
+    pub tipo: Option<Tipo>,
+}
+
+#[derive(Clone)]
+struct Firma {
+    parametros: Vec<Parametro>,
+    tipo_retorno: Tipo,
+}
+
+struct Verificador {
+    /// Funciones, objetos y enumeraciones visibles en el bloque actual:
+    /// los del propio bloque y los de los bloques que lo contienen.
+    funciones: HashMap<String, Firma>,
+    objetos: HashSet<String>,
+    enumeraciones: HashMap<String, Vec<Variante>>,
+    /// Variables visibles en la función actual o en el nivel superior.
+    variables: HashMap<String, Enlace>,
+    /// Nombres declarados en el bloque actual, que no se pueden volver a
+    /// declarar en él.
+    declaradas: HashSet<String>,
+    /// Nombre y tipo de retorno de la función que se está verificando.
+    funcion_actual: Option<(String, Tipo)>,
+    /// Cuántos bloques `intentar` de la función actual encierran la
+    /// sentencia que se verifica; en ellos un error se puede atrapar.
+    intentos: usize,
+    inferencias: Vec<Inferencia>,
+    errores: Vec<Diagnostico>,
+    advertencias: Vec<Diagnostico>,
+}
+
+fn es_numerico(tipo: &Tipo) -> bool {
+    matches!(tipo.resuelto(), Tipo::Entero | Tipo::Numero)
+}
+
+/// Indica si una declaración de tipo `destino` acepta un valor de tipo
+/// `origen`, con las mismas conversiones que aplica el intérprete.
+fn asignable(destino: &Tipo, origen: &Tipo) -> bool {
+    match (destino.resuelto(), origen.resuelto()) {
+        (Tipo::Opcional(_), Tipo::Nulo) => true,
+        (Tipo::Opcional(destino), Tipo::Opcional(origen)) => asignable(destino, origen),
+        (Tipo::Opcional(destino), origen) => asignable(destino, origen),
+        (_, Tipo::Nulo | Tipo::Opcional(_)) => false,
+        (Tipo::Entero | Tipo::Numero, origen) => es_numerico(origen),
+        (Tipo::Cadena | Tipo::Jsn, _) => true,
+        (Tipo::Lista(Some(destino)), Tipo::Lista(Some(origen))) => asignable(destino, origen),
+        (Tipo::Lista(_), Tipo::Lista(_)) => true,
+        (destino, origen) => destino == origen,
+    }
+}
+
+/// Indica si un argumento, un valor retornado o un valor reasignado de tipo
+/// `obtenido` cumple con el tipo `esperado`. Solo se permite ampliar de
+/// entero a número.
+fn compatible(esperado: &Tipo, obtenido: &Tipo) -> bool {
+    match (esperado.resuelto(), obtenido.resuelto()) {
+        (Tipo::Opcional(_), Tipo::Nulo) => true,
+        (Tipo::Opcional(esperado), Tipo::Opcional(obtenido)) => compatible(esperado, obtenido),
+        (Tipo::Opcional(esperado), obtenido) => compatible(esperado, obtenido),
+        (Tipo::Numero, Tipo::Entero) => true,
+        (Tipo::Lista(Some(esperado)), Tipo::Lista(Some(obtenido))) => compatible(esperado, obtenido),
+        (Tipo::Lista(_), Tipo::Lista(_)) => true,
+        (esperado, obtenido) => esperado == obtenido,
+    }
+}
+
+/// Indica si un valor de tipo `valor` puede coincidir con un patrón que solo
+/// acepta valores de tipo `patron`.
+fn puede_coincidir(valor: &Tipo, patron: &Tipo) -> bool {
+    match (valor.resuelto(), patron.resuelto()) {
+        (Tipo::Jsn, _) => true,
+        (Tipo::Opcional(_), Tipo::Nulo) => true,
+        (Tipo::Opcional(valor), patron) => puede_coincidir(valor, patron),
+        (valor, patron) if es_numerico(valor) && es_numerico(patron) => true,
+        (Tipo::Lista(_), Tipo::Lista(_)) => true,
+        (valor, patron) => valor == patron,
+    }
+}
+
+fn sin_opcional(tipo: Tipo) -> Tipo {
+    match sin_alias_opcional(tipo) {
+        Tipo::Opcional(tipo) => *tipo,
+        tipo => tipo,
+    }
+}
+
+/// Un alias de un tipo opcional, como `tipo Apodo = cadena?`, se trata como
+/// el opcional que representa.
+fn sin_alias_opcional(tipo: Tipo) -> Tipo {
+    match tipo {
+        Tipo::Alias { tipo, .. } if matches!(tipo.resuelto(), Tipo::Opcional(_)) => sin_alias_opcional(*tipo),
+        tipo => tipo,
+    }
+}
+
+/// Un valor que puede ser nulo donde no se admite tiene su propio código de
+/// error.
+fn codigo_incompatible(obtenido: &Tipo) -> &'static str {
+    match obtenido.resuelto() {
+        Tipo::Nulo | Tipo::Opcional(_) => diagnostico::VALOR_NULO,
+        _ => diagnostico::TIPOS_INCOMPATIBLES,
+    }
+}
+
+/// Tipo del resultado de un método de los valores básicos, o `None` si el
+/// método no existe para ese tipo.
+fn tipo_metodo(receptor: &Tipo, metodo: &str) -> Option<Option<Tipo>> {
+    let tipo = match (receptor.resuelto(), metodo) {
+        (Tipo::Nombre(_), _) => None,
+        (_, "cadena") if *receptor != Tipo::Vacio => Some(Tipo::Cadena),
+        (Tipo::Lista(_), "agregar") => Some(Tipo::Vacio),
+        (Tipo::Lista(_), "longitud") => Some(Tipo::Entero),
+        (Tipo::Cadena | Tipo::Numero, "entero") => Some(Tipo::Entero),
+        (Tipo::Cadena | Tipo::Entero, "numero") => Some(Tipo::Numero),
+        (Tipo::Cadena, "bool") => Some(Tipo::Bool),
+        (Tipo::Cadena, "lista") => Some(Tipo::Lista(None)),
+        (Tipo::Cadena, "jsn") => None,
+        _ => return None,
+    };
+    Some(tipo)
+}
+
+impl Verificador {
+    fn nuevo() -> Self {
+        Self {
+            funciones: HashMap::new(),
+            objetos: HashSet::new(),
+            enumeraciones: HashMap::new(),
+            variables: HashMap::new(),
+            declaradas: HashSet::new(),
+            funcion_actual: None,
+            intentos: 0,
+            inferencias: Vec::new(),
+            errores: Vec::new(),
+            advertencias: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, codigo: &'static str, pos: Posicion, mensaje: impl Into<String>) {
+        self.errores.push(Diagnostico::en(codigo, pos, mensaje));
+    }
+
+    fn advertir(&mut self, codigo: &'static str, pos: Posicion, mensaje: impl Into<String>) {
+        self.advertencias.push(Diagnostico::en(codigo, pos, mensaje).como_advertencia());
+    }
+
+    /// Registra las funciones, objetos y enumeraciones definidos
+    /// directamente en un bloque. Se registran antes de verificarlo porque
+    /// una función puede llamar a otra definida más abajo; las de un bloque
+    /// interno solo se registran al verificar ese bloque.
+    fn registrar(&mut self, sentencias: &[Sentencia]) {
+        for sentencia in sentencias {
+            match &sentencia.nodo {
+                NodoSentencia::Funcion { nombre, parametros, tipo_retorno, .. } => {
+                    self.funciones.insert(nombre.clone(), Firma {
+                        parametros: parametros.clone(),
+                        tipo_retorno: tipo_retorno.clone(),
+                    });
+                }
+                NodoSentencia::Objeto { nombre, .. } => {
+                    self.objetos.insert(nombre.clone());
+                }
+                NodoSentencia::Enumeracion { nombre, variantes } => {
+                    self.enumeraciones.insert(nombre.clone(), variantes.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Comprueba que el tipo exista; los objetos y las enumeraciones deben
+    /// estar definidos en el programa.
+    fn tipo_valido(&mut self, tipo: &Tipo, pos: Posicion) {
+        match tipo {
+            Tipo::Nombre(nombre) if !self.objetos.contains(nombre) && !self.enumeraciones.contains_key(nombre) => {
+                self.error(diagnostico::TIPO_DESCONOCIDO, pos, format!("Tipo desconocido: {}", nombre));
+            }
+            Tipo::Lista(Some(elemento)) | Tipo::Opcional(elemento) => self.tipo_valido(elemento, pos),
+            _ => {}
+        }
+    }
+
+    // ---------------------------------------------------------------
+    // Sentencias
+    // ---------------------------------------------------------------
+
+    fn sentencias(&mut self, sentencias: &[Sentencia]) {
+        self.registrar(sentencias);
+        for sentencia in sentencias {
+            self.sentencia(sentencia);
+        }
+    }
+
+    /// Verifica sentencias en un bloque propio, como hace el intérprete.
+    fn sentencias_bloque(&mut self, sentencias: &[Sentencia]) {
+        self.bloque(|verificador| verificador.sentencias(sentencias));
+    }
+
+    /// Las variables y definiciones declaradas dentro de `verificar` dejan
+    /// de ser visibles al terminar, y las externas que ocultaron vuelven a
+    /// serlo.
+    fn bloque(&mut self, verificar: impl FnOnce(&mut Self)) {
+        let variables = self.variables.clone();
+        let declaradas = std::mem::take(&mut self.declaradas);
+        let funciones = self.funciones.clone();
+        let objetos = self.objetos.clone();
+        let enumeraciones = self.enumeraciones.clone();
+        verificar(self);
+        self.variables = variables;
+        self.declaradas = declaradas;
+        self.funciones = funciones;
+        self.objetos = objetos;
+        self.enumeraciones = enumeraciones;
+    }
+
+    fn sentencia(&mut self, sentencia: &Sentencia) {
+        let pos = sentencia.pos;
+        match &sentencia.nodo {
+            NodoSentencia::Declaracion { tipo: None, nombre, mutable, valor } => {
+                // El analizador sintáctico exige el valor inicial de `var`
+                let tipo = valor.as_ref().and_then(|valor| self.inferir(nombre, valor));
+                self.inferencias.push(Inferencia { nombre: nombre.clone(), pos, tipo: tipo.clone() });
+                let enlace = Enlace { tipo, mutable: *mutable, declarada_en: Some(pos) };
+                self.declarar(nombre, enlace, pos);
+            }
+            NodoSentencia::Declaracion { tipo: Some(tipo), nombre, mutable, valor } => {
+                self.tipo_valido(tipo, pos);
+                match valor {
+                    Some(valor) => self.valor_asignado(tipo, valor, nombre, asignable),
+                    None => {
+                        // Los objetos no tienen valor por defecto
+                        match tipo.resuelto() {
+                            Tipo::Nombre(objeto) if self.objetos.contains(objeto) => self.error(
+                                diagnostico::TIPOS_INCOMPATIBLES,
+                                pos,
+                                format!("La variable '{}' de tipo {} necesita un valor inicial", nombre, tipo),
+                            ),
+                            _ => {}
+                        }
+                    }
+                }
+                let enlace = Enlace { tipo: Some(tipo.clone()), mutable: *mutable, declarada_en: Some(pos) };
+                self.declarar(nombre, enlace, pos);
+            }
+            NodoSentencia::Asignacion { nombre, valor } => {
+                let declarado = self.variable(nombre, pos);
+                self.comprobar_mutable(nombre, pos);
+                match declarado {
+                    Some(declarado) => self.valor_asignado(&declarado, valor, nombre, compatible),
+                    None => {
+                        self.expresion(valor);
+                    }
+                }
+            }
+            NodoSentencia::AsignacionIndice { nombre, indices, valor } => {
+                let mut tipo = self.variable(nombre, pos);
+                self.comprobar_mutable(nombre, pos);
+                let mut ruta = nombre.clone();
+                for indice in indices {
+                    let tipo_indice = self.expresion(indice);
+                    let indice_es = |esperado: Tipo| tipo_indice.as_ref().is_none_or(|tipo| *tipo == esperado);
+                    tipo = match tipo {
+                        Some(contenedor) => match (contenedor.resuelto(), &tipo_indice) {
+                            (Tipo::Lista(elemento), _) if indice_es(Tipo::Entero) => elemento.as_deref().cloned(),
+                            (Tipo::Jsn, _) if indice_es(Tipo::Cadena) => None,
+                            (_, Some(tipo_indice)) => {
+                                self.error(
+                                    diagnostico::TIPOS_INCOMPATIBLES,
+                                    indice.pos,
+                                    format!(
+                                        "No se puede asignar en un valor de tipo {} con un índice de tipo {}",
+                                        contenedor, tipo_indice
+                                    ),
+                                );
+                                None
+                            }
+                            _ => None,
+                        },
+                        None => None,
+                    };
+                    ruta.push_str(&format!("[{}]", indice));
+                }
+                match tipo {
+                    Some(tipo) => self.valor_asignado(&tipo, valor, &ruta, compatible),
+                    None => {
+                        self.expresion(valor);
+                    }
+                }
+            }
+            NodoSentencia::AsignacionCompuesta { nombre, operador, valor } => {
+                // `x += v` se verifica como `x = x + v`
+                let obtenido = self.expresion(valor);
+                let declarado = self.variable(nombre, pos);
+                self.comprobar_mutable(nombre, pos);
+                let resultado = self.binaria(*operador, declarado.clone(), obtenido, pos);
+                if let (Some(declarado), Some(resultado)) = (declarado, resultado) {
+                    if !compatible(&declarado, &resultado) {
+                        self.error(
+                            diagnostico::TIPOS_INCOMPATIBLES,
+                            valor.pos,
+                            format!("No se puede asignar un valor de tipo {} a la variable '{}' de tipo {}", resultado, nombre, declarado),
+                        );
+                    }
+                }
+            }
+            NodoSentencia::Incremento { nombre, .. } => {
+                let actual = self.variable(nombre, pos);
+                self.comprobar_mutable(nombre, pos);
+                if let Some(actual) = actual {
+                    if actual != Tipo::Entero {
+                        self.error(
+                            diagnostico::TIPOS_INCOMPATIBLES,
+                            pos,
+                            format!("Solo se pueden incrementar o decrementar variables enteras: '{}'", nombre),
+                        );
+                    }
+                }
+            }
+            NodoSentencia::Expresion(expresion) => {
+                self.expresion(expresion);
+            }
+            NodoSentencia::Si { ramas, sino } => {
+                for (condicion, cuerpo) in ramas {
+                    self.condicion(condicion);
+                    self.sentencias_bloque(cuerpo);
+                }
+                if let Some(cuerpo) = sino {
+                    self.sentencias_bloque(cuerpo);
+                }
+            }
+            NodoSentencia::Segun { valor, casos, otro } => {
+                let tipo = self.expresion(valor);
+                for caso in casos {
+                    self.bloque(|verificador| {
+                        // Cada alternativa declara sus propias variables
+                        let mut declaradas = HashSet::new();
+                        for patron in &caso.patrones {
+                            verificador.patron(patron, tipo.as_ref(), caso.pos);
+                            declaradas.extend(std::mem::take(&mut verificador.declaradas));
+                        }
+                        verificador.declaradas = declaradas;
+                        if let Some(guarda) = &caso.guarda {
+                            verificador.condicion(guarda);
+                        }
+                        verificador.sentencias(&caso.cuerpo);
+                    });
+                }
+                match otro {
+                    Some(cuerpo) => self.sentencias_bloque(cuerpo),
+                    None => self.exhaustividad(tipo.as_ref(), casos, pos),
+                }
+            }
+            NodoSentencia::Intentar { cuerpo, atrapar, finalmente } => {
+                self.intentos += 1;
+                self.sentencias_bloque(cuerpo);
+                self.intentos -= 1;
+                if let Some(atrapar) = atrapar {
+                    self.bloque(|verificador| {
+                        // Se puede atrapar cualquier valor, así que su tipo no se conoce
+                        if let Some(variable) = &atrapar.variable {
+                            let enlace = Enlace { tipo: None, mutable: false, declarada_en: Some(atrapar.pos) };
+                            verificador.declarar(variable, enlace, atrapar.pos);
+                        }
+                        verificador.sentencias(&atrapar.cuerpo);
+                    });
+                }
+                if let Some(cuerpo) = finalmente {
+                    self.sentencias_bloque(cuerpo);
+                }
+            }
+            NodoSentencia::Lanzar(valor) => {
+                if self.expresion(valor) == Some(Tipo::Vacio) {
+                    self.error(diagnostico::TIPOS_INCOMPATIBLES, valor.pos, "No se puede lanzar un valor vacio");
+                }
+            }
+            NodoSentencia::Mientras { condicion, cuerpo, .. } => {
+                self.condicion(condicion);
+                self.sentencias_bloque(cuerpo);
+            }
+            NodoSentencia::Hacer { cuerpo, condicion, .. } => {
+                self.sentencias_bloque(cuerpo);
+                self.condicion(condicion);
+            }
+            NodoSentencia::Para { inicio, condicion, paso, cuerpo, .. } => self.bloque(|verificador| {
+                verificador.sentencia(inicio);
+                verificador.condicion(condicion);
+                verificador.sentencias_bloque(cuerpo);
+                verificador.sentencia(paso);
+            }),
+            NodoSentencia::ParaCada { clave, variable, iterable, cuerpo, .. } => {
+                let (tipo_clave, elemento) = match self.expresion(iterable) {
+                    Some(iterado) => match iterado.resuelto() {
+                        Tipo::Lista(elemento) => (Some(Tipo::Entero), elemento.as_deref().cloned()),
+                        Tipo::Cadena => (Some(Tipo::Entero), Some(Tipo::Cadena)),
+                        // Con una sola variable, un jsn se recorre por sus claves
+                        Tipo::Jsn if clave.is_none() => (None, Some(Tipo::Cadena)),
+                        Tipo::Jsn => (Some(Tipo::Cadena), None),
+                        _ => {
+                            self.error(
+                                diagnostico::TIPOS_INCOMPATIBLES,
+                                iterable.pos,
+                                format!("Solo se pueden recorrer listas, cadenas, jsn y rangos, se obtuvo {}", iterado),
+                            );
+                            (None, None)
+                        }
+                    },
+                    None => (None, None),
+                };
+                self.bloque(|verificador| {
+                    if let Some(clave) = clave {
+                        verificador.variable_bucle(clave, tipo_clave, iterable, pos);
+                    }
+                    verificador.variable_bucle(variable, elemento, iterable, pos);
+                    verificador.sentencias(cuerpo);
+                });
+            }
+            NodoSentencia::Funcion { nombre, parametros, tipo_retorno, cuerpo } => {
+                self.tipo_valido(tipo_retorno, pos);
+                let funcion = self.funcion_actual.replace((nombre.clone(), tipo_retorno.clone()));
+                // La función puede llamarse fuera del `intentar` que la contiene
+                let intentos = std::mem::take(&mut self.intentos);
+                self.bloque(|verificador| {
+                    // El cuerpo de una función solo ve sus parámetros y sus
+                    // variables locales, declarados en el mismo bloque
+                    verificador.variables.clear();
+                    for parametro in parametros {
+                        verificador.tipo_valido(&parametro.tipo, parametro.pos);
+                        let enlace = Enlace {
+                            tipo: Some(parametro.tipo.clone()),
+                            mutable: parametro.mutable,
+                            declarada_en: Some(parametro.pos),
+                        };
+                        verificador.declarar(&parametro.nombre, enlace, parametro.pos);
+                    }
+                    verificador.sentencias(cuerpo);
+                });
+                self.funcion_actual = funcion;
+                self.intentos = intentos;
+            }
+            NodoSentencia::Objeto { .. } => {}
+            NodoSentencia::Enumeracion { nombre, variantes } => {
+                for (i, variante) in variantes.iter().enumerate() {
+                    if variantes[..i].iter().any(|v| v.nombre == variante.nombre) {
+                        self.error(
+                            diagnostico::TIPOS_INCOMPATIBLES,
+                            variante.pos,
+                            format!("La variante '{}' está repetida en la enumeración '{}'", variante.nombre, nombre),
+                        );
+                    }
+                    for tipo in &variante.datos {
+                        self.tipo_valido(tipo, variante.pos);
+                    }
+                }
+            }
+            NodoSentencia::Alias { tipo, .. } => self.tipo_valido(tipo, pos),
+            NodoSentencia::Retornar(valor) => self.retornar(valor.as_ref(), pos),
+            NodoSentencia::Romper(_) | NodoSentencia::Continuar(_) => {}
+        }
+    }
+
+    // ---------------------------------------------------------------
+    // Patrones de segun
+    // ---------------------------------------------------------------
+
+    /// Comprueba un patrón contra el tipo del valor, si se conoce, y declara
+    /// las variables que guarda.
+    fn patron(&mut self, patron: &Patron, tipo: Option<&Tipo>, pos: Posicion) {
+        match patron {
+            Patron::Comodin => {}
+            Patron::Literal(literal) => {
+                if let Some(literal_tipo) = self.expresion(literal) {
+                    self.patron_compatible(tipo, &literal_tipo, literal.pos);
+                }
+            }
+            Patron::Rango { desde, hasta, .. } => {
+                for limite in [desde, hasta] {
+                    match self.expresion(limite) {
+                        Some(limite_tipo) if !es_numerico(&limite_tipo) => {
+                            self.error(
+                                diagnostico::TIPOS_INCOMPATIBLES,
+                                limite.pos,
+                                format!("Los límites de un rango deben ser numéricos, se obtuvo {}", limite_tipo),
+                            );
+                        }
+                        Some(limite_tipo) => self.patron_compatible(tipo, &limite_tipo, limite.pos),
+                        None => {}
+                    }
+                }
+            }
+            Patron::Nombre(nombre) if self.tipo_definido(nombre) => {
+                self.patron_compatible(tipo, &Tipo::Nombre(nombre.clone()), pos);
+            }
+            Patron::Nombre(nombre) => self.declarar_patron(nombre, tipo.cloned(), pos),
+            Patron::Tipo { tipo: esperado, variable } => {
+                self.tipo_valido(esperado, pos);
+                self.patron_compatible(tipo, esperado, pos);
+                if let Some(variable) = variable {
+                    self.declarar_patron(variable, Some(esperado.clone()), pos);
+                }
+            }
+            Patron::Lista(patrones) => {
+                let sin_nulo = tipo.map(|tipo| sin_opcional(tipo.clone()));
+                let elemento = match (tipo, sin_nulo.as_ref().map(Tipo::resuelto)) {
+                    (_, Some(Tipo::Lista(elemento))) => elemento.as_deref().cloned(),
+                    (_, Some(Tipo::Jsn)) | (None, _) | (_, None) => None,
+                    (Some(tipo), Some(_)) => {
+                        self.error(
+                            diagnostico::TIPOS_INCOMPATIBLES,
+                            pos,
+                            format!("Un patrón de lista no puede coincidir con un valor de tipo {}", tipo),
+                        );
+                        None
+                    }
+                };
+                for patron in patrones {
+                    self.patron(patron, elemento.as_ref(), pos);
+                }
+            }
+            Patron::Jsn(campos) => {
+                if let Some(tipo) = tipo {
+                    let admitido = match sin_opcional(tipo.clone()).resuelto() {
+                        Tipo::Jsn => true,
+                        Tipo::Nombre(nombre) => self.objetos.contains(nombre),
+                        _ => false,
+                    };
+                    if !admitido {
+                        self.error(
+                            diagnostico::TIPOS_INCOMPATIBLES,
+                            pos,
+                            format!("Un patrón jsn no puede coincidir con un valor de tipo {}", tipo),
+                        );
+                    }
+                }
+                for (_, patron) in campos {
+                    self.patron(patron, None, pos);
+                }
+            }
+            Patron::Variante { enumeracion, variante, datos } => {
+                let declarados = match self.enumeraciones.get(enumeracion) {
+                    Some(variantes) => match variantes.iter().find(|v| v.nombre == *variante) {
+                        Some(declarada) => Some(declarada.datos.clone()),
+                        None => {
+                            self.error(
+                                diagnostico::MIEMBRO_NO_ENCONTRADO,
+                                pos,
+                                format!("La enumeración '{}' no tiene la variante '{}'", enumeracion, variante),
+                            );
+                            None
+                        }
+                    },
+                    None => {
+                        self.error(
+                            diagnostico::TIPO_DESCONOCIDO,
+                            pos,
+                            format!("Enumeración desconocida: {}", enumeracion),
+                        );
+                        None
+                    }
+                };
+                if self.enumeraciones.contains_key(enumeracion) {
+                    self.patron_compatible(tipo, &Tipo::Nombre(enumeracion.clone()), pos);
+                }
+                let Some(patrones) = datos else { return };
+                if let Some(declarados) = &declarados {
+                    if declarados.len() != patrones.len() {
+                        self.error(
+                            diagnostico::NUMERO_DE_ARGUMENTOS,
+                            pos,
+                            format!(
+                                "La variante '{}.{}' tiene {} datos, pero el patrón tiene {}",
+                                enumeracion,
+                                variante,
+                                declarados.len(),
+                                patrones.len()
+                            ),
+                        );
+                    }
+                }
+                for (i, patron) in patrones.iter().enumerate() {
+                    let dato = declarados.as_ref().and_then(|declarados| declarados.get(i));
+                    self.patron(patron, dato.cloned().as_ref(), pos);
+                }
+            }
+        }
+    }
+
+    fn tipo_definido(&self, nombre: &str) -> bool {
+        self.objetos.contains(nombre) || self.enumeraciones.contains_key(nombre)
+    }
+
+    /// Informa si ningún valor del tipo `valor` puede coincidir con un
+    /// patrón de tipo `patron`.
+    fn patron_compatible(&mut self, valor: Option<&Tipo>, patron: &Tipo, pos: Posicion) {
+        if let Some(valor) = valor {
+            if !puede_coincidir(valor, patron) {
+                self.error(
+                    diagnostico::TIPOS_INCOMPATIBLES,
+                    pos,
+                    format!("Un patrón de tipo {} no puede coincidir con un valor de tipo {}", patron, valor),
+                );
+            }
+        }
+    }
+
+    fn declarar_patron(&mut self, nombre: &str, tipo: Option<Tipo>, pos: Posicion) {
+        let enlace = Enlace { tipo, mutable: false, declarada_en: Some(pos) };
+        self.declarar(nombre, enlace, pos);
+    }
+
+    /// Advierte si algún valor puede no coincidir con ningún caso. Los casos
+    /// con guarda no cuentan porque la guarda puede no cumplirse.
+    fn exhaustividad(&mut self, tipo: Option<&Tipo>, casos: &[Caso], pos: Posicion) {
+        let patrones: Vec<&Patron> = casos
+            .iter()
+            .filter(|caso| caso.guarda.is_none())
+            .flat_map(|caso| &caso.patrones)
+            .collect();
+        match self.faltantes(tipo, &patrones) {
+            Some(faltantes) if faltantes.is_empty() => {}
+            Some(faltantes) => {
+                let verbo = if faltantes.len() == 1 { "falta" } else { "faltan" };
+                self.advertir(
+                    diagnostico::SEGUN_NO_EXHAUSTIVO,
+                    pos,
+                    format!(
+                        "El segun no cubre todos los casos, {} {}; agrega los casos que faltan u 'otro =>'",
+                        verbo,
+                        faltantes.join(", ")
+                    ),
+                );
+            }
+            None => {
+                let tipo = match tipo {
+                    Some(tipo) => format!(" de tipo {}", tipo),
+                    None => String::new(),
+                };
+                self.advertir(
+                    diagnostico::SEGUN_NO_EXHAUSTIVO,
+                    pos,
+                    format!("El segun no cubre todos los valores posibles{}; agrega 'otro =>'", tipo),
+                );
+            }
+        }
+    }
+
+    /// Valores del tipo que no cubre ningún patrón, o `None` si el tipo no
+    /// tiene una cantidad conocida de valores y ningún patrón los cubre todos.
+    fn faltantes(&self, tipo: Option<&Tipo>, patrones: &[&Patron]) -> Option<Vec<String>> {
+        if patrones.iter().any(|patron| self.irrefutable(patron, tipo)) {
+            return Some(Vec::new());
+        }
+        match tipo?.resuelto() {
+            Tipo::Bool => {
+                let faltantes = [(true, "verdadero"), (false, "falso")]
+                    .into_iter()
+                    .filter(|(valor, _)| {
+                        !patrones.iter().any(|patron| {
+                            matches!(patron, Patron::Literal(Expresion { nodo: NodoExpresion::Bool(b), .. }) if b == valor)
+                        })
+                    })
+                    .map(|(_, nombre)| nombre.to_string())
+                    .collect();
+                Some(faltantes)
+            }
+            Tipo::Opcional(interior) => {
+                let mut faltantes = self.faltantes(Some(interior), patrones)?;
+                let cubre_nulo = patrones
+                    .iter()
+                    .any(|patron| matches!(patron, Patron::Literal(Expresion { nodo: NodoExpresion::Nulo, .. })));
+                if !cubre_nulo {
+                    faltantes.insert(0, "nulo".to_string());
+                }
+                Some(faltantes)
+            }
+            Tipo::Nombre(nombre) => {
+                let variantes = self.enumeraciones.get(nombre)?;
+                let faltantes = variantes
+                    .iter()
+                    .filter(|variante| !patrones.iter().any(|patron| self.cubre_variante(patron, nombre, variante)))
+                    .map(|variante| format!("{}.{}", nombre, variante.nombre))
+                    .collect();
+                Some(faltantes)
+            }
+            _ => None,
+        }
+    }
+
+    /// Indica si el patrón coincide con cualquier valor del tipo.
+    fn irrefutable(&self, patron: &Patron, tipo: Option<&Tipo>) -> bool {
+        match patron {
+            Patron::Comodin => true,
+            Patron::Nombre(nombre) if self.tipo_definido(nombre) => {
+                tipo.is_some_and(|tipo| *tipo == Tipo::Nombre(nombre.clone()))
+            }
+            Patron::Nombre(_) => true,
+            Patron::Tipo { tipo: esperado, .. } => tipo.is_some_and(|tipo| tipo == esperado),
+            _ => false,
+        }
+    }
+
+    fn cubre_variante(&self, patron: &Patron, enumeracion: &str, variante: &Variante) -> bool {
+        match patron {
+            Patron::Variante { enumeracion: nombre, variante: cubierta, datos }
+                if nombre == enumeracion && *cubierta == variante.nombre =>
+            {
+                match datos {
+                    None => true,
+                    Some(patrones) => patrones
+                        .iter()
+                        .zip(&variante.datos)
+                        .all(|(patron, tipo)| self.irrefutable(patron, Some(tipo))),
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Comprueba el tipo escrito de una variable de `para ... en` contra el
+    /// de los valores que recibe, y la declara.
+    fn variable_bucle(&mut self, variable: &VariableBucle, valor: Option<Tipo>, iterable: &Expresion, pos: Posicion) {
+        if let Some(tipo) = &variable.tipo {
+            self.tipo_valido(tipo, pos);
+            if let Some(valor) = &valor {
+                if !asignable(tipo, valor) {
+                    self.error(
+                        diagnostico::TIPOS_INCOMPATIBLES,
+                        iterable.pos,
+                        format!(
+                            "No se puede asignar un valor de tipo {} a la variable '{}' de tipo {}",
+                            valor, variable.nombre, tipo
+                        ),
+                    );
+                }
+            }
+        }
+        let enlace = Enlace { tipo: variable.tipo.clone().or(valor), mutable: variable.mutable, declarada_en: Some(pos) };
+        self.declarar(&variable.nombre, enlace, pos);
+    }
+
+    /// Declara la variable en el bloque actual, como `Entorno::declarar`.
+    fn declarar(&mut self, nombre: &str, enlace: Enlace, pos: Posicion) {
+        if !self.declaradas.insert(nombre.to_string()) {
+            let anterior = self.variables.get(nombre).and_then(|enlace| enlace.declarada_en);
+            self.errores.push(entorno::redeclarada(nombre, anterior).con_posicion(pos));
+        }
+        self.variables.insert(nombre.to_string(), enlace);
+    }
+
+    /// Tipo declarado de una variable; informa si no existe.
+    fn variable(&mut self, nombre: &str, pos: Posicion) -> Option<Tipo> {
+        match self.variables.get(nombre) {
+            Some(enlace) => enlace.tipo.clone(),
+            None => {
+                self.error(diagnostico::VARIABLE_NO_ENCONTRADA, pos, format!("Variable '{}' no encontrada", nombre));
+                None
+            }
+        }
+    }
+
+    /// Comprueba que el valor se pueda guardar en `ruta`, de tipo `destino`:
+    /// con `asignable` al declararla y con `compatible`, que no convierte, al
+    /// reasignarla. Un literal de lista asignado a una `lista<T>` se comprueba
+    /// elemento a elemento para señalar exactamente el que no es de tipo `T`.
+    fn valor_asignado(&mut self, destino: &Tipo, valor: &Expresion, ruta: &str, acepta: fn(&Tipo, &Tipo) -> bool) {
+        if let (Tipo::Lista(Some(tipo_elemento)), NodoExpresion::Lista(elementos)) = (destino.resuelto(), &valor.nodo) {
+            for (i, elemento) in elementos.iter().enumerate() {
+                self.valor_asignado(tipo_elemento, elemento, &format!("{}[{}]", ruta, i), acepta);
+            }
+            return;
+        }
+        if let Some(obtenido) = self.expresion(valor) {
+            if !acepta(destino, &obtenido) {
+                self.error(
+                    codigo_incompatible(&obtenido),
+                    valor.pos,
+                    no_asignable(&obtenido.to_string(), ruta, destino),
+                );
+            }
+        }
+    }
+
+    /// Tipo de una declaración con `var`, igual al que infiere el intérprete:
+    /// el declarado de la variable o el de retorno de la función si el valor
+    /// es una de ellas, y si no el del valor. Un valor que puede ser nulo
+    /// solo tiene un tipo fijo en el primer caso.
+    fn inferir(&mut self, nombre: &str, valor: &Expresion) -> Option<Tipo> {
+        let tipo = self.expresion(valor)?;
+        let estatico = match &valor.nodo {
+            NodoExpresion::Variable(_) => true,
+            NodoExpresion::Llamada { funcion, .. } => {
+                matches!(&funcion.nodo, NodoExpresion::Variable(funcion) if self.funciones.contains_key(funcion))
+            }
+            _ => false,
+        };
+        let motivo = match tipo.resuelto() {
+            Tipo::Nulo => format!("a partir de nulo; escribe el tipo, como 'cadena? {} = nulo'", nombre),
+            Tipo::Vacio => "porque la expresión no produce ningún valor".to_string(),
+            Tipo::Opcional(interno) if !estatico => format!(
+                "porque el valor puede ser {} o nulo; escribe el tipo, como '{} {}'",
+                interno, tipo, nombre
+            ),
+            _ => return Some(tipo),
+        };
+        self.error(
+            diagnostico::TIPO_NO_INFERIBLE,
+            valor.pos,
+            format!("No se puede inferir el tipo de '{}' {}", nombre, motivo),
+        );
+        None
+    }
+
+    /// Informa si la variable existe pero se declaró sin `mut`.
+    fn comprobar_mutable(&mut self, nombre: &str, pos: Posicion) {
+        let declarada_en = match self.variables.get(nombre) {
+            Some(enlace) if !enlace.mutable => enlace.declarada_en,
+            _ => return,
+        };
+        let mut error = Diagnostico::en(
+            diagnostico::VARIABLE_INMUTABLE,
+            pos,
+            format!("No se puede modificar la variable inmutable '{}'", nombre),
+        );
+        if let Some(declarada_en) = declarada_en {
+            error = error.con_nota(declarada_en, format!("'{}' se declaró aquí sin 'mut'", nombre));
+        }
+        self.errores.push(error);
+    }
+
+    /// Modificar un elemento o una propiedad también modifica la variable que
+    /// lo contiene, así que esta debe ser mutable.
+    fn comprobar_raiz_mutable(&mut self, objeto: &Expresion, pos: Posicion) {
+        if let Some(raiz) = objeto.variable_raiz() {
+            self.comprobar_mutable(raiz, pos);
+        }
+    }
+
+    fn condicion(&mut self, condicion: &Expresion) {
+        match self.expresion(condicion) {
+            Some(otro) if otro != Tipo::Bool => self.error(
+                diagnostico::TIPOS_INCOMPATIBLES,
+                condicion.pos,
+                format!("La condición debe ser bool, se obtuvo {}", otro),
+            ),
+            _ => {}
+        }
+    }
+
+    fn retornar(&mut self, valor: Option<&Expresion>, pos: Posicion) {
+        let obtenido = valor.and_then(|valor| self.expresion(valor));
+        // El analizador sintáctico ya rechaza `retornar` fuera de una función
+        let (funcion, esperado) = match &self.funcion_actual {
+            Some((funcion, esperado)) => (funcion.clone(), esperado.clone()),
+            None => return,
+        };
+        match (valor, esperado.resuelto()) {
+            (Some(valor), Tipo::Vacio) => self.error(
+                diagnostico::TIPOS_INCOMPATIBLES,
+                valor.pos,
+                format!("La función '{}' es de tipo vacio y no puede retornar un valor", funcion),
+            ),
+            (Some(valor), _) => {
+                if let Some(obtenido) = obtenido {
+                    if !compatible(&esperado, &obtenido) {
+                        self.error(
+                            codigo_incompatible(&obtenido),
+                            valor.pos,
+                            format!("La función '{}' debe retornar {}, se obtuvo {}", funcion, esperado, obtenido),
+                        );
+                    }
+                }
+            }
+            (None, Tipo::Vacio) => {}
+            (None, _) => self.error(
+                diagnostico::TIPOS_INCOMPATIBLES,
+                pos,
+                format!("La función '{}' debe retornar un valor de tipo {}", funcion, esperado),
+            ),
+        }
+    }
+
+    // ---------------------------------------------------------------
+    // Expresiones
+    // ---------------------------------------------------------------
+
+    fn expresion(&mut self, expresion: &Expresion) -> Option<Tipo> {
+        let pos = expresion.pos;
+        match &expresion.nodo {
+            NodoExpresion::Entero(_) => Some(Tipo::Entero),
+            NodoExpresion::Numero(_) => Some(Tipo::Numero),
+            NodoExpresion::Cadena(_) => Some(Tipo::Cadena),
+            NodoExpresion::Interpolacion(partes) => {
+                for parte in partes {
+                    if let ParteCadena::Expresion(expresion) = parte {
+                        self.expresion(expresion);
+                    }
+                }
+                Some(Tipo::Cadena)
+            }
+            NodoExpresion::Bool(_) => Some(Tipo::Bool),
+            NodoExpresion::Nulo => Some(Tipo::Nulo),
+            NodoExpresion::Variable(nombre) => self.variable(nombre, pos),
+            NodoExpresion::Lista(elementos) => {
+                let tipos: Vec<Option<Tipo>> = elementos.iter().map(|e| self.expresion(e)).collect();
+                // Si todos los elementos tienen el mismo tipo conocido, es el de la lista
+                let elemento = match tipos.first() {
+                    Some(Some(primero)) if tipos.iter().all(|t| t.as_ref() == Some(primero)) => {
+                        Some(Box::new(primero.clone()))
+                    }
+                    _ => None,
+                };
+                Some(Tipo::Lista(elemento))
+            }
+            NodoExpresion::Jsn(campos) => {
+                for (_, valor) in campos {
+                    self.expresion(valor);
+                }
+                Some(Tipo::Jsn)
+            }
+            NodoExpresion::Unaria { operador, operando } => {
+                let tipo = self.expresion(operando)?;
+                match (operador, tipo.resuelto()) {
+                    (OperadorUnario::Negacion, Tipo::Bool) => Some(Tipo::Bool),
+                    (OperadorUnario::Negacion, _) => {
+                        self.error(diagnostico::TIPOS_INCOMPATIBLES, pos, "Operando de negación no es booleano");
+                        Some(Tipo::Bool)
+                    }
+                    (OperadorUnario::Negativo, _) if es_numerico(&tipo) => Some(tipo),
+                    (OperadorUnario::Negativo, _) => {
+                        self.error(
+                            diagnostico::TIPOS_INCOMPATIBLES,
+                            pos,
+                            format!("No se puede negar un valor de tipo {}", tipo),
+                        );
+                        None
+                    }
+                }
+            }
+            NodoExpresion::Binaria { operador: OperadorBinario::Y | OperadorBinario::O, izquierda, derecha } => {
+                self.operando_logico(izquierda, "izquierdo");
+                self.operando_logico(derecha, "derecho");
+                Some(Tipo::Bool)
+            }
+            NodoExpresion::Binaria { operador: OperadorBinario::Coalescencia, izquierda, derecha } => {
+                let izq = self.expresion(izquierda);
+                let der = self.expresion(derecha);
+                match (sin_alias_opcional(izq?), der.map(sin_alias_opcional)) {
+                    (Tipo::Nulo, der) => der,
+                    (Tipo::Opcional(tipo), Some(der)) => match der {
+                        Tipo::Nulo => Some(Tipo::Opcional(tipo)),
+                        Tipo::Opcional(der) if compatible(&tipo, &der) => Some(Tipo::Opcional(tipo)),
+                        der if compatible(&tipo, &der) => Some(*tipo),
+                        _ => None,
+                    },
+                    (Tipo::Opcional(_), None) => None,
+                    // Un valor que no es opcional nunca es nulo
+                    (izq, _) => Some(izq),
+                }
+            }
+            NodoExpresion::Binaria { operador, izquierda, derecha } => {
+                let izq = self.expresion(izquierda);
+                let der = self.expresion(derecha);
+                // Un divisor que es el literal cero falla siempre, aunque
+                // dentro de `intentar` el error se puede atrapar
+                let divisor_cero = match derecha.nodo {
+                    NodoExpresion::Entero(n) => n == 0,
+                    NodoExpresion::Numero(n) => n == 0.0,
+                    _ => false,
+                };
+                if divisor_cero && matches!(operador, OperadorBinario::Division | OperadorBinario::Modulo) {
+                    if self.intentos > 0 {
+                        self.advertir(diagnostico::DIVISION_POR_CERO, pos, "División por cero; siempre lanza una excepción DivisionPorCero");
+                    } else {
+                        self.error(diagnostico::DIVISION_POR_CERO, pos, "División por cero");
+                    }
+                }
+                self.binaria(*operador, izq, der, pos)
+            }
+            NodoExpresion::Ternaria { condicion, si_verdadero, si_falso } => {
+                self.condicion(condicion);
+                let verdadero = self.expresion(si_verdadero);
+                let falso = self.expresion(si_falso);
+                match (verdadero, falso) {
+                    (verdadero, falso) if verdadero == falso => verdadero,
+                    (Some(Tipo::Nulo), Some(tipo)) | (Some(tipo), Some(Tipo::Nulo)) => match tipo.resuelto() {
+                        Tipo::Opcional(_) => Some(tipo),
+                        _ => Some(Tipo::Opcional(Box::new(tipo))),
+                    },
+                    _ => None,
+                }
+            }
+            NodoExpresion::Llamada { funcion, argumentos } => self.llamada(funcion, argumentos, pos),
+            NodoExpresion::Miembro { objeto, nombre, opcional } => {
+                if let Some(enumeracion) = self.enumeracion_nombrada(objeto) {
+                    return self.variante(&enumeracion, nombre, &[], &[], pos);
+                }
+                let tipo = self.expresion(objeto)?;
+                let receptor = self.receptor(tipo, *opcional, nombre, pos)?;
+                let con_propiedades = match receptor.resuelto() {
+                    Tipo::Jsn => true,
+                    Tipo::Nombre(nombre) => !self.enumeraciones.contains_key(nombre),
+                    _ => false,
+                };
+                if !con_propiedades {
+                    self.error(
+                        diagnostico::MIEMBRO_NO_ENCONTRADO,
+                        pos,
+                        format!("Un valor de tipo {} no tiene la propiedad '{}'", receptor, nombre),
+                    );
+                }
+                None
+            }
+            NodoExpresion::Indice { objeto, indice } => {
+                let tipo_objeto = self.expresion(objeto);
+                let tipo_indice = self.expresion(indice);
+                let tipo_objeto = tipo_objeto?;
+                let esperado = match tipo_objeto.resuelto() {
+                    Tipo::Lista(_) | Tipo::Cadena => Tipo::Entero,
+                    Tipo::Jsn => Tipo::Cadena,
+                    _ => {
+                        self.error(
+                            diagnostico::TIPOS_INCOMPATIBLES,
+                            pos,
+                            format!("No se puede indexar un valor de tipo {}", tipo_objeto),
+                        );
+                        return None;
+                    }
+                };
+                if let Some(tipo_indice) = tipo_indice {
+                    if tipo_indice != esperado {
+                        self.error(
+                            diagnostico::TIPOS_INCOMPATIBLES,
+                            pos,
+                            format!("No se puede indexar un valor de tipo {} con {}", tipo_objeto, tipo_indice),
+                        );
+                    }
+                }
+                match tipo_objeto.resuelto() {
+                    Tipo::Lista(elemento) => elemento.as_deref().cloned(),
+                    Tipo::Cadena => Some(Tipo::Cadena),
+                    _ => None,
+                }
+            }
+            NodoExpresion::Nuevo { tipo, argumentos } => {
+                for argumento in argumentos {
+                    self.expresion(argumento);
+                }
+                if !self.objetos.contains(tipo) {
+                    self.error(diagnostico::TIPO_DESCONOCIDO, pos, format!("Objeto '{}' no definido", tipo));
+                    return None;
+                }
+                Some(Tipo::Nombre(tipo.clone()))
+            }
+            NodoExpresion::Rango { desde, hasta, paso, .. } => {
+                for limite in [Some(desde), Some(hasta), paso.as_ref()].into_iter().flatten() {
+                    if let Some(tipo) = self.expresion(limite) {
+                        if *tipo.resuelto() != Tipo::Entero {
+                            self.error(
+                                diagnostico::TIPOS_INCOMPATIBLES,
+                                limite.pos,
+                                format!("Los límites y el paso de un rango deben ser enteros, se obtuvo {}", tipo),
+                            );
+                        }
+                    }
+                }
+                Some(Tipo::Lista(Some(Box::new(Tipo::Entero))))
+            }
+        }
+    }
+
+    fn operando_logico(&mut self, expresion: &Expresion, lado: &str) {
+        match self.expresion(expresion) {
+            Some(tipo) if tipo != Tipo::Bool => self.error(
+                diagnostico::TIPOS_INCOMPATIBLES,
+                expresion.pos,
+                format!("Operando {} no es booleano", lado),
+            ),
+            _ => {}
+        }
+    }
+
+    /// Mismas reglas que `evaluar_operacion_binaria` en el intérprete.
+    fn binaria(&mut self, operador: OperadorBinario, izq: Option<Tipo>, der: Option<Tipo>, pos: Posicion) -> Option<Tipo> {
+        use OperadorBinario::*;
+
+        match operador {
+            Suma if izq == Some(Tipo::Cadena) || der == Some(Tipo::Cadena) => Some(Tipo::Cadena),
+            Suma | Resta | Multiplicacion | Division | Modulo => match (izq?, der?) {
+                (a, b) if a == Tipo::Entero && b == Tipo::Entero => Some(Tipo::Entero),
+                (a, b) if es_numerico(&a) && es_numerico(&b) => Some(Tipo::Numero),
+                (a, b) => {
+                    let codigo = match codigo_incompatible(&a) {
+                        diagnostico::VALOR_NULO => diagnostico::VALOR_NULO,
+                        _ => codigo_incompatible(&b),
+                    };
+                    self.error(
+                        codigo,
+                        pos,
+                        format!("Tipos incompatibles para '{}': {} y {}", operador, a, b),
+                    );
+                    None
+                }
+            },
+            _ => {
+                if let (Some(a), Some(b)) = (izq, der) {
+                    let igualdad = matches!(operador, Igual | Distinto);
+                    // `==` y `!=` aceptan nulo y valores opcionales
+                    let (a, b) = match (sin_alias_opcional(a), sin_alias_opcional(b)) {
+                        (Tipo::Nulo, _) | (_, Tipo::Nulo) if igualdad => return Some(Tipo::Bool),
+                        (Tipo::Opcional(a), b) | (b, Tipo::Opcional(a)) if igualdad => (*a, sin_opcional(b)),
+                        (a, b) => (a, b),
+                    };
+                    let comparables = (es_numerico(&a) && es_numerico(&b))
+                        || (igualdad && a == b && self.comparable_por_igualdad(&a));
+                    if !comparables {
+                        let codigo = match codigo_incompatible(&a) {
+                            diagnostico::VALOR_NULO => diagnostico::VALOR_NULO,
+                            _ => codigo_incompatible(&b),
+                        };
+                        self.error(
+                            codigo,
+                            pos,
+                            format!("Tipos incompatibles para '{}': {} y {}", operador, a, b),
+                        );
+                    }
+                }
+                Some(Tipo::Bool)
+            }
+        }
+    }
+
+    /// Tipos cuyos valores se comparan con `==` y `!=`.
+    fn comparable_por_igualdad(&self, tipo: &Tipo) -> bool {
+        match tipo.resuelto() {
+            Tipo::Cadena | Tipo::Bool => true,
+            Tipo::Nombre(nombre) => self.enumeraciones.contains_key(nombre),
+            _ => false,
+        }
+    }
+
+    /// Nombre de la enumeración en `Estado.Pendiente` o `Estado.Pagado(10)`,
+    /// si no hay una variable con ese nombre.
+    fn enumeracion_nombrada(&self, objeto: &Expresion) -> Option<String> {
+        match &objeto.nodo {
+            NodoExpresion::Variable(nombre)
+                if !self.variables.contains_key(nombre) && self.enumeraciones.contains_key(nombre) =>
+            {
+                Some(nombre.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Comprueba la construcción de una variante con sus datos.
+    fn variante(&mut self, enumeracion: &str, variante: &str, argumentos: &[Expresion], tipos: &[Option<Tipo>], pos: Posicion) -> Option<Tipo> {
+        let tipo = Some(Tipo::Nombre(enumeracion.to_string()));
+        let datos = match self.enumeraciones[enumeracion].iter().find(|v| v.nombre == variante) {
+            Some(declarada) => declarada.datos.clone(),
+            None => {
+                self.error(
+                    diagnostico::MIEMBRO_NO_ENCONTRADO,
+                    pos,
+                    format!("La enumeración '{}' no tiene la variante '{}'", enumeracion, variante),
+                );
+                return tipo;
+            }
+        };
+        if argumentos.len() != datos.len() {
+            self.error(
+                diagnostico::NUMERO_DE_ARGUMENTOS,
+                pos,
+                format!(
+                    "La variante '{}.{}' espera {} datos, pero se proporcionaron {}",
+                    enumeracion,
+                    variante,
+                    datos.len(),
+                    argumentos.len()
+                ),
+            );
+        }
+        for (i, ((esperado, argumento), obtenido)) in datos.iter().zip(argumentos).zip(tipos).enumerate() {
+            if let Some(obtenido) = obtenido {
+                if !compatible(esperado, obtenido) {
+                    self.error(
+                        codigo_incompatible(obtenido),
+                        argumento.pos,
+                        format!(
+                            "El dato {} de '{}.{}' es de tipo {}, pero se pasó un valor de tipo {}",
+                            i + 1,
+                            enumeracion,
+                            variante,
+                            esperado,
+                            obtenido
+                        ),
+                    );
+                }
+            }
+        }
+        tipo
+    }
+
+    fn llamada(&mut self, funcion: &Expresion, argumentos: &[Expresion], pos: Posicion) -> Option<Tipo> {
+        match &funcion.nodo {
+            NodoExpresion::Miembro { objeto, nombre, opcional } => {
+                self.metodo(objeto, nombre, *opcional, argumentos, pos)
+            }
+            NodoExpresion::Variable(nombre) => {
+                let tipos: Vec<Option<Tipo>> = argumentos.iter().map(|a| self.expresion(a)).collect();
+                self.llamada_funcion(nombre, argumentos, &tipos, pos)
+            }
+            _ => {
+                self.error(
+                    diagnostico::TIPOS_INCOMPATIBLES,
+                    pos,
+                    format!("La expresión '{}' no se puede llamar", funcion),
+                );
+                None
+            }
+        }
+    }
+
+    fn llamada_funcion(&mut self, nombre: &str, argumentos: &[Expresion], tipos: &[Option<Tipo>], pos: Posicion) -> Option<Tipo> {
+        if funcion_impresion(nombre).is_some() {
+            if argumentos.len() > 1 {
+                self.error(
+                    diagnostico::NUMERO_DE_ARGUMENTOS,
+                    pos,
+                    "Las funciones de impresión reciben un solo argumento",
+                );
+            }
+            return Some(Tipo::Vacio);
+        }
+
+        if let Some(firma) = self.funciones.get(nombre) {
+            let parametros = firma.parametros.clone();
+            let tipo_retorno = firma.tipo_retorno.clone();
+            if argumentos.len() != parametros.len() {
+                self.error(
+                    diagnostico::NUMERO_DE_ARGUMENTOS,
+                    pos,
+                    format!(
+                        "Función '{}' espera {} argumentos, pero se proporcionaron {}",
+                        nombre,
+                        parametros.len(),
+                        argumentos.len()
+                    ),
+                );
+            }
+            for ((parametro, argumento), tipo) in parametros.iter().zip(argumentos).zip(tipos) {
+                if let Some(tipo) = tipo {
+                    if !compatible(&parametro.tipo, tipo) {
+                        self.error(
+                            codigo_incompatible(tipo),
+                            argumento.pos,
+                            format!(
+                                "El parámetro '{}' de '{}' es de tipo {}, pero se pasó un valor de tipo {}",
+                                parametro.nombre, nombre, parametro.tipo, tipo
+                            ),
+                        );
+                    }
+                }
+            }
+            return Some(tipo_retorno);
+        }
+
+        match nombre {
+            "sumar" => match tipos.iter().map(|t| t.as_ref().map(Tipo::resuelto)).collect::<Vec<_>>()[..] {
+                [Some(Tipo::Entero), Some(Tipo::Entero)] => Some(Tipo::Entero),
+                [Some(Tipo::Numero), Some(Tipo::Numero)] => Some(Tipo::Numero),
+                [None, _] | [_, None] => None,
+                _ => {
+                    self.error(diagnostico::TIPOS_INCOMPATIBLES, pos, "Tipos incompatibles para suma");
+                    None
+                }
+            },
+            "saludar" => Some(Tipo::Cadena),
+            "suma_envolvente" | "resta_envolvente" | "producto_envolvente" | "suma_saturada" | "resta_saturada"
+            | "producto_saturado" => {
+                let enteros = tipos.len() == 2 && tipos.iter().all(|t| matches!(t.as_ref().map(Tipo::resuelto), Some(Tipo::Entero) | None));
+                if !enteros {
+                    self.error(diagnostico::TIPOS_INCOMPATIBLES, pos, format!("{} espera dos enteros", nombre));
+                }
+                Some(Tipo::Entero)
+            }
+            "calcular_promedio" => {
+                match tipos.first().map(|t| t.as_ref().map(Tipo::resuelto)) {
+                    Some(Some(Tipo::Lista(_))) | Some(None) => {}
+                    _ => self.error(diagnostico::TIPOS_INCOMPATIBLES, pos, "calcular_promedio espera una lista"),
+                }
+                Some(Tipo::Numero)
+            }
+            _ => {
+                self.error(
+                    diagnostico::FUNCION_NO_ENCONTRADA,
+                    pos,
+                    format!("Función '{}' no reconocida", nombre),
+                );
+                None
+            }
+        }
+    }
+
+    /// Tipo sobre el que se busca una propiedad o un método. Un valor
+    /// opcional solo se puede usar con `?.`; si es nulo no hay nada que
+    /// comprobar.
+    fn receptor(&mut self, tipo: Tipo, opcional: bool, nombre: &str, pos: Posicion) -> Option<Tipo> {
+        match tipo.resuelto() {
+            Tipo::Opcional(interior) if opcional => Some((**interior).clone()),
+            Tipo::Nulo if opcional => None,
+            Tipo::Opcional(_) | Tipo::Nulo => {
+                self.error(
+                    diagnostico::VALOR_NULO,
+                    pos,
+                    format!("Un valor de tipo {} puede ser nulo; usa '?.' para acceder a '{}'", tipo, nombre),
+                );
+                None
+            }
+            _ => Some(tipo),
+        }
+    }
+
+    fn metodo(&mut self, objeto: &Expresion, metodo: &str, opcional: bool, argumentos: &[Expresion], pos: Posicion) -> Option<Tipo> {
+        let tipos: Vec<Option<Tipo>> = argumentos.iter().map(|a| self.expresion(a)).collect();
+
+        if let Some(enumeracion) = self.enumeracion_nombrada(objeto) {
+            return self.variante(&enumeracion, metodo, argumentos, &tipos, pos);
+        }
+
+        // Método invocado sobre el nombre de un objeto y no sobre una instancia
+        if let NodoExpresion::Variable(nombre) = &objeto.nodo {
+            if !self.variables.contains_key(nombre) && self.objetos.contains(nombre) {
+                return None;
+            }
+        }
+
+        let Some(tipo) = self.expresion(objeto) else {
+            // Con un tipo desconocido, como el de una propiedad de un jsn,
+            // `agregar` solo puede ser el de una lista
+            if metodo == "agregar" {
+                self.comprobar_raiz_mutable(objeto, pos);
+            }
+            return None;
+        };
+        let puede_ser_nulo = matches!(tipo.resuelto(), Tipo::Opcional(_));
+        let receptor = self.receptor(tipo, opcional, metodo, pos)?;
+        if let (Tipo::Lista(elemento), "agregar") = (receptor.resuelto(), metodo) {
+            self.comprobar_raiz_mutable(objeto, pos);
+            if let (Some(elemento), Some(Some(obtenido))) = (elemento, tipos.first()) {
+                if !asignable(elemento, obtenido) {
+                    self.error(
+                        codigo_incompatible(obtenido),
+                        argumentos[0].pos,
+                        format!("No se puede agregar un valor de tipo {} a la lista '{}' de tipo {}", obtenido, objeto, receptor),
+                    );
+                }
+            }
+        }
+        let resultado = match receptor.resuelto() {
+            Tipo::Nombre(nombre) if self.enumeraciones.contains_key(nombre) => match metodo {
+                "cadena" => Some(Some(Tipo::Cadena)),
+                "jsn" => Some(Some(Tipo::Jsn)),
+                _ => None,
+            },
+            _ => tipo_metodo(&receptor, metodo),
+        };
+        match resultado {
+            // Con `?.` sobre un valor opcional el resultado también puede ser nulo
+            Some(Some(tipo)) if puede_ser_nulo && tipo != Tipo::Vacio => Some(Tipo::Opcional(Box::new(tipo))),
+            Some(tipo) => tipo,
+            None => {
+                self.error(
+                    diagnostico::MIEMBRO_NO_ENCONTRADO,
+                    pos,
+                    format!("Método '{}' no disponible para {}", metodo, receptor),
+                );
+                None
+            }
+        }
+    }
+}
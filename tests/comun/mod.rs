@@ -0,0 +1,88 @@
+// Utilidades compartidas por las pruebas: ejecutan el intérprete sobre un
+// programa escrito en la propia prueba y recogen lo que imprime. Cada archivo
+// de pruebas usa solo algunas.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub struct Salida {
+    pub exito: bool,
+    pub salida: String,
+    pub errores: String,
+}
+
+impl Salida {
+    /// Códigos de los errores mostrados, como `Q0301`, en orden.
+    pub fn codigos(&self) -> Vec<&str> {
+        codigos(&self.errores, "Error[")
+    }
+
+    /// Códigos de las advertencias mostradas.
+    pub fn advertencias(&self) -> Vec<&str> {
+        codigos(&self.errores, "Advertencia[")
+    }
+
+    /// Líneas impresas por el programa.
+    pub fn lineas(&self) -> Vec<&str> {
+        self.salida.lines().collect()
+    }
+}
+
+fn codigos<'a>(texto: &'a str, prefijo: &str) -> Vec<&'a str> {
+    texto
+        .lines()
+        .filter_map(|linea| linea.strip_prefix(prefijo))
+        .filter_map(|resto| resto.split(']').next())
+        .collect()
+}
+
+/// Ejecuta el programa como `quetzal programa.qz`, con la verificación de
+/// tipos previa.
+pub fn ejecutar(programa: &str) -> Salida {
+    quetzal_con(&[], programa)
+}
+
+/// Ejecuta el programa con `--sin-verificar`, para comprobar los errores que
+/// detecta el intérprete por sí mismo.
+pub fn ejecutar_sin_verificar(programa: &str) -> Salida {
+    quetzal_con(&["--sin-verificar"], programa)
+}
+
+/// Verifica el programa con `quetzal verificar` sin ejecutarlo.
+pub fn verificar(programa: &str) -> Salida {
+    quetzal_con(&["verificar"], programa)
+}
+
+/// Ejecuta `quetzal` con las opciones indicadas antes del archivo.
+pub fn quetzal_con(opciones: &[&str], programa: &str) -> Salida {
+    let archivo = archivo_temporal(programa);
+    let mut argumentos = opciones.to_vec();
+    argumentos.push(archivo.to_str().expect("ruta temporal no válida"));
+    let salida = quetzal(&argumentos);
+    let _ = fs::remove_file(&archivo);
+    salida
+}
+
+/// Ejecuta `quetzal` con estos argumentos, sin ningún archivo.
+pub fn quetzal(argumentos: &[&str]) -> Salida {
+    let resultado = Command::new(env!("CARGO_BIN_EXE_quetzal"))
+        .args(argumentos)
+        .output()
+        .expect("no se pudo ejecutar quetzal");
+    Salida {
+        exito: resultado.status.success(),
+        salida: String::from_utf8_lossy(&resultado.stdout).into_owned(),
+        errores: String::from_utf8_lossy(&resultado.stderr).into_owned(),
+    }
+}
+
+fn archivo_temporal(programa: &str) -> PathBuf {
+    static SIGUIENTE: AtomicUsize = AtomicUsize::new(0);
+    let numero = SIGUIENTE.fetch_add(1, Ordering::Relaxed);
+    let archivo = std::env::temp_dir().join(format!("quetzal-prueba-{}-{}.qz", std::process::id(), numero));
+    fs::write(&archivo, programa).expect("no se pudo escribir el programa de prueba");
+    archivo
+}
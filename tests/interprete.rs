@@ -0,0 +1,581 @@
+// Comportamiento del intérprete: cada prueba ejecuta un programa y compara
+// lo que imprime. Los errores que el verificador detectaría antes se
+// comprueban con `--sin-verificar`, para que los encuentre el intérprete.
+
+mod comun;
+
+use comun::{ejecutar, ejecutar_sin_verificar};
+
+/// Ejecuta el programa, que debe terminar sin errores, y devuelve lo que
+/// imprimió.
+fn imprime(programa: &str) -> String {
+    let salida = ejecutar(programa);
+    assert!(salida.exito, "{}", salida.errores);
+    salida.salida
+}
+
+/// Ejecuta el programa sin verificar y comprueba el código del error con el
+/// que termina.
+fn falla_con(programa: &str, codigo: &str) {
+    let salida = ejecutar_sin_verificar(programa);
+    assert!(!salida.exito, "se esperaba el error {} en:\n{}", codigo, programa);
+    assert_eq!(salida.codigos(), [codigo], "{}", salida.errores);
+}
+
+#[test]
+fn nombres_que_empiezan_como_palabras_clave() {
+    let programa = r#"
+entero sigma = 1
+entero parametro = 2
+cadena s = "a = b"
+imprimir(sigma)
+imprimir(parametro)
+imprimir(s)
+si (sigma == 1) {
+    imprimir("si")
+}
+"#;
+    assert_eq!(imprime(programa), "1\n2\na = b\nsi\n");
+
+    let salida = ejecutar("entero x = @\n");
+    assert!(!salida.exito);
+    assert_eq!(salida.codigos(), ["Q0101"]);
+    assert!(salida.errores.contains(".qz:1:12"), "{}", salida.errores);
+}
+
+#[test]
+fn precedencia_de_operadores() {
+    let programa = r#"
+imprimir(1 + 2 * 3)
+imprimir((1 + 2) * 3)
+imprimir(10 - 4 - 3)
+imprimir(-2 * 3)
+imprimir(!falso == falso)
+imprimir(2 > 1 && 1 > 2 || verdadero)
+imprimir(verdadero ? "a:b" : "c")
+"#;
+    assert_eq!(imprime(programa), "7\n9\n3\n-6\nfalso\nverdadero\na:b\n");
+}
+
+#[test]
+fn llamadas_en_expresiones() {
+    let programa = r#"
+entero doble(entero n) {
+    retornar n * 2
+}
+bool es_par(entero n) {
+    retornar n % 2 == 0
+}
+cadena unir(cadena a, cadena b) {
+    retornar a + b
+}
+imprimir(doble(doble(2)) + 1)
+entero z = doble(1) + doble(2)
+imprimir(z)
+si (es_par(doble(3))) {
+    imprimir("par")
+}
+lista<entero> l = [doble(1), sumar(1, 2)]
+imprimir(l)
+imprimir(unir("a,b", unir("c", "(")))
+"#;
+    assert_eq!(imprime(programa), "9\n6\npar\n[2, 3]\na,bc(\n");
+}
+
+#[test]
+fn retornar_desde_bucles_anidados() {
+    let programa = "\
+entero buscar(lista<entero> l, entero objetivo) {
+    para (entero mut i = 0; i < 3; i++) {
+        para (x en l) {
+            si (x * i == objetivo) {
+                retornar i
+            }
+        }
+    }
+    retornar -1
+}
+imprimir(buscar([1, 2], 4))
+imprimir(buscar([1], 9))
+entero mut total = 0
+mientras (verdadero) {
+    total = total + 1
+    si (total < 3) {
+        continuar
+    }
+    romper
+}
+imprimir(total)
+";
+    assert_eq!(imprime(programa), "2\n-1\n3\n");
+
+    let salida = ejecutar("romper\n");
+    assert!(!salida.exito);
+    assert!(salida.errores.contains("'romper' solo puede usarse dentro de un bucle"), "{}", salida.errores);
+    let salida = ejecutar("retornar 1\n");
+    assert!(salida.errores.contains("'retornar' solo puede usarse dentro de una función"), "{}", salida.errores);
+}
+
+#[test]
+fn diagnosticos_con_codigo_y_fragmento() {
+    let salida = ejecutar("entero x = 1\nimprimir(x + faltante)\n");
+    assert!(!salida.exito);
+    assert_eq!(salida.codigos(), ["Q0301"]);
+    assert!(salida.errores.contains(".qz:2:14\n"), "{}", salida.errores);
+    assert!(
+        salida.errores.contains("2 | imprimir(x + faltante)\n  |              ^^^^^^^^"),
+        "{}",
+        salida.errores
+    );
+
+    let explicacion = comun::quetzal(&["explicar", "q0101"]);
+    assert!(explicacion.exito);
+    assert!(explicacion.salida.starts_with("Q0101: Carácter inesperado."), "{}", explicacion.salida);
+    assert!(!comun::quetzal(&["explicar", "Q9999"]).exito);
+}
+
+#[test]
+fn todos_los_errores_de_sintaxis_antes_de_ejecutar() {
+    let programa = "\
+imprimir(\"antes\")
+entero = 1
+cadena = \"a\"
+vacio nunca_llamada() {
+    entero = 2
+}
+";
+    let salida = ejecutar(programa);
+    assert!(!salida.exito);
+    assert_eq!(salida.codigos(), ["Q0201", "Q0201", "Q0201"]);
+    assert_eq!(salida.salida, "");
+}
+
+#[test]
+fn escapes_en_cadenas() {
+    let programa = r#"
+imprimir("a\tb \"c\" \\ \u{1F426}")
+imprimir('dijo "hola" y \'adiós\'')
+imprimir("uno\ndos")
+imprimir("a" + "b")
+"#;
+    assert_eq!(imprime(programa), "a\tb \"c\" \\ 🐦\ndijo \"hola\" y 'adiós'\nuno\ndos\nab\n");
+    assert_eq!(ejecutar("cadena s = \"sin cerrar\n").codigos(), ["Q0102"]);
+    assert_eq!(ejecutar("cadena s = \"\\q\"\n").codigos(), ["Q0105"]);
+}
+
+#[test]
+fn interpolacion_en_cadenas() {
+    let programa = r#"
+cadena nombre = "Ana"
+entero edad = 30
+jsn persona = { nombre: "Luis" }
+entero doble(entero n) {
+    retornar n * 2
+}
+cadena saludo(cadena n) {
+    retornar "Hola ${n}"
+}
+imprimir("Hola ${nombre}, tienes ${edad + 1} años")
+imprimir("${persona.nombre} ${doble(2)} ${nombre.cadena()}")
+cadena s = "x${1 + 1}"
+imprimir(s)
+imprimir(saludo("Eva"))
+imprimir("\${literal}")
+"#;
+    assert_eq!(imprime(programa), "Hola Ana, tienes 31 años\nLuis 4 Ana\nx2\nHola Eva\n${literal}\n");
+    assert_eq!(ejecutar("imprimir(\"${}\")\n").codigos(), ["Q0106"]);
+}
+
+#[test]
+fn comentarios_en_cualquier_posicion() {
+    let programa = "\
+/* exterior /* anidado */ sigue */
+entero x = 5 // edad inicial
+entero z = /* en medio */ 2
+cadena url = \"https://ejemplo.com/*no*/\"
+imprimir(x + z)
+imprimir(url)
+";
+    assert_eq!(imprime(programa), "7\nhttps://ejemplo.com/*no*/\n");
+    assert_eq!(ejecutar("/* abierto /* */\nimprimir(1)\n").codigos(), ["Q0103"]);
+}
+
+#[test]
+fn sentencias_en_varias_lineas() {
+    let programa = "\
+entero total = sumar(  // comentario al final
+    1,
+    2
+)
+lista<entero> l = [
+    1, 2,
+    3
+]
+si (total > 0 &&
+    l.longitud() == 3) {
+    imprimir(total +
+        l.longitud())
+}
+";
+    assert_eq!(imprime(programa), "6\n");
+    // Los errores se siguen situando en su propia línea
+    let salida = ejecutar(&format!("{}imprimir(faltante)\n", programa));
+    assert!(salida.errores.contains(".qz:14:10"), "{}", salida.errores);
+}
+
+#[test]
+fn tipos_de_argumentos_y_retorno() {
+    assert_eq!(imprime("número mitad(número x) {\n    retornar x / 2\n}\nimprimir(mitad(5))\n"), "2.5\n");
+    falla_con("entero doble(entero n) {\n    retornar n * 2\n}\ndoble(\"dos\")\n", "Q0304");
+    falla_con("entero f() {\n    retornar \"a\"\n}\nf()\n", "Q0304");
+    falla_con("entero f() {\n}\nf()\n", "Q0304");
+    falla_con("vacio f() {\n    retornar [1]\n}\nf()\n", "Q0304");
+    let salida = ejecutar_sin_verificar("entero doble(entero n) {\n    retornar n * 2\n}\ndoble(\"dos\")\n");
+    assert!(
+        salida.errores.contains("El parámetro 'n' de 'doble' es de tipo entero, pero se pasó un valor de tipo cadena"),
+        "{}",
+        salida.errores
+    );
+    // El error se señala en el `retornar`, no en la llamada
+    let salida = ejecutar_sin_verificar("entero f() {\n    retornar \"a\"\n}\nf()\n");
+    assert!(salida.errores.contains(".qz:2:14"), "{}", salida.errores);
+}
+
+#[test]
+fn reasignacion_con_el_tipo_declarado() {
+    assert_eq!(imprime("número mut n = 1\nn = 2\nimprimir(n + 0.5)\n"), "2.5\n");
+    falla_con("entero mut x = 1\nx = \"dos\"\n", "Q0304");
+    falla_con("entero mut x = 1\nx += \"dos\"\n", "Q0304");
+    falla_con("cadena mut s = \"a\"\ns++\n", "Q0304");
+    falla_con("para (entero e en [\"a\"]) {\n}\n", "Q0304");
+    // Al reasignar no hay conversiones: solo se amplía de entero a número
+    falla_con("entero mut x = 1\nx = 2.9\n", "Q0304");
+    falla_con("entero mut x = 1\nx += 0.6\n", "Q0304");
+    falla_con("cadena mut s = \"a\"\ns = 5\n", "Q0304");
+    falla_con("jsn mut j = {}\nj = 5\n", "Q0304");
+    falla_con("lista<entero> mut l = [1]\nl[0] = 2.5\n", "Q0304");
+    assert_eq!(imprime("lista<número> mut l = [1.5]\nl[0] = 2\nl = [3]\nimprimir(l)\n"), "[3]\n");
+    // Con `var` el tipo declarado es el del valor inicial
+    assert_eq!(imprime("var x = 1\nvar l = [\"a\"]\nimprimir(x)\nimprimir(l)\n"), "1\n[a]\n");
+    falla_con("var mut x = 1\nx = \"uno\"\n", "Q0304");
+}
+
+#[test]
+fn variables_inmutables() {
+    falla_con("entero x = 1\nx = 2\n", "Q0307");
+    falla_con("entero x = 1\nx++\n", "Q0307");
+    falla_con("lista<entero> l = []\nl.agregar(1)\n", "Q0307");
+    falla_con("lista<lista<entero>> m = [[1]]\nm[0].agregar(2)\n", "Q0307");
+    falla_con("jsn p = { items: [1] }\np.items.agregar(2)\n", "Q0307");
+    let salida = ejecutar_sin_verificar("entero x = 1\nx = 2\n");
+    assert!(salida.errores.contains("'x' se declaró aquí sin 'mut'"), "{}", salida.errores);
+}
+
+#[test]
+fn listas_tipadas() {
+    let programa = "\
+lista<número> mut l = [1, 2.5]
+l.agregar(3)
+l[0] = 4
+imprimir(l)
+";
+    assert_eq!(imprime(programa), "[4, 2.5, 3]\n");
+    falla_con("lista<entero> mut l = []\nl.agregar(\"tres\")\n", "Q0304");
+    falla_con("lista<lista<entero>> mut m = [[1]]\nm[0][0] = \"uno\"\n", "Q0304");
+    falla_con("lista<entero> l = [1, \"dos\"]\n", "Q0304");
+}
+
+#[test]
+fn agregar_a_elementos_y_propiedades() {
+    let programa = "\
+lista<lista<número>> mut m = [[1], []]
+m[0].agregar(2)
+m[1].agregar(0.5)
+imprimir(m)
+jsn mut p = { items: [1] }
+p.items.agregar(2)
+imprimir(p.items)
+";
+    assert_eq!(imprime(programa), "[[1, 2], [0.5]]\n[1, 2]\n");
+    falla_con("lista<lista<entero>> mut m = [[1]]\nm[0].agregar(\"dos\")\n", "Q0304");
+}
+
+#[test]
+fn valores_nulos() {
+    let programa = "\
+cadena? apodo = nulo
+imprimir(apodo ?? \"sin apodo\")
+jsn datos = { usuario: { nombre: \"Ana\" } }
+imprimir(datos.usuario?.correo)
+imprimir(datos?.usuario?.nombre)
+";
+    assert_eq!(imprime(programa), "sin apodo\nnulo\nAna\n");
+    falla_con("cadena s = nulo\n", "Q0308");
+}
+
+#[test]
+fn aritmetica_entera_comprobada() {
+    assert_eq!(
+        imprime("entero m = 9223372036854775807\nimprimir(suma_saturada(m, 1))\nimprimir(suma_envolvente(m, 1))\n"),
+        "9223372036854775807\n-9223372036854775808\n"
+    );
+    falla_con("entero m = 9223372036854775807\nimprimir(m + 1)\n", "Q0403");
+    falla_con("entero cero = 0\nimprimir(1 / cero)\n", "Q0401");
+    falla_con("entero cero = 0\nimprimir(1 % cero)\n", "Q0401");
+    falla_con("entero mut x = 1\nentero cero = 0\nx /= cero\n", "Q0401");
+}
+
+#[test]
+fn alias_de_tipo() {
+    assert_eq!(imprime("tipo Dinero = número\nDinero saldo = 10\nimprimir(saldo / 4)\n"), "2.5\n");
+    assert_eq!(imprime("tipo Nombres = lista<cadena>\nNombres n = [\"a\"]\nimprimir(n)\n"), "[a]\n");
+}
+
+#[test]
+fn enumeraciones() {
+    let programa = "\
+enumeracion Estado { Pendiente, Pagado(número) }
+Estado e = Estado.Pagado(250)
+imprimir(e)
+imprimir(e == Estado.Pagado(250))
+imprimir(e == Estado.Pendiente)
+";
+    assert_eq!(imprime(programa), "Estado.Pagado(250)\nverdadero\nfalso\n");
+}
+
+#[test]
+fn jsn_e_instancias_en_orden_de_clave() {
+    let programa = "\
+enumeracion Estado { Pendiente, Pagado(número) }
+objeto Punto {
+    entero z
+    entero x
+}
+jsn d = { c: 3, a: 1, b: { z: 0, m: 2 } }
+imprimir(d)
+imprimir(Estado.Pagado(250).jsn())
+imprimir(nuevo Punto(1, 2))
+";
+    assert_eq!(
+        imprime(programa),
+        "{a: 1, b: {m: 2, z: 0}, c: 3}\n{datos: [250], tipo: Estado, variante: Pagado}\nPunto { x: 2, z: 1 }\n"
+    );
+}
+
+#[test]
+fn segun_con_patrones() {
+    let programa = "\
+vacio describir(jsn dato) {
+    segun (dato.valor) {
+        caso 0 => imprimir(\"cero\")
+        caso 1, 2 => imprimir(\"poco\")
+        caso 3..=9 => imprimir(\"varios\")
+        caso [a, b] => imprimir(\"par \" + a.cadena() + b.cadena())
+        caso { nombre: n } => imprimir(\"hola \" + n)
+        caso n si n > 100 => imprimir(\"mucho\")
+        otro => imprimir(\"otro\")
+    }
+}
+describir({ valor: 0 })
+describir({ valor: 2 })
+describir({ valor: 9 })
+describir({ valor: [1, 2] })
+describir({ valor: { nombre: \"Ana\" } })
+describir({ valor: 500 })
+describir({ valor: 50 })
+";
+    assert_eq!(imprime(programa), "cero\npoco\nvarios\npar 12\nhola Ana\nmucho\notro\n");
+}
+
+#[test]
+fn intentar_atrapar_finalmente() {
+    let programa = "\
+intentar {
+    lanzar { tipo: \"Validacion\", mensaje: \"edad negativa\" }
+} atrapar (e) {
+    imprimir(e.tipo + \": \" + e.mensaje)
+} finalmente {
+    imprimir(\"fin\")
+}
+intentar {
+    entero cero = 0
+    imprimir(1 / cero)
+} atrapar (e) {
+    imprimir(e.tipo + \" \" + e.codigo + \" \" + e.linea.cadena())
+}
+";
+    assert_eq!(imprime(programa), "Validacion: edad negativa\nfin\nDivisionPorCero Q0401 10\n");
+
+    let conversion = "\
+intentar {
+    entero n = \"abc\".entero()
+} atrapar (e) {
+    imprimir(e.tipo + \" \" + e.codigo)
+}
+";
+    assert_eq!(imprime(conversion), "ErrorDeConversion Q0406\n");
+    falla_con("jsn d = \"{\".jsn()\n", "Q0406");
+
+    let salida = ejecutar("lanzar \"sin atrapar\"\n");
+    assert!(!salida.exito);
+    assert_eq!(salida.codigos(), ["Q0404"]);
+}
+
+#[test]
+fn finalmente_tras_retornar() {
+    let programa = "\
+entero f() {
+    intentar {
+        retornar 1
+    } finalmente {
+        imprimir(\"finalmente\")
+    }
+    retornar 2
+}
+imprimir(f())
+";
+    assert_eq!(imprime(programa), "finalmente\n1\n");
+}
+
+#[test]
+fn romper_y_continuar_con_etiqueta() {
+    let programa = "\
+externo: para (entero mut i = 0; i < 3; i++) {
+    para (entero mut j = 0; j < 3; j++) {
+        si (j == 1) {
+            continuar externo
+        }
+        si (i == 2) {
+            romper externo
+        }
+        imprimir(i.cadena() + j.cadena())
+    }
+}
+";
+    assert_eq!(imprime(programa), "00\n10\n");
+}
+
+#[test]
+fn rangos_y_recorridos() {
+    let programa = "\
+para (i en 0..3) {
+    imprimir(i)
+}
+para (i en 6..=0 paso -3) {
+    imprimir(i)
+}
+lista<entero> l = 1..=3
+imprimir(l)
+para (i, c en \"ab\") {
+    imprimir(i.cadena() + c)
+}
+jsn config = { b: 2, a: 1 }
+para (cadena clave, valor en config) {
+    imprimir(clave + valor.cadena())
+}
+";
+    assert_eq!(imprime(programa), "0\n1\n2\n6\n3\n0\n[1, 2, 3]\n0a\n1b\na1\nb2\n");
+    falla_con("para (i en 0..3 paso 0) {\n}\n", "Q0400");
+}
+
+#[test]
+fn rangos_grandes() {
+    // Al recorrerlo, un rango no se guarda entero en memoria
+    assert_eq!(imprime("para (i en 0..100000000 paso 40000000) {\n    imprimir(i)\n}\n"), "0\n40000000\n80000000\n");
+    falla_con("lista<entero> l = 0..100000000\n", "Q0405");
+    falla_con("imprimir((-9223372036854775807..=9223372036854775807).longitud())\n", "Q0405");
+}
+
+#[test]
+fn variables_de_bloque() {
+    let programa = "\
+entero mut total = 0
+entero x = 1
+si (verdadero) {
+    cadena x = \"sombra\"
+    imprimir(x)
+    total = total + 5
+}
+imprimir(x)
+para (e en [1, 2]) {
+    total = total + e
+}
+imprimir(total)
+";
+    assert_eq!(imprime(programa), "sombra\n1\n8\n");
+    falla_con("si (verdadero) {\n    entero x = 1\n}\nimprimir(x)\n", "Q0301");
+    falla_con("para (e en [1]) {\n}\nimprimir(e)\n", "Q0301");
+    falla_con("entero x = 1\nentero x = 2\n", "Q0312");
+    falla_con("para (e en [1, 2]) {\n    entero e = 2\n}\n", "Q0312");
+    let salida = ejecutar_sin_verificar("entero x = 1\ncadena x = \"dos\"\n");
+    assert!(salida.errores.contains("'x' se declaró antes aquí"), "{}", salida.errores);
+}
+
+#[test]
+fn enumeraciones_y_objetos_dentro_de_funciones() {
+    let programa = "\
+enumeracion Estado { Pendiente, Pagado(número) }
+objeto Punto {
+    entero x
+    entero z
+}
+Estado crear(número monto) {
+    retornar Estado.Pagado(monto)
+}
+cadena describir(Estado e) {
+    segun (e) {
+        caso Estado.Pagado(m) => retornar \"pagado \" + m.cadena()
+        otro => retornar \"pendiente\"
+    }
+}
+Punto origen() {
+    retornar nuevo Punto(0, 0)
+}
+imprimir(crear(5))
+imprimir(describir(crear(7)))
+imprimir(describir(Estado.Pendiente))
+imprimir(origen().x)
+";
+    assert_eq!(imprime(programa), "Estado.Pagado(5)\npagado 7\npendiente\n0\n");
+}
+
+#[test]
+fn campos_de_objetos_con_tipos_compuestos() {
+    let programa = "\
+objeto Persona {
+    lista<entero> notas
+    mutable entero edad
+    cadena? apodo
+    cadena mut nombre
+}
+Persona p = nuevo Persona([1], 3, nulo, \"Ana\")
+imprimir(p)
+imprimir(p.notas)
+";
+    assert_eq!(imprime(programa), "Persona { apodo: nulo, edad: 3, nombre: Ana, notas: [1] }\n[1]\n");
+}
+
+#[test]
+fn funciones_llamadas_antes_de_su_definicion() {
+    let programa = "\
+imprimir(f(2))
+si (verdadero) {
+    imprimir(es_par(4))
+    bool es_par(entero n) {
+        retornar n == 0 ? verdadero : es_impar(n - 1)
+    }
+    bool es_impar(entero n) {
+        retornar n == 0 ? falso : es_par(n - 1)
+    }
+}
+entero f(entero n) {
+    retornar n * 10
+}
+";
+    // El verificador y el intérprete aceptan lo mismo
+    assert_eq!(imprime(programa), "20\nverdadero\n");
+    let salida = ejecutar_sin_verificar(programa);
+    assert!(salida.exito, "{}", salida.errores);
+    assert_eq!(salida.salida, "20\nverdadero\n");
+    falla_con("si (verdadero) {\n    vacio f() {\n    }\n}\nf()\n", "Q0302");
+}
@@ -0,0 +1,211 @@
+// Diagnósticos del verificador de tipos: cada prueba verifica un programa
+// con `quetzal verificar` y comprueba los códigos que se informan.
+
+mod comun;
+
+use comun::verificar;
+
+/// Comprueba que el programa se rechace exactamente con estos códigos.
+fn rechaza(programa: &str, esperados: &[&str]) {
+    let salida = verificar(programa);
+    assert!(!salida.exito, "se esperaba un error en:\n{}", programa);
+    assert_eq!(salida.codigos(), esperados, "{}", salida.errores);
+}
+
+fn acepta(programa: &str) {
+    let salida = verificar(programa);
+    assert!(salida.exito, "{}", salida.errores);
+    assert!(salida.advertencias().is_empty(), "{}", salida.errores);
+}
+
+#[test]
+fn errores_lexicos_y_de_sintaxis() {
+    rechaza("cadena s = \"sin cerrar\n", &["Q0102"]);
+    rechaza("entero @ = 1\n", &["Q0101"]);
+    // Se informan todos los errores de sintaxis del archivo
+    rechaza("entero = 1\ncadena = \"a\"\n", &["Q0201", "Q0201"]);
+}
+
+#[test]
+fn informa_todos_los_errores_sin_ejecutar() {
+    let salida = comun::ejecutar("imprimir(\"antes\")\nentero x = \"uno\"\nimprimir(z)\nfaltante()\n");
+    assert!(!salida.exito);
+    assert_eq!(salida.codigos(), ["Q0304", "Q0301", "Q0302"]);
+    assert_eq!(salida.salida, "");
+}
+
+#[test]
+fn argumentos_y_retorno_de_funciones() {
+    let funcion = "entero doble(entero n) {\n    retornar n * 2\n}\n";
+    acepta(&format!("{}imprimir(doble(2))\n", funcion));
+    rechaza(&format!("{}doble(\"dos\")\n", funcion), &["Q0304"]);
+    rechaza(&format!("{}doble(1, 2)\n", funcion), &["Q0305"]);
+    rechaza("entero f() {\n    retornar \"a\"\n}\n", &["Q0304"]);
+    rechaza("vacio f() {\n    retornar 1\n}\n", &["Q0304"]);
+    acepta("número mitad(número x) {\n    retornar x / 2\n}\nimprimir(mitad(5))\n");
+}
+
+#[test]
+fn reasignacion_con_el_tipo_declarado() {
+    rechaza("entero mut x = 1\nx = \"dos\"\n", &["Q0304"]);
+    rechaza("entero mut x = 1\nx += \"dos\"\n", &["Q0304"]);
+    rechaza("cadena mut s = \"a\"\ns++\n", &["Q0304"]);
+    rechaza("para (entero e en [\"a\"]) {\n}\n", &["Q0304"]);
+    // Al reasignar no hay conversiones: solo se amplía de entero a número
+    rechaza("entero mut x = 1\nx = 2.9\n", &["Q0304"]);
+    rechaza("entero mut x = 1\nx += 0.6\n", &["Q0304"]);
+    rechaza("cadena mut s = \"a\"\ns = 5\n", &["Q0304"]);
+    rechaza("jsn mut j = {}\nj = 5\n", &["Q0304"]);
+    rechaza("lista<entero> mut l = [1]\nl[0] = 2.5\n", &["Q0304"]);
+    acepta("número mut n = 1\nn = 2\nn += 1\n");
+}
+
+#[test]
+fn variables_inmutables() {
+    rechaza("entero x = 1\nx = 2\n", &["Q0307"]);
+    rechaza("entero x = 1\nx += 2\n", &["Q0307"]);
+    rechaza("entero x = 1\nx++\n", &["Q0307"]);
+    rechaza("lista<entero> l = []\nl.agregar(1)\n", &["Q0307"]);
+    rechaza("vacio f(entero n) {\n    n = 2\n}\n", &["Q0307"]);
+    // Agregar a un elemento o a una propiedad modifica la variable
+    rechaza("lista<lista<entero>> m = [[1]]\nm[0].agregar(2)\n", &["Q0307"]);
+    rechaza("jsn p = { items: [1] }\np.items.agregar(2)\n", &["Q0307"]);
+    acepta("entero mut x = 1\nx = 2\nmutable lista<entero> l = []\nl.agregar(x)\n");
+}
+
+#[test]
+fn elementos_de_listas_tipadas() {
+    rechaza("lista<entero> l = [1, \"dos\"]\n", &["Q0304"]);
+    rechaza("lista<entero> mut l = []\nl.agregar(\"tres\")\n", &["Q0304"]);
+    rechaza("lista<lista<entero>> mut m = [[1]]\nm[0][0] = \"uno\"\n", &["Q0304"]);
+    rechaza("lista<entero> l = [1]\npara (bool e en l) {\n}\n", &["Q0304"]);
+    acepta("lista<número> mut l = [1, 2.5]\nl.agregar(3)\n");
+    // También al agregar a un elemento de la lista
+    rechaza("lista<lista<entero>> mut m = [[1]]\nm[0].agregar(\"dos\")\n", &["Q0304"]);
+    acepta("lista<lista<número>> mut m = [[1]]\nm[0].agregar(2.5)\njsn mut p = { items: [1] }\np.items.agregar(2)\n");
+}
+
+#[test]
+fn valores_nulos() {
+    rechaza("cadena s = nulo\n", &["Q0308"]);
+    rechaza("cadena? s = nulo\ncadena t = s\n", &["Q0308"]);
+    rechaza("cadena? s = nulo\nimprimir(s.cadena())\n", &["Q0308"]);
+    acepta("cadena? s = nulo\ncadena t = s ?? \"otro\"\nimprimir(s?.cadena())\n");
+}
+
+#[test]
+fn inferencia_con_var() {
+    rechaza("var x = nulo\n", &["Q0309"]);
+    rechaza("var mut x = 1\nx = \"uno\"\n", &["Q0304"]);
+    let salida = comun::quetzal_con(&["verificar", "--tipos"], "var x = 1\nvar l = [\"a\"]\n");
+    assert!(salida.exito, "{}", salida.errores);
+    let lineas = salida.lineas();
+    assert!(lineas[0].ends_with(":1:1: var x: entero"), "{}", lineas[0]);
+    assert!(lineas[1].ends_with(":2:1: var l: lista<cadena>"), "{}", lineas[1]);
+}
+
+#[test]
+fn division_por_cero_literal() {
+    rechaza("imprimir(1 / 0)\n", &["Q0401"]);
+    rechaza("imprimir(1.5 % 0.0)\n", &["Q0401"]);
+    // Dentro de `intentar` el error se puede atrapar: solo se advierte
+    let salida = verificar("entero a = 1\nintentar {\n    imprimir(a / 0)\n} atrapar (e) {\n    imprimir(e)\n}\n");
+    assert!(salida.exito, "{}", salida.errores);
+    assert_eq!(salida.advertencias(), ["Q0401"]);
+    rechaza("intentar {\n} atrapar (e) {\n    imprimir(1 % 0)\n}\n", &["Q0401"]);
+    rechaza("intentar {\n    vacio f() {\n        imprimir(1 / 0)\n    }\n} finalmente {\n}\n", &["Q0401"]);
+}
+
+#[test]
+fn alias_de_tipo() {
+    acepta("tipo Dinero = número\nDinero saldo = 10\n");
+    rechaza("tipo Dinero = número\nDinero saldo = \"mucho\"\n", &["Q0304"]);
+    rechaza("tipo A = B\ntipo B = A\n", &["Q0310"]);
+}
+
+#[test]
+fn enumeraciones() {
+    let estado = "enumeracion Estado { Pendiente, Pagado(número) }\n";
+    acepta(&format!("{}Estado e = Estado.Pagado(5)\n", estado));
+    rechaza(&format!("{}Estado e = Estado.Pagado(\"cinco\")\n", estado), &["Q0304"]);
+    rechaza(&format!("{}Estado e = Estado.Pagado()\n", estado), &["Q0305"]);
+    rechaza(&format!("{}Estado e = Estado.Anulado\n", estado), &["Q0306"]);
+    rechaza("enumeracion Color { Rojo, Rojo }\n", &["Q0304"]);
+}
+
+#[test]
+fn segun_exhaustivo() {
+    let estado = "enumeracion Estado { Pendiente, Pagado(número) }\nEstado e = Estado.Pendiente\n";
+    let incompleto = format!("{}segun (e) {{\n    caso Estado.Pendiente => imprimir(1)\n}}\n", estado);
+    let salida = verificar(&incompleto);
+    assert!(salida.exito, "{}", salida.errores);
+    assert_eq!(salida.advertencias(), ["Q0311"]);
+    assert!(salida.errores.contains("falta Estado.Pagado"), "{}", salida.errores);
+
+    acepta(&format!(
+        "{}segun (e) {{\n    caso Estado.Pendiente => imprimir(1)\n    caso Estado.Pagado(m) => imprimir(m)\n}}\n",
+        estado
+    ));
+    rechaza("entero n = 1\nsegun (n) {\n    caso \"uno\" => imprimir(1)\n    otro => imprimir(2)\n}\n", &["Q0304"]);
+}
+
+#[test]
+fn lanzar_y_atrapar() {
+    rechaza("vacio f() {\n}\nlanzar f()\n", &["Q0304"]);
+    acepta("intentar {\n    lanzar \"error\"\n} atrapar (e) {\n    imprimir(e)\n}\n");
+    // La variable de `atrapar` solo existe en su bloque
+    rechaza("intentar {\n} atrapar (e) {\n}\nimprimir(e)\n", &["Q0301"]);
+}
+
+#[test]
+fn etiquetas_de_bucles() {
+    rechaza("mientras (verdadero) {\n    romper externo\n}\n", &["Q0206"]);
+    rechaza("romper\n", &["Q0204"]);
+    acepta("externo: mientras (verdadero) {\n    mientras (verdadero) {\n        romper externo\n    }\n}\n");
+}
+
+#[test]
+fn rangos_y_recorridos() {
+    rechaza("para (i en 0..2.5) {\n}\n", &["Q0304"]);
+    rechaza("para (i en 10) {\n}\n", &["Q0304"]);
+    rechaza("para (entero c en \"abc\") {\n}\n", &["Q0304"]);
+    acepta("lista<entero> l = 0..3\npara (entero i, cadena c en \"ab\") {\n    imprimir(i)\n}\n");
+}
+
+#[test]
+fn variables_de_bloque() {
+    rechaza("si (verdadero) {\n    entero x = 1\n}\nimprimir(x)\n", &["Q0301"]);
+    rechaza("para (x en [1]) {\n}\nimprimir(x)\n", &["Q0301"]);
+    rechaza("para (entero mut i = 0; i < 2; i++) {\n}\nimprimir(i)\n", &["Q0301"]);
+    acepta("entero x = 1\nsi (verdadero) {\n    cadena x = \"otra\"\n    imprimir(x)\n}\nimprimir(x + 1)\n");
+}
+
+#[test]
+fn enumeraciones_dentro_de_funciones() {
+    acepta("enumeracion Estado { Pendiente, Pagado(número) }\nEstado crear() {\n    retornar Estado.Pagado(5)\n}\n");
+    rechaza(
+        "enumeracion Estado { Pendiente, Pagado(número) }\nEstado crear() {\n    retornar Estado.Pagado(\"cinco\")\n}\n",
+        &["Q0304"],
+    );
+}
+
+#[test]
+fn definiciones_de_bloque() {
+    rechaza("si (verdadero) {\n    vacio f() {\n    }\n}\nf()\n", &["Q0302"]);
+    rechaza("vacio g() {\n    vacio f() {\n    }\n}\nf()\n", &["Q0302"]);
+    rechaza("si (verdadero) {\n    enumeracion Color { Rojo }\n}\nColor c = Color.Rojo\n", &["Q0303", "Q0301"]);
+    acepta("si (verdadero) {\n    vacio f() {\n        imprimir(1)\n    }\n    f()\n}\n");
+}
+
+#[test]
+fn redeclaraciones() {
+    rechaza("entero x = 1\nentero x = 2\n", &["Q0312"]);
+    rechaza("entero x = 1\nvar x = 2\n", &["Q0312"]);
+    rechaza("vacio f(entero n) {\n    cadena n = \"otra\"\n}\n", &["Q0312"]);
+    rechaza("vacio f(entero n, entero n) {\n}\n", &["Q0312"]);
+    rechaza("para (x en [1]) {\n    entero x = 2\n}\n", &["Q0312"]);
+    let salida = verificar("entero x = 1\nentero x = 2\n");
+    assert!(salida.errores.contains("'x' se declaró antes aquí"), "{}", salida.errores);
+    acepta("entero x = 1\nsi (verdadero) {\n    entero x = 2\n    imprimir(x)\n}\n");
+    acepta("jsn d = { v: [1] }\nsegun (d.v) {\n    caso [a], { a: a } => imprimir(a)\n    otro => imprimir(0)\n}\n");
+}